@@ -0,0 +1,1092 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use chrono::Local;
+use rusqlite::Error as SqlError;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    audit::{self, AuditEntry},
+    db::{Bound, DbConn, Field, FieldType, Model, DATE_TIME_FORMAT},
+    dredging::{self, Assessment},
+    duplicate,
+    export::{self, ExportOptions, TextEncoding},
+    forecast::{self, Forecast},
+    import::{self, ImportRow, Issue},
+    inspection_model::InspectionModel,
+    recycle::{self, DeletedRiver},
+    report,
+    security_model::{conflict_message, SecurityModel},
+};
+
+/// Collection name of `SecurityModel` in paths, the name of its view.
+pub const RIVERS: &str = "water_security";
+/// Collection name of the changes to rivers in paths.
+pub const AUDIT: &str = "audit";
+/// Collection name of the deleted rivers in paths, the name of their view.
+pub const RECYCLE_BIN: &str = recycle::RECYCLE_VIEW;
+
+/// An HTTP request as the handlers see it, independent of the server.
+pub struct Request {
+    pub method: String,
+    /// Path without the query string, e.g. `/api/water_security/3`.
+    pub path: String,
+    /// Decoded query parameters in order.
+    pub query: Vec<(String, String)>,
+    /// Media type of the body, without parameters such as the charset.
+    pub content_type: String,
+    pub body: String,
+}
+
+impl Request {
+    /// Request for `url`, a path with an optional query string.
+    pub fn new(method: &str, url: &str, content_type: &str, body: &str) -> Self {
+        let (path, query) = match url.find('?') {
+            Some(index) => (&url[..index], &url[index + 1..]),
+            None => (url, ""),
+        };
+        Request {
+            method: method.to_uppercase(),
+            path: decode(path, false),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| match pair.find('=') {
+                    Some(index) => (
+                        decode(&pair[..index], true),
+                        decode(&pair[index + 1..], true),
+                    ),
+                    None => (decode(pair, true), String::new()),
+                })
+                .collect(),
+            content_type: content_type
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase(),
+            body: String::from(body),
+        }
+    }
+
+    /// Last value of query parameter `name`, unless blank.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// Percent-decodes `text`, reading `+` as a space in query strings.
+fn decode(text: &str, query: bool) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len() => {
+                let byte = std::str::from_utf8(&bytes[index + 1..index + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        index += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' if query => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &Value) -> Self {
+        Response {
+            status,
+            headers: vec![(
+                "Content-Type",
+                String::from("application/json; charset=utf-8"),
+            )],
+            body: serde_json::to_vec_pretty(value).unwrap_or_default(),
+        }
+    }
+
+    /// `{"error": message}` with `status`.
+    pub fn error(status: u16, message: &str) -> Self {
+        Response::json(status, &json!({ "error": message }))
+    }
+
+    fn empty(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+/// A failed request, answered with the response it carries.
+type Handled = Result<Response, Response>;
+
+fn bad_request(message: String) -> Response {
+    Response::error(400, &message)
+}
+
+fn server_error<E: ToString>(error: E) -> Response {
+    Response::error(500, &error.to_string())
+}
+
+/// 422 listing every problem with the submitted river.
+fn invalid(issues: Vec<Value>) -> Response {
+    Response::json(422, &json!({ "error": "河道数据有误", "issues": issues }))
+}
+
+fn issue_json(issue: &Issue) -> Value {
+    json!({
+        "column": issue.column,
+        "message": issue.message,
+        "severity": issue.severity.name(),
+    })
+}
+
+/// Methods served at `segments`, empty for unknown paths.
+fn allowed_methods(segments: &[&str]) -> &'static str {
+    match segments {
+        ["openapi.json"] => "GET",
+        ["api", RIVERS] => "GET, POST",
+        ["api", RIVERS, "calculate"] => "POST",
+        ["api", RIVERS, "export"] | ["api", RIVERS, "duplicates"] => "GET",
+        ["api", RIVERS, "duplicates", "merge"] => "POST",
+        ["api", RIVERS, _] => "GET, PUT, PATCH, DELETE",
+        ["api", RIVERS, _, "inspections"]
+        | ["api", RIVERS, _, "assessment"]
+        | ["api", RIVERS, _, "forecast"] => "GET",
+        ["api", AUDIT] => "GET",
+        ["api", AUDIT, _, "restore"] => "POST",
+        ["api", RECYCLE_BIN] => "GET",
+        ["api", RECYCLE_BIN, _] => "DELETE",
+        ["api", RECYCLE_BIN, _, "restore"] => "POST",
+        _ => "",
+    }
+}
+
+/// Answers `request` from the database behind `conn`.
+pub fn handle(conn: &mut DbConn<SecurityModel>, request: &Request) -> Response {
+    let segments: Vec<&str> = request
+        .path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let allowed = allowed_methods(&segments);
+    if allowed.is_empty() {
+        return Response::error(404, &format!("找不到{}", request.path));
+    }
+    if request.method == "OPTIONS" {
+        return Response::empty(204).with_header("Allow", String::from(allowed));
+    }
+    if !allowed.split(", ").any(|method| method == request.method) {
+        return Response::error(405, &format!("{}仅支持{}", request.path, allowed))
+            .with_header("Allow", String::from(allowed));
+    }
+    if ["POST", "PUT", "PATCH"].contains(&request.method.as_str())
+        && request.content_type != "application/json"
+    {
+        return Response::error(415, "请求内容须为application/json");
+    }
+
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["openapi.json"]) => Ok(Response::json(200, &openapi())),
+        ("GET", ["api", RIVERS]) => list(conn, request),
+        ("POST", ["api", RIVERS]) => create(conn, request),
+        ("POST", ["api", RIVERS, "calculate"]) => calculate(request),
+        ("GET", ["api", RIVERS, "export"]) => export_file(conn, request),
+        ("GET", ["api", RIVERS, "duplicates"]) => duplicates(conn),
+        ("POST", ["api", RIVERS, "duplicates", "merge"]) => merge(conn, request),
+        ("GET", ["api", RIVERS, id]) => {
+            find(conn, id).map(|model| Response::json(200, &model.to_json()))
+        }
+        ("PUT", ["api", RIVERS, id]) | ("PATCH", ["api", RIVERS, id]) => update(conn, id, request),
+        ("DELETE", ["api", RIVERS, id]) => delete(conn, id),
+        ("GET", ["api", RIVERS, id, "inspections"]) => inspections(conn, id),
+        ("GET", ["api", RIVERS, id, "assessment"]) => find(conn, id)
+            .map(|model| Response::json(200, &assessment_json(&dredging::assess(&model)))),
+        ("GET", ["api", RIVERS, id, "forecast"]) => forecast(conn, id),
+        ("GET", ["api", AUDIT]) => audit_entries(conn, request),
+        ("POST", ["api", AUDIT, id, "restore"]) => restore(conn, id),
+        ("GET", ["api", RECYCLE_BIN]) => deleted_rivers(conn),
+        ("POST", ["api", RECYCLE_BIN, id, "restore"]) => undelete(conn, id),
+        ("DELETE", ["api", RECYCLE_BIN, id]) => purge(conn, id),
+        _ => Err(Response::error(404, &format!("找不到{}", request.path))),
+    };
+    *conn.model = None;
+    result.unwrap_or_else(|response| response)
+}
+
+/// `value` as stored and exported, without the noise of widening to f64.
+fn number(value: f32) -> Value {
+    Value::from(value.to_string().parse::<f64>().unwrap_or_default())
+}
+
+fn assessment_json(assessment: &Assessment) -> Value {
+    json!({
+        "channel_width": number(assessment.channel_width),
+        "threshold": number(assessment.threshold),
+        "dredging": assessment.dredging,
+        "steps": assessment
+            .steps
+            .iter()
+            .map(|step| json!({
+                "label": step.label,
+                "formula": step.formula,
+                "result": step.result,
+            }))
+            .collect::<Vec<Value>>(),
+    })
+}
+
+fn forecast_json(forecast: &Forecast) -> Value {
+    json!({
+        "curve": forecast.fit.curve.name(),
+        "samples": forecast.fit.samples,
+        "threshold": forecast.threshold,
+        "latest_depth": forecast.latest_depth,
+        "rate": forecast.rate,
+        "year": forecast.year,
+        "year_text": forecast.year_text(),
+        "interval": forecast.interval_text(),
+        "exceeded": forecast.exceeded(),
+    })
+}
+
+fn find(conn: &DbConn<SecurityModel>, id: &str) -> Result<SecurityModel, Response> {
+    let not_found = || Response::error(404, &format!("找不到编号为{}的河道", id));
+    let id = id.parse::<u32>().map_err(|_| not_found())?;
+    conn.find_by_id(id).map_err(|error| match error {
+        SqlError::QueryReturnedNoRows => not_found(),
+        error => server_error(error),
+    })
+}
+
+type Setter = fn(&mut ExportOptions, &str) -> Result<(), String>;
+
+/// Filters, columns and sort of a list or export, named as the options of
+/// `wrs-cli export`.
+fn export_options(request: &Request) -> Result<ExportOptions, Response> {
+    let mut options = ExportOptions::default();
+    if let Some(keyword) = request.param("q") {
+        options.set_keyword(keyword);
+    }
+    if let Some(areas) = request.param("area") {
+        options.set_areas(areas);
+    }
+    let setters: [(&str, Setter); 6] = [
+        ("level", ExportOptions::set_levels),
+        ("dredging", ExportOptions::set_dredging),
+        ("from", ExportOptions::set_since),
+        ("to", ExportOptions::set_until),
+        ("columns", ExportOptions::set_columns),
+        ("sort", ExportOptions::set_sort),
+    ];
+    for (name, set) in setters.iter() {
+        if let Some(value) = request.param(name) {
+            set(&mut options, value).map_err(bad_request)?;
+        }
+    }
+    Ok(options)
+}
+
+fn number_param(request: &Request, name: &str) -> Result<Option<usize>, Response> {
+    request
+        .param(name)
+        .map(|value| {
+            value
+                .parse::<usize>()
+                .map_err(|_| bad_request(format!("{}须为非负整数：{}", name, value)))
+        })
+        .transpose()
+}
+
+fn list(conn: &DbConn<SecurityModel>, request: &Request) -> Handled {
+    let options = export_options(request)?;
+    let models = options.rivers(conn).map_err(server_error)?;
+    let offset = number_param(request, "offset")?.unwrap_or(0);
+    let limit = number_param(request, "limit")?.unwrap_or(usize::MAX);
+    let items: Vec<Value> = models
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(SecurityModel::to_json)
+        .collect();
+    Ok(Response::json(
+        200,
+        &json!({ "total": models.len(), "offset": offset, "items": items }),
+    ))
+}
+
+/// Parses the body as one river, checking only the fields it gives.
+fn parse_body(request: &Request) -> Result<ImportRow<SecurityModel>, Response> {
+    let table = import::parse_json(&request.body).map_err(bad_request)?;
+    if table.rows.len() != 1 {
+        return Err(bad_request(String::from("请求内容应为一个河道对象")));
+    }
+    let fields = SecurityModel::fields();
+    let unknown: Vec<Value> = table
+        .headers
+        .iter()
+        .filter(|header| !fields.iter().any(|field| field.matches(header)))
+        .map(|header| json!({ "column": header, "message": "无法识别的字段", "severity": "错误" }))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(invalid(unknown));
+    }
+    let row = import::preview::<SecurityModel>(&table, None)
+        .rows
+        .pop()
+        .ok_or_else(|| bad_request(String::from("请求内容中没有字段")))?;
+    if !row.issues.is_empty() {
+        return Err(invalid(row.issues.iter().map(issue_json).collect()));
+    }
+    Ok(row)
+}
+
+/// 422 unless `model` is valid as a whole.
+fn check(model: &SecurityModel) -> Result<(), Response> {
+    let errors = SecurityModel::validate(model);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let fields = SecurityModel::fields();
+    Err(invalid(
+        errors
+            .into_iter()
+            .map(|(column, message)| {
+                let header = fields
+                    .iter()
+                    .find(|field| field.column == column)
+                    .map(|field| field.header())
+                    .unwrap_or_default();
+                json!({ "column": header, "message": message, "severity": "错误" })
+            })
+            .collect(),
+    ))
+}
+
+/// Stored river other than `id` with the name, jurisdiction and ends of
+/// `model`.
+fn same_river(conn: &DbConn<SecurityModel>, model: &SecurityModel, id: u32) -> Option<u32> {
+    model.find_same(&conn.instance, id).ok().flatten()
+}
+
+fn conflict(id: u32) -> Response {
+    Response::error(409, &conflict_message(id))
+}
+
+fn create(conn: &mut DbConn<SecurityModel>, request: &Request) -> Handled {
+    let mut model = parse_body(request)?.model;
+    model.id = 0;
+    model.recompute();
+    check(&model)?;
+    if let Some(id) = same_river(conn, &model, 0) {
+        return Err(conflict(id));
+    }
+    conn.set(model.clone());
+    conn.insert().map_err(server_error)?;
+    let created = same_river(conn, &model, 0)
+        .ok_or_else(|| server_error("找不到新增的河道"))
+        .and_then(|id| conn.find_by_id(id).map_err(server_error))?;
+    Ok(Response::json(201, &created.to_json())
+        .with_header("Location", format!("/api/{}/{}", RIVERS, created.id)))
+}
+
+/// Changes the fields given in the body and keeps the others. Like an
+/// import, a change without a time counts as measured now.
+fn update(conn: &mut DbConn<SecurityModel>, id: &str, request: &Request) -> Handled {
+    let existing = find(conn, id)?;
+    let row = parse_body(request)?;
+    let mut merged = import::merge(&existing, &row.model, &row.provided, false);
+    merged.recompute();
+    if import::diff(&existing, &merged).is_empty() {
+        return Ok(Response::json(200, &existing.to_json()));
+    }
+    if !row.provided.contains(&"time") {
+        merged.time = Local::now();
+    }
+    check(&merged)?;
+    if let Some(other) = same_river(conn, &merged, existing.id) {
+        return Err(conflict(other));
+    }
+    conn.set(merged);
+    conn.update().map_err(server_error)?;
+    let updated = conn.find_by_id(existing.id).map_err(server_error)?;
+    Ok(Response::json(200, &updated.to_json()))
+}
+
+fn delete(conn: &mut DbConn<SecurityModel>, id: &str) -> Handled {
+    let existing = find(conn, id)?;
+    conn.set(existing);
+    conn.delete().map_err(server_error)?;
+    Ok(Response::empty(204))
+}
+
+fn inspections(conn: &DbConn<SecurityModel>, id: &str) -> Handled {
+    let model = find(conn, id)?;
+    let fields = InspectionModel::fields();
+    let inspections = model
+        .inspections(&conn.instance)
+        .map_err(server_error)?
+        .iter()
+        .map(|inspection| export::json_record(&fields, inspection))
+        .collect::<Vec<Value>>();
+    Ok(Response::json(200, &Value::from(inspections)))
+}
+
+fn forecast(conn: &DbConn<SecurityModel>, id: &str) -> Handled {
+    let model = find(conn, id)?;
+    let forecast = forecast::forecast_river(&conn.instance, &model).map_err(server_error)?;
+    Ok(Response::json(
+        200,
+        &forecast.as_ref().map_or(Value::Null, forecast_json),
+    ))
+}
+
+fn audit_json(entry: &AuditEntry) -> Value {
+    json!({
+        "id": entry.id,
+        "time": entry.time.format(DATE_TIME_FORMAT).to_string(),
+        "user": entry.user,
+        "source": entry.source,
+        "action": entry.action.code(),
+        "river_id": entry.river_id,
+        "name": entry.name(),
+        "old": entry.old,
+        "new": entry.new,
+    })
+}
+
+fn audit_entries(conn: &DbConn<SecurityModel>, request: &Request) -> Handled {
+    let river_id = request
+        .param("river")
+        .map(|id| {
+            id.parse::<u32>()
+                .map_err(|_| bad_request(format!("river须为河道编号：{}", id)))
+        })
+        .transpose()?;
+    let since =
+        export::parse_since(request.param("from").unwrap_or_default()).map_err(bad_request)?;
+    let before =
+        export::parse_until(request.param("to").unwrap_or_default()).map_err(bad_request)?;
+    let entries = audit::entries(&conn.instance, river_id, since, before)
+        .map_err(server_error)?
+        .iter()
+        .map(audit_json)
+        .collect::<Vec<Value>>();
+    Ok(Response::json(200, &Value::from(entries)))
+}
+
+fn restore(conn: &mut DbConn<SecurityModel>, id: &str) -> Handled {
+    let not_found = || Response::error(404, &format!("找不到第{}条修改记录", id));
+    let id = id.parse::<u32>().map_err(|_| not_found())?;
+    let entry = audit::find(&conn.instance, id)
+        .map_err(server_error)?
+        .ok_or_else(not_found)?;
+    if entry.old.is_none() {
+        return Err(Response::error(
+            409,
+            &format!("第{}条修改记录之前没有{}的数据", id, entry.name()),
+        ));
+    }
+    let old = entry
+        .old_river()
+        .map_err(|message| Response::error(422, &message))?;
+    if let Some(other) = same_river(conn, &old, old.id) {
+        return Err(conflict(other));
+    }
+    let model = audit::restore(conn, id).map_err(server_error)?;
+    Ok(Response::json(200, &model.to_json()))
+}
+
+fn deleted_rivers(conn: &DbConn<SecurityModel>) -> Handled {
+    let rivers = recycle::deleted_rivers(&conn.instance)
+        .map_err(server_error)?
+        .iter()
+        .map(|river| {
+            let mut value = river.model.to_json();
+            value["deleted"] = Value::from(river.deleted.format(DATE_TIME_FORMAT).to_string());
+            value
+        })
+        .collect::<Vec<Value>>();
+    Ok(Response::json(200, &Value::from(rivers)))
+}
+
+fn duplicates(conn: &DbConn<SecurityModel>) -> Handled {
+    let groups = duplicate::groups(&conn.instance)
+        .map_err(server_error)?
+        .iter()
+        .map(|group| {
+            json!({
+                "similarity": group.similarity.code(),
+                "reason": group.similarity.reason(),
+                "rivers": group.rivers.iter().map(SecurityModel::to_json).collect::<Vec<Value>>(),
+            })
+        })
+        .collect::<Vec<Value>>();
+    let enforced = duplicate::is_enforced(&conn.instance).map_err(server_error)?;
+    Ok(Response::json(
+        200,
+        &json!({ "enforced": enforced, "groups": groups }),
+    ))
+}
+
+/// Merges the rivers listed in `merge` into river `keep`, see
+/// `duplicate::merge`.
+fn merge(conn: &mut DbConn<SecurityModel>, request: &Request) -> Handled {
+    let body: Value = serde_json::from_str(&request.body)
+        .map_err(|error| bad_request(format!("请求内容不是有效的JSON：{}", error)))?;
+    let id = |value: &Value| value.as_u64().map(|id| id as u32);
+    let keep =
+        id(&body["keep"]).ok_or_else(|| bad_request(String::from("请指定保留的河道编号keep")))?;
+    let others = body["merge"]
+        .as_array()
+        .and_then(|ids| ids.iter().map(id).collect::<Option<Vec<u32>>>())
+        .filter(|ids| !ids.is_empty())
+        .ok_or_else(|| bad_request(String::from("请以数组merge指定并入的河道编号")))?;
+    for id in std::iter::once(&keep).chain(others.iter()) {
+        find(conn, &id.to_string())?;
+    }
+    let enforced =
+        duplicate::merge(conn, keep, &others).map_err(|error| Response::error(422, &error))?;
+    let kept = conn.find_by_id(keep).map_err(server_error)?;
+    Ok(Response::json(
+        200,
+        &json!({ "river": kept.to_json(), "enforced": enforced }),
+    ))
+}
+
+fn find_deleted(conn: &DbConn<SecurityModel>, id: &str) -> Result<DeletedRiver, Response> {
+    let not_found = || Response::error(404, &format!("回收站中没有编号为{}的河道", id));
+    let id = id.parse::<u32>().map_err(|_| not_found())?;
+    recycle::find(&conn.instance, id)
+        .map_err(server_error)?
+        .ok_or_else(not_found)
+}
+
+fn undelete(conn: &mut DbConn<SecurityModel>, id: &str) -> Handled {
+    let river = find_deleted(conn, id)?;
+    if let Some(other) = same_river(conn, &river.model, river.model.id) {
+        return Err(conflict(other));
+    }
+    let model = recycle::restore(conn, river.model.id).map_err(server_error)?;
+    Ok(Response::json(200, &model.to_json()))
+}
+
+fn purge(conn: &mut DbConn<SecurityModel>, id: &str) -> Handled {
+    let river = find_deleted(conn, id)?;
+    recycle::purge(&conn.instance, river.model.id).map_err(server_error)?;
+    Ok(Response::empty(204))
+}
+
+/// Channel width, threshold and verdict of the inputs in the body, without
+/// storing anything.
+fn calculate(request: &Request) -> Handled {
+    let model = parse_body(request)?.model;
+    Ok(Response::json(
+        200,
+        &assessment_json(&dredging::assess(&model)),
+    ))
+}
+
+/// Scratch file for one export, removed once read.
+fn scratch_path(extension: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    env::temp_dir().join(format!(
+        "wrs-server-{}-{}.{}",
+        process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
+
+fn export_file(conn: &DbConn<SecurityModel>, request: &Request) -> Handled {
+    let options = export_options(request)?;
+    let format = request.param("format").unwrap_or("xlsx").to_lowercase();
+    let encoding = match request.param("encoding") {
+        Some(encoding) => TextEncoding::parse(encoding)
+            .ok_or_else(|| bad_request(format!("不支持的编码：{}", encoding)))?,
+        None => TextEncoding::Utf8,
+    };
+    let (extension, content_type) = match format.as_str() {
+        "xlsx" | "report" => (
+            "xlsx",
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ),
+        "csv" if encoding == TextEncoding::Gbk => ("csv", "text/csv; charset=gbk"),
+        "csv" => ("csv", "text/csv; charset=utf-8"),
+        "json" => ("json", "application/json; charset=utf-8"),
+        "ndjson" => ("ndjson", "application/x-ndjson; charset=utf-8"),
+        _ => return Err(bad_request(format!("不支持的导出格式：{}", format))),
+    };
+
+    let path = scratch_path(extension);
+    let written = if format == "report" {
+        report::write_report(conn, &path, &options)
+    } else {
+        export::write_file(conn, &path, encoding, &options)
+    };
+    let body = written.and_then(|_| fs::read(&path).map_err(|error| error.to_string()));
+    let _ = fs::remove_file(&path);
+    let name = if format == "report" {
+        format!("{}-report.{}", RIVERS, extension)
+    } else {
+        format!("{}.{}", RIVERS, extension)
+    };
+    Ok(Response {
+        status: 200,
+        headers: vec![
+            ("Content-Type", String::from(content_type)),
+            (
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", name),
+            ),
+        ],
+        body: body.map_err(server_error)?,
+    })
+}
+
+/// Schema of one field, with its header as the description. Numbers written
+/// with a label, such as levels, may be either, as in exports.
+fn property<T: Model + Default + Clone>(field: &Field<T>) -> Value {
+    let sample = T::default();
+    let mut schema = match field.field_type {
+        FieldType::Integer | FieldType::Real
+            if (field.display)(&sample) != (field.formatter)(&sample) =>
+        {
+            json!({ "oneOf": [{ "type": "string" }, { "type": "number" }] })
+        }
+        FieldType::Integer => json!({ "type": "integer" }),
+        FieldType::Real => json!({ "type": "number" }),
+        FieldType::Text => json!({ "type": "string" }),
+        FieldType::DateTime => json!({ "type": "string", "example": "2024-05-01 09:30:00" }),
+    };
+    schema["description"] = Value::from(field.header());
+    for (bound, name, exclusive) in [
+        (field.min, "minimum", "exclusiveMinimum"),
+        (field.max, "maximum", "exclusiveMaximum"),
+    ]
+    .iter()
+    {
+        match bound {
+            Some(Bound::Inclusive(value)) => schema[*name] = Value::from(*value),
+            Some(Bound::Exclusive(value)) => {
+                schema[*name] = Value::from(*value);
+                schema[*exclusive] = Value::from(true);
+            }
+            None => {}
+        }
+    }
+    schema
+}
+
+fn river_schema() -> Value {
+    let fields = SecurityModel::fields();
+    let mut properties = Map::new();
+    for field in fields.iter() {
+        let mut schema = property(field);
+        if field.column == "id" || dredging::DERIVED_COLUMNS.contains(&field.column) {
+            schema["description"] =
+                Value::from(format!("{}，按输入数据计算，写入时忽略", field.header()));
+            schema["readOnly"] = Value::from(true);
+        }
+        properties.insert(String::from(field.column), schema);
+    }
+    let required: Vec<&str> = fields
+        .iter()
+        .filter(|field| field.required)
+        .map(|field| field.column)
+        .collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn inspection_schema() -> Value {
+    let properties: Map<String, Value> = InspectionModel::fields()
+        .iter()
+        .map(|field| (String::from(field.column), property(field)))
+        .collect();
+    json!({ "type": "object", "properties": properties })
+}
+
+fn query(name: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "query", "required": false, "schema": { "type": "string" }, "description": description })
+}
+
+fn content(schema: &str) -> Value {
+    json!({ "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", schema) } } })
+}
+
+fn reply(description: &str, schema: &str) -> Value {
+    json!({ "description": description, "content": content(schema) })
+}
+
+/// OpenAPI 3.0 description of the endpoints, generated from the field
+/// metadata so that it always lists the current columns.
+pub fn openapi() -> Value {
+    let filters = vec![
+        query(
+            "q",
+            "关键字，按河道名称、辖区、起点、终点和清淤判断检索，多个词以空格分隔；\
+             字母也按名称和辖区的拼音全拼或首字母匹配，如qhh",
+        ),
+        query("area", "辖区，可用逗号分隔多个，按包含匹配"),
+        query("level", "防洪排涝等级，如1,第二级"),
+        query("dredging", "清淤判断：needed、suggested或none，可多个"),
+        query("from", "最近一次录入时间不早于该日期"),
+        query("to", "最近一次录入时间不晚于该日期（含当天）"),
+        query("sort", "排序列，如area,depth:desc"),
+    ];
+    let id = json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" }, "description": "河道编号" });
+    let mut list_parameters = filters.clone();
+    list_parameters.push(
+        json!({ "name": "offset", "in": "query", "schema": { "type": "integer", "minimum": 0 } }),
+    );
+    list_parameters.push(
+        json!({ "name": "limit", "in": "query", "schema": { "type": "integer", "minimum": 0 } }),
+    );
+    let mut export_parameters = filters;
+    export_parameters.push(json!({ "name": "format", "in": "query", "schema": { "type": "string", "enum": ["xlsx", "csv", "json", "ndjson", "report"], "default": "xlsx" }, "description": "report为带格式和汇总表的报表" }));
+    export_parameters.push(json!({ "name": "encoding", "in": "query", "schema": { "type": "string", "enum": ["utf8", "gbk"], "default": "utf8" }, "description": "csv的编码" }));
+    export_parameters.push(query("columns", "导出的列及顺序，默认全部列"));
+    let duplicates_schema = json!({
+        "type": "object",
+        "properties": {
+            "enforced": { "type": "boolean", "description": "河道唯一约束是否已生效" },
+            "groups": { "type": "array", "items": { "$ref": "#/components/schemas/DuplicateGroup" } },
+        },
+    });
+    let merge_body = json!({
+        "required": true,
+        "content": { "application/json": { "schema": {
+            "type": "object",
+            "required": ["keep", "merge"],
+            "properties": {
+                "keep": { "type": "integer", "description": "保留的河道编号" },
+                "merge": { "type": "array", "items": { "type": "integer" }, "description": "并入的河道编号" },
+            },
+        } } },
+    });
+    let merged_schema = json!({
+        "type": "object",
+        "properties": {
+            "river": { "$ref": "#/components/schemas/River" },
+            "enforced": { "type": "boolean" },
+        },
+    });
+    let errors = json!({
+        "400": reply("参数有误", "Error"),
+        "404": reply("找不到河道", "Error"),
+        "422": reply("河道数据有误", "Invalid"),
+    });
+    let body = json!({ "required": true, "content": content("River") });
+    let audit_parameters = vec![
+        json!({ "name": "river", "in": "query", "schema": { "type": "integer" }, "description": "河道编号，不指定时列出全部河道" }),
+        query("from", "修改时间不早于该日期"),
+        query("to", "修改时间不晚于该日期（含当天）"),
+    ];
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "水安全数据接口",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "读写water-resources.db中的河道数据。字段名为数据库列名，写入时也可使用中文表头；等级和是否允许浪爬高以文字输出，写入时文字和数字均可。",
+        },
+        "servers": [{ "url": "/" }],
+        "paths": {
+            format!("/api/{}", RIVERS): {
+                "get": {
+                    "summary": "按条件查询河道",
+                    "parameters": list_parameters,
+                    "responses": { "200": reply("河道列表", "RiverList"), "400": errors["400"] },
+                },
+                "post": {
+                    "summary": "新增河道，河槽宽度、淤积阈值和清淤判断按输入数据计算",
+                    "requestBody": body,
+                    "responses": {
+                        "201": reply("新增的河道", "River"),
+                        "409": reply("已有名称、辖区、起点和终点都相同的河道", "Error"),
+                        "422": errors["422"],
+                    },
+                },
+            },
+            format!("/api/{}/{{id}}", RIVERS): {
+                "parameters": [id],
+                "get": {
+                    "summary": "查询一条河道",
+                    "responses": { "200": reply("河道", "River"), "404": errors["404"] },
+                },
+                "put": {
+                    "summary": "修改河道，只修改请求中给出的字段；未给出录入时间时按当前时间记录巡查",
+                    "requestBody": body,
+                    "responses": {
+                        "200": reply("修改后的河道", "River"),
+                        "404": errors["404"],
+                        "409": reply("已有名称、辖区、起点和终点都相同的河道", "Error"),
+                        "422": errors["422"],
+                    },
+                },
+                "patch": {
+                    "summary": "同PUT",
+                    "requestBody": body,
+                    "responses": { "200": reply("修改后的河道", "River"), "404": errors["404"], "422": errors["422"] },
+                },
+                "delete": {
+                    "summary": "删除河道，移入回收站，可恢复",
+                    "responses": { "204": { "description": "已删除" }, "404": errors["404"] },
+                },
+            },
+            format!("/api/{}/{{id}}/inspections", RIVERS): {
+                "parameters": [id],
+                "get": {
+                    "summary": "河道的全部巡查记录，按时间排序",
+                    "responses": {
+                        "200": {
+                            "description": "巡查记录",
+                            "content": { "application/json": { "schema": { "type": "array", "items": { "$ref": "#/components/schemas/Inspection" } } } },
+                        },
+                        "404": errors["404"],
+                    },
+                },
+            },
+            format!("/api/{}/{{id}}/assessment", RIVERS): {
+                "parameters": [id],
+                "get": {
+                    "summary": "按当前数据重新计算的清淤评估及计算过程",
+                    "responses": { "200": reply("评估结果", "Assessment"), "404": errors["404"] },
+                },
+            },
+            format!("/api/{}/{{id}}/forecast", RIVERS): {
+                "parameters": [id],
+                "get": {
+                    "summary": "按巡查记录预测淤积深度达到阈值的年份，巡查记录不足时为null",
+                    "responses": { "200": reply("预测结果", "Forecast"), "404": errors["404"] },
+                },
+            },
+            format!("/api/{}/calculate", RIVERS): {
+                "post": {
+                    "summary": "按请求中的输入数据计算清淤评估，不保存",
+                    "requestBody": body,
+                    "responses": { "200": reply("评估结果", "Assessment"), "422": errors["422"] },
+                },
+            },
+            format!("/api/{}", AUDIT): {
+                "get": {
+                    "summary": "河道的新增、修改、删除和导入记录，按时间排序",
+                    "parameters": audit_parameters,
+                    "responses": {
+                        "200": {
+                            "description": "修改记录",
+                            "content": { "application/json": { "schema": { "type": "array", "items": { "$ref": "#/components/schemas/AuditEntry" } } } },
+                        },
+                        "400": errors["400"],
+                    },
+                },
+            },
+            format!("/api/{}/{{id}}/restore", AUDIT): {
+                "parameters": [{ "name": "id", "in": "path", "required": true, "schema": { "type": "integer" }, "description": "修改记录的记录号" }],
+                "post": {
+                    "summary": "将河道恢复为该条修改记录之前的版本，已删除的河道按原编号恢复",
+                    "responses": {
+                        "200": reply("恢复后的河道", "River"),
+                        "404": reply("找不到修改记录", "Error"),
+                        "409": reply("该记录之前没有河道的数据，如新增；或已有名称、辖区、起点和终点都相同的河道", "Error"),
+                        "422": reply("该记录中的河道无法读取", "Error"),
+                    },
+                },
+            },
+            format!("/api/{}", RECYCLE_BIN): {
+                "get": {
+                    "summary": "回收站中已删除的河道，最近删除的在前",
+                    "responses": {
+                        "200": {
+                            "description": "已删除的河道",
+                            "content": { "application/json": { "schema": { "type": "array", "items": { "$ref": "#/components/schemas/DeletedRiver" } } } },
+                        },
+                    },
+                },
+            },
+            format!("/api/{}/{{id}}", RECYCLE_BIN): {
+                "parameters": [id],
+                "delete": {
+                    "summary": "永久删除回收站中的河道及其巡查记录，无法从回收站恢复",
+                    "responses": { "204": { "description": "已永久删除" }, "404": reply("回收站中没有该河道", "Error") },
+                },
+            },
+            format!("/api/{}/{{id}}/restore", RECYCLE_BIN): {
+                "parameters": [id],
+                "post": {
+                    "summary": "从回收站恢复河道及其巡查记录",
+                    "responses": {
+                        "200": reply("恢复的河道", "River"),
+                        "404": reply("回收站中没有该河道", "Error"),
+                        "409": reply("已有名称、辖区、起点和终点都相同的河道", "Error"),
+                    },
+                },
+            },
+            format!("/api/{}/duplicates", RIVERS): {
+                "get": {
+                    "summary": "疑似重复录入的河道：名称、辖区、起点和终点完全相同或仅写法不同，或同名同辖区的河段重叠",
+                    "responses": {
+                        "200": {
+                            "description": "疑似重复的河道分组；存在完全相同的河道时enforced为false，唯一约束在合并或删除后生效",
+                            "content": { "application/json": { "schema": duplicates_schema } },
+                        },
+                    },
+                },
+            },
+            format!("/api/{}/duplicates/merge", RIVERS): {
+                "post": {
+                    "summary": "将重复的河道并入保留的河道：补充其缺少的巡查记录，其余河道移入回收站",
+                    "requestBody": merge_body,
+                    "responses": {
+                        "200": {
+                            "description": "合并后的河道及河道唯一约束是否已生效",
+                            "content": { "application/json": { "schema": merged_schema } },
+                        },
+                        "400": errors["400"],
+                        "404": reply("找不到河道", "Error"),
+                        "422": reply("河道名称或辖区不同，不能合并", "Error"),
+                    },
+                },
+            },
+            format!("/api/{}/export", RIVERS): {
+                "get": {
+                    "summary": "按条件导出河道文件，与wrs-cli export相同",
+                    "parameters": export_parameters,
+                    "responses": {
+                        "200": { "description": "导出的文件" },
+                        "400": errors["400"],
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "River": river_schema(),
+                "Inspection": inspection_schema(),
+                "RiverList": {
+                    "type": "object",
+                    "properties": {
+                        "total": { "type": "integer", "description": "符合条件的河道数" },
+                        "offset": { "type": "integer" },
+                        "items": { "type": "array", "items": { "$ref": "#/components/schemas/River" } },
+                    },
+                },
+                "Assessment": {
+                    "type": "object",
+                    "properties": {
+                        "channel_width": { "type": "number" },
+                        "threshold": { "type": "number" },
+                        "dredging": { "type": "string" },
+                        "steps": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "label": { "type": "string" },
+                                    "formula": { "type": "string" },
+                                    "result": { "type": "string" },
+                                },
+                            },
+                        },
+                    },
+                },
+                "Forecast": {
+                    "type": "object",
+                    "nullable": true,
+                    "properties": {
+                        "curve": { "type": "string" },
+                        "samples": { "type": "integer" },
+                        "threshold": { "type": "number" },
+                        "latest_depth": { "type": "number" },
+                        "rate": { "type": "number", "description": "最近一次巡查时的淤积速率(m/年)" },
+                        "year": { "type": "number", "nullable": true },
+                        "year_text": { "type": "string" },
+                        "interval": { "type": "string", "description": "95%置信区间" },
+                        "exceeded": { "type": "boolean" },
+                    },
+                },
+                "DeletedRiver": {
+                    "allOf": [
+                        { "$ref": "#/components/schemas/River" },
+                        { "type": "object", "properties": { "deleted": { "type": "string", "description": "删除时间" } } },
+                    ],
+                },
+                "DuplicateGroup": {
+                    "type": "object",
+                    "properties": {
+                        "similarity": { "type": "string", "enum": ["identical", "spelling", "overlapping"] },
+                        "reason": { "type": "string" },
+                        "rivers": { "type": "array", "items": { "$ref": "#/components/schemas/River" } },
+                    },
+                },
+                "AuditEntry": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "integer", "description": "记录号" },
+                        "time": { "type": "string" },
+                        "user": { "type": "string" },
+                        "source": { "type": "string", "description": "修改来源，如命令行、接口或导入的文件" },
+                        "action": { "type": "string", "enum": ["insert", "update", "delete", "restore", "purge"] },
+                        "river_id": { "type": "integer" },
+                        "name": { "type": "string" },
+                        "old": { "type": "object", "nullable": true, "description": "修改前的河道，新增时为null" },
+                        "new": { "type": "object", "nullable": true, "description": "修改后的河道，删除时为null" },
+                    },
+                },
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "string" } },
+                },
+                "Invalid": {
+                    "type": "object",
+                    "properties": {
+                        "error": { "type": "string" },
+                        "issues": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "column": { "type": "string" },
+                                    "message": { "type": "string" },
+                                    "severity": { "type": "string" },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
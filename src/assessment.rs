@@ -0,0 +1,448 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use printpdf::{
+    path::{PaintMode, WindingOrder},
+    Color, IndirectFontRef, Line, LineDashPattern, Mm, PdfDocument, PdfLayerReference, Point,
+    Polygon, Rgb,
+};
+
+use crate::{
+    db::Model,
+    dredging::{self, Step},
+    import,
+    section::{self, Anchor, Section, Shape, SKETCH_HEIGHT, SKETCH_WIDTH},
+    security_model::SecurityModel,
+};
+
+/// Columns identifying the reach, in the order shown.
+const IDENTITY_COLUMNS: [&str; 7] = ["id", "name", "area", "level", "start", "end", "time"];
+
+/// Measured and design inputs of the dredging rules, in the order shown.
+const INPUT_COLUMNS: [&str; 7] = [
+    "river_width",
+    "ratio",
+    "elevation",
+    "line",
+    "allow",
+    "safe",
+    "depth",
+];
+
+const SIGNATURES: [&str; 4] = ["编制", "校核", "审核", "批准"];
+
+/// Fonts with Chinese glyphs tried for PDF output when none is given.
+const SYSTEM_FONTS: [&str; 4] = [
+    "C:\\Windows\\Fonts\\simhei.ttf",
+    "C:\\Windows\\Fonts\\simsun.ttc",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+];
+
+/// Everything shown on the assessment page of one reach.
+pub struct AssessmentPage {
+    pub title: String,
+    /// Header and displayed value of the identity fields.
+    pub identity: Vec<(String, String)>,
+    pub inputs: Vec<(String, String)>,
+    pub steps: Vec<Step>,
+    pub sketch: Vec<Shape>,
+    pub verdict: &'static str,
+    /// Set when the stored verdict differs from the one computed now.
+    pub note: Option<String>,
+}
+
+impl AssessmentPage {
+    /// Assesses `model` again so that the page shows the calculation behind
+    /// the verdict.
+    pub fn of(model: &SecurityModel) -> Self {
+        let fields = SecurityModel::fields();
+        let rows = |columns: &[&str]| {
+            columns
+                .iter()
+                .filter_map(|column| fields.iter().find(|field| field.column == *column))
+                .map(|field| (field.header(), (field.display)(model)))
+                .collect::<Vec<(String, String)>>()
+        };
+        let mut computed = model.clone();
+        computed.recompute();
+        let assessment = dredging::assess(model);
+        let note = if model.dredging != assessment.dredging {
+            Some(format!(
+                "库中记录的清淤判断为“{}”，与按当前参数计算的结果不一致。",
+                model.dredging
+            ))
+        } else {
+            None
+        };
+        AssessmentPage {
+            title: format!("{}河道清淤评估表", model.name),
+            identity: rows(&IDENTITY_COLUMNS),
+            inputs: rows(&INPUT_COLUMNS),
+            steps: assessment.steps,
+            sketch: Section::of(&computed).sketch(),
+            verdict: assessment.dredging,
+            note,
+        }
+    }
+}
+
+const HTML_STYLE: &str = r#"
+body { font-family: "SimSun", "Songti SC", serif; margin: 0; }
+.page { width: 180mm; margin: 10mm auto; page-break-after: always; }
+.page:last-child { page-break-after: auto; }
+h1 { font-size: 20pt; text-align: center; margin: 0 0 6mm; }
+h2 { font-size: 13pt; margin: 5mm 0 2mm; }
+table { width: 100%; border-collapse: collapse; font-size: 10.5pt; }
+th, td { border: 1px solid #333; padding: 1.5mm 2mm; text-align: left; }
+th { background: #f0f0f0; font-weight: normal; width: 22%; }
+.sketch { text-align: center; }
+.verdict { font-size: 14pt; font-weight: bold; }
+.note { color: #c00000; }
+.signatures td { height: 12mm; width: 25%; vertical-align: top; }
+.seal { text-align: right; margin-top: 6mm; }
+@page { size: A4; margin: 0; }
+"#;
+
+fn html_pairs(html: &mut String, pairs: &[(String, String)]) {
+    html.push_str("<table>\n");
+    for pair in pairs.chunks(2) {
+        html.push_str("<tr>");
+        for (header, value) in pair {
+            html.push_str(&format!(
+                "<th>{}</th><td>{}</td>",
+                section::escape(header),
+                section::escape(value)
+            ));
+        }
+        if pair.len() == 1 {
+            html.push_str("<th></th><td></td>");
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+fn html_page(html: &mut String, page: &AssessmentPage) {
+    html.push_str("<div class=\"page\">\n");
+    html.push_str(&format!("<h1>{}</h1>\n", section::escape(&page.title)));
+    html.push_str("<h2>一、基本信息</h2>\n");
+    html_pairs(html, &page.identity);
+    html.push_str("<h2>二、输入参数</h2>\n");
+    html_pairs(html, &page.inputs);
+    html.push_str(
+        "<h2>三、计算过程</h2>\n<table>\n<tr><th>项目</th><th>计算式</th><th>结果</th></tr>\n",
+    );
+    for step in page.steps.iter() {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            section::escape(step.label),
+            section::escape(&step.formula),
+            section::escape(&step.result)
+        ));
+    }
+    html.push_str("</table>\n<h2>四、断面示意图</h2>\n<div class=\"sketch\">\n");
+    html.push_str(&section::svg(&page.sketch));
+    html.push_str("</div>\n<h2>五、评估结论</h2>\n");
+    html.push_str(&format!(
+        "<p class=\"verdict\">{}</p>\n",
+        section::escape(page.verdict)
+    ));
+    if let Some(note) = page.note.as_ref() {
+        html.push_str(&format!(
+            "<p class=\"note\">{}</p>\n",
+            section::escape(note)
+        ));
+    }
+    html.push_str("<table class=\"signatures\">\n<tr>");
+    for role in SIGNATURES.iter() {
+        html.push_str(&format!("<td>{}：</td>", role));
+    }
+    html.push_str("</tr>\n<tr>");
+    for _ in SIGNATURES.iter() {
+        html.push_str("<td>日期：</td>");
+    }
+    html.push_str("</tr>\n</table>\n<p class=\"seal\">单位（盖章）：</p>\n</div>\n");
+}
+
+/// Renders the pages as one HTML document, one printed page per reach.
+pub fn html(pages: &[AssessmentPage]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<title>河道清淤评估表</title>\n<style>{}</style>\n</head>\n<body>\n",
+        HTML_STYLE
+    );
+    for page in pages {
+        html_page(&mut html, page);
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Font file for PDF output: `font` if given, else the first Chinese system
+/// font found.
+fn font_path(font: Option<&Path>) -> Result<PathBuf, String> {
+    match font {
+        Some(font) => Ok(font.to_path_buf()),
+        None => SYSTEM_FONTS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file())
+            .ok_or_else(|| String::from("未找到中文字体，请指定字体文件")),
+    }
+}
+
+fn rgb(color: &str) -> Option<Color> {
+    let value = u32::from_str_radix(color.strip_prefix('#')?, 16).ok()?;
+    let channel = |shift: u32| ((value >> shift) & 0xFF) as f32 / 255.0;
+    Some(Color::Rgb(Rgb::new(
+        channel(16),
+        channel(8),
+        channel(0),
+        None,
+    )))
+}
+
+/// Approximate width in mm of `text` at `size` points, counting CJK
+/// characters as square and others as half as wide.
+fn text_width(text: &str, size: f32) -> f32 {
+    let em = size * 25.4 / 72.0;
+    text.chars()
+        .map(|c| if (c as u32) >= 0x2E80 { em } else { em * 0.55 })
+        .sum()
+}
+
+/// Writes PDF pages top-down, keeping track of the baseline.
+struct PdfWriter<'a> {
+    layer: PdfLayerReference,
+    font: &'a IndirectFontRef,
+    /// Distance of the next line from the bottom of the page in mm.
+    y: f32,
+}
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const LEFT: f32 = 20.0;
+const RIGHT: f32 = 190.0;
+
+impl PdfWriter<'_> {
+    fn text(&self, text: &str, size: f32, x: f32, y: f32) {
+        self.layer.use_text(text, size, Mm(x), Mm(y), self.font);
+    }
+
+    fn line(&mut self, text: &str, size: f32) {
+        self.y -= size * 0.5;
+        self.text(text, size, LEFT, self.y);
+        self.y -= size * 0.15;
+    }
+
+    fn rule(&self, y: f32) {
+        self.layer
+            .set_outline_color(Color::Rgb(Rgb::new(0.2, 0.2, 0.2, None)));
+        self.layer.set_outline_thickness(0.5);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(LEFT), Mm(y)), false),
+                (Point::new(Mm(RIGHT), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.y -= 3.0;
+        self.line(text, 12.0);
+        self.rule(self.y + 1.0);
+        self.y -= 1.0;
+    }
+
+    fn pairs(&mut self, pairs: &[(String, String)]) {
+        for pair in pairs.chunks(2) {
+            self.y -= 5.5;
+            for (index, (header, value)) in pair.iter().enumerate() {
+                let x = LEFT + index as f32 * 85.0;
+                self.text(&format!("{}：", header), 10.0, x, self.y);
+                self.text(value, 10.0, x + 34.0, self.y);
+            }
+        }
+    }
+
+    /// Draws sketch shapes scaled into a box `width` mm wide, centred, with
+    /// its top at the current line.
+    fn sketch(&mut self, shapes: &[Shape], width: f32) {
+        let scale = width / SKETCH_WIDTH;
+        let left = (PAGE_WIDTH - width) / 2.0;
+        let top = self.y - 2.0;
+        let point = |(x, y): (f32, f32)| Point::new(Mm(left + x * scale), Mm(top - y * scale));
+        for shape in shapes {
+            match shape {
+                Shape::Polygon {
+                    points,
+                    fill,
+                    stroke,
+                } => {
+                    let (fill, stroke) = (rgb(fill), rgb(stroke));
+                    let mode = match (fill.is_some(), stroke.is_some()) {
+                        (true, true) => PaintMode::FillStroke,
+                        (true, false) => PaintMode::Fill,
+                        (false, true) => PaintMode::Stroke,
+                        (false, false) => continue,
+                    };
+                    if let Some(fill) = fill {
+                        self.layer.set_fill_color(fill);
+                    }
+                    if let Some(stroke) = stroke {
+                        self.layer.set_outline_color(stroke);
+                    }
+                    self.layer.set_outline_thickness(0.8);
+                    self.layer.add_polygon(Polygon {
+                        rings: vec![points.iter().map(|p| (point(*p), false)).collect()],
+                        mode,
+                        winding_order: WindingOrder::NonZero,
+                    });
+                }
+                Shape::Line {
+                    from,
+                    to,
+                    stroke,
+                    dashed,
+                } => {
+                    if let Some(stroke) = rgb(stroke) {
+                        self.layer.set_outline_color(stroke);
+                    }
+                    self.layer.set_outline_thickness(0.8);
+                    if *dashed {
+                        self.layer.set_line_dash_pattern(LineDashPattern {
+                            dash_1: Some(3),
+                            gap_1: Some(2),
+                            ..Default::default()
+                        });
+                    }
+                    self.layer.add_line(Line {
+                        points: vec![(point(*from), false), (point(*to), false)],
+                        is_closed: false,
+                    });
+                    if *dashed {
+                        self.layer.set_line_dash_pattern(LineDashPattern::default());
+                    }
+                }
+                Shape::Text {
+                    at,
+                    text,
+                    size,
+                    anchor,
+                } => {
+                    // Sketch units are about a pixel, i.e. three quarters of a point.
+                    let size = size * scale / 0.3528;
+                    let shift = match anchor {
+                        Anchor::Start => 0.0,
+                        Anchor::Middle => text_width(text, size) / 2.0,
+                        Anchor::End => text_width(text, size),
+                    };
+                    self.layer
+                        .set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+                    let Point { x, y } = point(*at);
+                    self.layer.use_text(
+                        text.as_str(),
+                        size,
+                        Mm::from(x) - Mm(shift),
+                        Mm::from(y),
+                        self.font,
+                    );
+                }
+            }
+        }
+        self.layer
+            .set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        self.y = top - SKETCH_HEIGHT * scale;
+    }
+
+    fn page(&mut self, page: &AssessmentPage) {
+        self.y = PAGE_HEIGHT - 15.0;
+        let title_width = text_width(&page.title, 18.0);
+        self.y -= 9.0;
+        self.text(&page.title, 18.0, (PAGE_WIDTH - title_width) / 2.0, self.y);
+
+        self.heading("一、基本信息");
+        self.pairs(&page.identity);
+        self.heading("二、输入参数");
+        self.pairs(&page.inputs);
+        self.heading("三、计算过程");
+        for step in page.steps.iter() {
+            self.y -= 5.5;
+            self.text(step.label, 10.0, LEFT, self.y);
+            self.text(&step.formula, 10.0, LEFT + 30.0, self.y);
+            self.text(&step.result, 10.0, LEFT + 125.0, self.y);
+        }
+        self.heading("四、断面示意图");
+        self.sketch(&page.sketch, 130.0);
+        self.heading("五、评估结论");
+        self.line(page.verdict, 14.0);
+        if let Some(note) = page.note.as_ref() {
+            self.line(note, 10.0);
+        }
+
+        self.y -= 8.0;
+        for (index, role) in SIGNATURES.iter().enumerate() {
+            let x = LEFT + index as f32 * 42.5;
+            self.text(&format!("{}：", role), 10.0, x, self.y);
+            self.text("日期：", 10.0, x, self.y - 10.0);
+        }
+        self.y -= 22.0;
+        self.text("单位（盖章）：", 10.0, RIGHT - 50.0, self.y);
+    }
+}
+
+/// Renders the pages as an A4 PDF, one page per reach, embedding the font
+/// at `font` or a Chinese system font.
+pub fn pdf(pages: &[AssessmentPage], font: Option<&Path>) -> Result<Vec<u8>, String> {
+    let (document, first_page, first_layer) =
+        PdfDocument::new("河道清淤评估表", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "评估表");
+    let font_path = font_path(font)?;
+    let font_file =
+        File::open(&font_path).map_err(|error| format!("{}：{}", font_path.display(), error))?;
+    let font = document
+        .add_external_font(font_file)
+        .map_err(|error| format!("{}：{}", font_path.display(), error))?;
+
+    for (index, page) in pages.iter().enumerate() {
+        let (page_index, layer_index) = if index == 0 {
+            (first_page, first_layer)
+        } else {
+            document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "评估表")
+        };
+        let mut writer = PdfWriter {
+            layer: document.get_page(page_index).get_layer(layer_index),
+            font: &font,
+            y: 0.0,
+        };
+        writer.page(page);
+    }
+
+    let mut bytes = BufWriter::new(Vec::new());
+    document
+        .save(&mut bytes)
+        .map_err(|error| error.to_string())?;
+    bytes.into_inner().map_err(|error| error.to_string())
+}
+
+/// Writes an assessment page for each river, as PDF when `path` ends in
+/// `.pdf` and as HTML otherwise. Returns the number of pages written.
+pub fn write_assessments<P: AsRef<Path>>(
+    models: &[SecurityModel],
+    path: P,
+    font: Option<&Path>,
+) -> Result<usize, String> {
+    if models.is_empty() {
+        return Err(String::from("没有需要生成评估表的河道"));
+    }
+    let pages: Vec<AssessmentPage> = models.iter().map(AssessmentPage::of).collect();
+    let bytes = match import::extension(path.as_ref()).as_str() {
+        "pdf" => pdf(&pages, font)?,
+        _ => html(&pages).into_bytes(),
+    };
+    fs::write(path, bytes).map_err(|error| error.to_string())?;
+    Ok(pages.len())
+}
@@ -0,0 +1,373 @@
+use std::env;
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, ToSql};
+use serde_json::Value;
+
+use crate::{
+    db::{DbConn, DbOpt, Model},
+    import, recycle,
+    security_model::SecurityModel,
+};
+
+/// Table of every change to a river, with the river before and after as
+/// JSON objects keyed by column name.
+pub const AUDIT_TABLE: &str = "audit_log";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AuditAction {
+    Insert,
+    Update,
+    /// Moved to the recycle bin.
+    Delete,
+    /// Taken back out of the recycle bin.
+    Restore,
+    /// Removed from the recycle bin for good.
+    Purge,
+}
+
+impl AuditAction {
+    /// Name stored in the log and given by the API.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuditAction::Insert => "insert",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+            AuditAction::Purge => "purge",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "insert" => Some(AuditAction::Insert),
+            "update" => Some(AuditAction::Update),
+            "delete" => Some(AuditAction::Delete),
+            "restore" => Some(AuditAction::Restore),
+            "purge" => Some(AuditAction::Purge),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuditAction::Insert => "新增",
+            AuditAction::Update => "修改",
+            AuditAction::Delete => "删除",
+            AuditAction::Restore => "从回收站恢复",
+            AuditAction::Purge => "永久删除",
+        }
+    }
+}
+
+/// One change to a river.
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub id: u32,
+    pub time: DateTime<Local>,
+    pub user: String,
+    /// Where the change was made, e.g. "命令行" or "导入 河道.xlsx".
+    pub source: String,
+    pub action: AuditAction,
+    pub river_id: u32,
+    /// River before the change, `None` when it was inserted.
+    pub old: Option<Value>,
+    /// River after the change, `None` when it was deleted.
+    pub new: Option<Value>,
+}
+
+impl AuditEntry {
+    fn from_row(row: &Row) -> Result<Self> {
+        let json = |index: usize| -> Result<Option<Value>> {
+            Ok(row
+                .get::<_, Option<String>>(index)?
+                .and_then(|text| serde_json::from_str(&text).ok()))
+        };
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            time: row.get(1)?,
+            user: row.get(2)?,
+            source: row.get(3)?,
+            action: AuditAction::parse(&row.get::<_, String>(4)?).unwrap_or(AuditAction::Update),
+            river_id: row.get(5)?,
+            old: json(6)?,
+            new: json(7)?,
+        })
+    }
+
+    /// Name of the river after the change, or before it when deleted.
+    pub fn name(&self) -> String {
+        self.new
+            .as_ref()
+            .or(self.old.as_ref())
+            .map(|river| text(&river["name"]))
+            .unwrap_or_default()
+    }
+
+    /// River as it was before the change, under its own id.
+    pub fn old_river(&self) -> Result<SecurityModel, String> {
+        let old = self
+            .old
+            .as_ref()
+            .ok_or_else(|| format!("第{}条修改记录之前没有{}的数据", self.id, self.name()))?;
+        let table = import::parse_json(&old.to_string())?;
+        let mut model = import::preview::<SecurityModel>(&table, None)
+            .rows
+            .pop()
+            .filter(|row| row.is_valid())
+            .ok_or_else(|| format!("第{}条修改记录中的河道无法读取", self.id))?
+            .model;
+        model.id = self.river_id;
+        Ok(model)
+    }
+
+    /// Header and text before and after of each field the change set.
+    /// Every field is listed for an insert or a delete.
+    pub fn changes(&self) -> Vec<(String, String, String)> {
+        let null = Value::Null;
+        SecurityModel::fields()
+            .iter()
+            .filter_map(|field| {
+                let old = self
+                    .old
+                    .as_ref()
+                    .map_or(&null, |river| &river[field.column]);
+                let new = self
+                    .new
+                    .as_ref()
+                    .map_or(&null, |river| &river[field.column]);
+                if old == new {
+                    None
+                } else {
+                    Some((field.header(), text(old), text(new)))
+                }
+            })
+            .collect()
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+pub(crate) fn create(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        r#"CREATE TABLE {0}
+        (
+            id       INTEGER PRIMARY KEY AUTOINCREMENT,
+            time     TEXT NOT NULL,
+            user     TEXT NOT NULL,
+            source   TEXT NOT NULL,
+            action   TEXT NOT NULL,
+            river_id INTEGER NOT NULL,
+            old      TEXT,
+            new      TEXT
+        );
+        CREATE INDEX {0}_river_time ON {0} (river_id, time);
+        CREATE INDEX {0}_time ON {0} (time);"#,
+        AUDIT_TABLE
+    ))
+}
+
+/// Name of the user logged in to the system, as recorded by default.
+pub fn system_user() -> String {
+    env::var("USERNAME")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or_else(|_| String::from("未知"))
+}
+
+/// Records changes made through `conn` as made by `user` from `source`
+/// until the connection is closed or this is set again.
+pub fn set_context(conn: &Connection, user: &str, source: &str) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TEMP TABLE IF NOT EXISTS audit_context(user TEXT, source TEXT);
+        DELETE FROM temp.audit_context;",
+    )?;
+    conn.execute(
+        "INSERT INTO temp.audit_context(user, source) VALUES (?1, ?2)",
+        params![user, source],
+    )?;
+    Ok(())
+}
+
+/// Changes the source recorded for `conn` and keeps the user.
+pub fn set_source(conn: &Connection, source: &str) -> Result<()> {
+    let (user, _) = context(conn)?;
+    set_context(conn, &user, source)
+}
+
+/// User and source recorded for changes made through `conn`: the system
+/// user and no source unless set.
+pub fn context(conn: &Connection) -> Result<(String, String)> {
+    let set = conn.query_row(
+        "SELECT count(*) FROM sqlite_temp_master WHERE type='table' AND name='audit_context'",
+        [],
+        |row| row.get::<_, u32>(0),
+    )? > 0;
+    let context = if set {
+        conn.query_row("SELECT user, source FROM temp.audit_context", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?
+    } else {
+        None
+    };
+    Ok(context.unwrap_or_else(|| (system_user(), String::new())))
+}
+
+/// River `id` as stored, as a JSON object, or `None` when there is none.
+pub(crate) fn snapshot(conn: &Connection, id: u32) -> Result<Option<Value>> {
+    conn.query_row(
+        format!("{} WHERE id=?", SecurityModel::get_sql(DbOpt::Select)).as_str(),
+        [id],
+        SecurityModel::from_row,
+    )
+    .optional()
+    .map(|model| model.as_ref().map(SecurityModel::to_json))
+}
+
+/// Logs the change of river `river_id` from `old` to `new`, if anything
+/// changed.
+pub(crate) fn record(
+    conn: &Connection,
+    river_id: u32,
+    old: Option<Value>,
+    new: Option<Value>,
+) -> Result<()> {
+    let action = match (&old, &new) {
+        (None, None) => return Ok(()),
+        (None, Some(_)) => AuditAction::Insert,
+        (Some(_), None) => AuditAction::Delete,
+        (Some(old), Some(new)) if old == new => return Ok(()),
+        (Some(_), Some(_)) => AuditAction::Update,
+    };
+    record_as(conn, action, river_id, old, new)
+}
+
+/// Logs `action` on river `river_id`, which went from `old` to `new`.
+pub(crate) fn record_as(
+    conn: &Connection,
+    action: AuditAction,
+    river_id: u32,
+    old: Option<Value>,
+    new: Option<Value>,
+) -> Result<()> {
+    let (user, source) = context(conn)?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {}(time, user, source, action, river_id, old, new) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            AUDIT_TABLE
+        ),
+        params![
+            Local::now(),
+            user,
+            source,
+            action.code(),
+            river_id,
+            old.map(|old| old.to_string()),
+            new.map(|new| new.to_string()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Changes of river `river_id`, or of every river, made at or after `since`
+/// and before `before`, oldest first.
+pub fn entries(
+    conn: &Connection,
+    river_id: Option<u32>,
+    since: Option<DateTime<Local>>,
+    before: Option<DateTime<Local>>,
+) -> Result<Vec<AuditEntry>> {
+    let mut conditions = Vec::new();
+    let mut params: Vec<(&str, &dyn ToSql)> = Vec::new();
+    if let Some(river_id) = river_id.as_ref() {
+        conditions.push("river_id = :river_id");
+        params.push((":river_id", river_id));
+    }
+    if let Some(since) = since.as_ref() {
+        conditions.push("time >= :since");
+        params.push((":since", since));
+    }
+    if let Some(before) = before.as_ref() {
+        conditions.push("time < :before");
+        params.push((":before", before));
+    }
+    let mut sql = format!(
+        "SELECT id, time, user, source, action, river_id, old, new FROM {}",
+        AUDIT_TABLE
+    );
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY time ASC, id ASC");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params.as_slice())?;
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next()? {
+        entries.push(AuditEntry::from_row(row)?);
+    }
+    Ok(entries)
+}
+
+pub fn find(conn: &Connection, id: u32) -> Result<Option<AuditEntry>> {
+    conn.query_row(
+        &format!(
+            "SELECT id, time, user, source, action, river_id, old, new FROM {} WHERE id=?",
+            AUDIT_TABLE
+        ),
+        [id],
+        AuditEntry::from_row,
+    )
+    .optional()
+}
+
+/// Puts the river of change `id` back as it was before that change, under
+/// the same id, taking it out of the recycle bin or recreating it when it
+/// has since been deleted or purged. The restore is itself logged as a
+/// change. A purged river comes back with the inspection it showed; its
+/// earlier inspections were purged with it.
+pub fn restore(conn: &mut DbConn<SecurityModel>, id: u32) -> Result<SecurityModel, String> {
+    let entry = find(&conn.instance, id)
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| format!("找不到第{}条修改记录", id))?;
+    let model = entry.old_river()?;
+
+    let sql_error = |error: rusqlite::Error| error.to_string();
+    let (user, source) = context(&conn.instance).map_err(sql_error)?;
+    set_context(&conn.instance, &user, &format!("恢复第{}条修改记录", id)).map_err(sql_error)?;
+    let result = (|| {
+        if recycle::find(&conn.instance, entry.river_id)
+            .map_err(sql_error)?
+            .is_some()
+        {
+            recycle::restore(conn, entry.river_id)?;
+        }
+        let purged = snapshot(&conn.instance, entry.river_id)
+            .map_err(sql_error)?
+            .is_none();
+        if purged {
+            conn.instance
+                .execute("INSERT INTO rivers(id) VALUES (?1)", [entry.river_id])
+                .map_err(sql_error)?;
+        }
+        conn.set(model);
+        let result = conn.update();
+        if purged && result.is_err() {
+            conn.instance
+                .execute("DELETE FROM rivers WHERE id=?1", [entry.river_id])
+                .map_err(sql_error)?;
+        }
+        result.map_err(sql_error)
+    })();
+    *conn.model = None;
+    set_context(&conn.instance, &user, &source).map_err(sql_error)?;
+    result?;
+    conn.find_by_id(entry.river_id).map_err(sql_error)
+}
@@ -0,0 +1,770 @@
+use std::{env, path::Path, process};
+
+use wrs_nwg::{
+    assessment,
+    audit::{self, AuditEntry},
+    db::{DbConn, Model, DATE_TIME_FORMAT},
+    duplicate,
+    estimate::{self, DredgingMethod, EstimateOptions, PriceCatalog},
+    export::{self, ExportOptions, TextEncoding},
+    forecast,
+    import::{self, Action, ConflictPolicy, Severity},
+    mapping::MappingProfile,
+    recycle, report, section,
+    security_model::SecurityModel,
+    statistics::{self, STATISTICS_HEADERS},
+    template,
+};
+
+const USAGE: &str = r#"用法: wrs-cli [--db <数据库文件>] [--user <用户>] <命令> [参数]
+
+命令:
+    forecast [编号...]    预测河道淤积深度达到淤积阈值的年份
+    estimate [编号...] [--method mechanical|hydraulic] [--distance <运距km>] [--target <保留淤积深度m>]
+                          估算清淤方量与费用，并按辖区汇总
+    prices [<项目>=<单价>...]
+                          查看或修改清淤单价（mechanical、hydraulic、haulage、disposal）
+    import <文件> [--sheet <工作表>] [--profile <映射方案>] [--policy skip|overwrite|newer|fill]
+           [--check-derived] [--dry-run] [--errors <错误工作簿>]
+                          校验并导入xlsx/xlsm/xlsb/xls/ods/csv/json/ndjson文件，仅导入无错误的行；
+                          csv可为UTF-8或GBK编码；
+                          河槽宽度、淤积阈值和清淤判断按输入数据重新计算，
+                          --check-derived提示表中与计算结果不一致的值；
+                          已有河道按--policy跳过、覆盖（默认）、录入时间较新者为准或仅补充空白字段，
+                          未指定工作表时自动选择表头最匹配的工作表，
+                          未指定映射方案时自动使用覆盖表头最多的映射方案
+    search <关键字...> [与export相同的筛选和排序参数]
+                          按河道名称、辖区、起点、终点和清淤判断检索河道，多个关键字须同时匹配；
+                          字母也按名称和辖区的拼音全拼或首字母匹配，如qhh可找到清河湖
+    export <文件> [--encoding utf8|gbk] [--search <关键字>] [--area <辖区,...>] [--level <等级,...>]
+           [--dredging needed|suggested|none,...] [--from <日期>] [--to <日期>]
+           [--columns <列,...>] [--sort <列[:desc],...>] [--sections <断面图目录>]
+                          按扩展名导出河道到xlsx、csv、json或ndjson文件，可原样导入；
+                          csv默认为UTF-8编码；
+                          --search按关键字检索，用法同search，--area按辖区包含的名称筛选，--from、--to按最近一次录入时间筛选（含当天），
+                          --columns指定导出的列及顺序，默认导出全部列，--sort指定排序，默认按编号
+                          --sections同时将导出河道的断面示意图保存为svg文件
+    report <文件> [--by <分组,...>] [与export相同的筛选、列和排序参数]
+                          生成xlsx格式的报表：河道明细按清淤判断着色，并按辖区和等级汇总河道数与长度；
+                          导出清淤判断列时着色随该列修改而变化，否则为固定颜色；
+                          指定--by时另加一张统计表，分组同statistics
+    statistics [--by area|level|dredging|year,...] [与export相同的筛选参数]
+                          按辖区（默认）、等级、清淤判断或年份分组统计河道数、需要和建议清淤数、
+                          总长度、平均和最大淤积深度、超过淤积阈值的河道数、最大超出值和比例；
+                          按年份分组时每条河道按每年最后一次录入计入
+    assessment <文件> [编号...] [--font <字体文件>] [与export相同的筛选和排序参数]
+                          按扩展名生成html或pdf格式的河道清淤评估表，每条河道一页，
+                          含基本信息、输入参数、计算过程、断面示意图、评估结论和签字栏；
+                          未指定编号时按筛选条件选择河道，pdf默认使用系统中文字体
+    section <目录> [编号...] [与export相同的筛选参数]
+                          将河道断面示意图（河底高程、设计洪水位、安全超高、淤积层和淤积阈值）
+                          保存为svg文件，文件名为“编号-河道名称.svg”
+    template <文件>       生成xlsx格式的数据录入模板：表头与导入字段一致，
+                          等级和是否允许浪爬高可下拉选择，选中单元格显示单位和填写说明，含一行示例
+    audit [编号] [--from <日期>] [--to <日期>]
+                          查看河道的新增、修改、删除和导入记录及改动的字段，
+                          未指定编号时列出全部河道，--from、--to按修改时间筛选（含当天）
+    restore <记录号>      将河道恢复为该条修改记录之前的版本，已删除的河道按原编号恢复
+    recycle [restore <编号...> | purge <编号...>|--all]
+                          查看回收站中已删除的河道，恢复或永久删除；永久删除的河道及其巡查记录无法从回收站恢复
+    duplicates [merge <保留编号> <编号...>]
+                          列出疑似重复录入的河道：名称、辖区、起点和终点完全相同，仅全半角、空格或大小写不同，
+                          或同名同辖区的桩号河段重叠；merge将其余河道并入保留的河道，补充其缺少的巡查记录，
+                          其余河道移入回收站；旧数据中完全相同的河道全部合并或删除后，才禁止重复录入
+    mappings [show|delete <方案>]
+    mappings set <方案> <表头>=<字段>...
+                          查看、删除或修改表头映射方案，字段为空表示忽略该列
+    help                  显示本帮助"#;
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let db_path = match take_option(&mut args, "--db") {
+        Ok(db_path) => db_path.unwrap_or_else(|| String::from("./water-resources.db")),
+        Err(message) => fail(message.as_str()),
+    };
+    let user = match take_option(&mut args, "--user") {
+        Ok(user) => user.unwrap_or_else(audit::system_user),
+        Err(message) => fail(message.as_str()),
+    };
+    if args.is_empty() {
+        fail(USAGE);
+    }
+
+    let mut conn = match DbConn::<SecurityModel>::open(&db_path) {
+        Ok(conn) => conn,
+        Err(error) => fail(format!("打开数据库{}失败：{}", db_path, error).as_str()),
+    };
+    if let Err(error) = audit::set_context(&conn.instance, &user, "命令行") {
+        fail(error.to_string().as_str());
+    }
+    if let Ok(false) = duplicate::is_enforced(&conn.instance) {
+        eprintln!(
+            "提示：数据库中有完全相同的河道，合并或删除前不禁止重复录入，请用duplicates命令查看"
+        );
+    }
+
+    let command = args.remove(0);
+    let result = match command.as_str() {
+        "forecast" => forecast_command(&conn, &args),
+        "estimate" => estimate_command(&conn, args),
+        "prices" => prices_command(&conn, &args),
+        "search" => search_command(&conn, args),
+        "import" => import_command(&mut conn, args),
+        "export" => export_command(&conn, args),
+        "report" => report_command(&conn, args),
+        "statistics" => statistics_command(&conn, args),
+        "assessment" => assessment_command(&conn, args),
+        "section" => section_command(&conn, args),
+        "template" => template_command(&args),
+        "mappings" => mappings_command(&conn, &args),
+        "audit" => audit_command(&conn, args),
+        "restore" => restore_command(&mut conn, &args),
+        "recycle" => recycle_command(&conn, &args),
+        "duplicates" => duplicates_command(&conn, &args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("未知命令：{}\n\n{}", command, USAGE)),
+    };
+    if let Err(message) = result {
+        fail(message.as_str());
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Removes `name <value>` from `args`, returning the value if present.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            if index + 1 >= args.len() {
+                return Err(format!("{} 需要指定参数值", name));
+            }
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Removes `name` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+fn take_number(args: &mut Vec<String>, name: &str) -> Result<Option<f64>, String> {
+    match take_option(args, name)? {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} 必须为数字：{}", name, value)),
+        None => Ok(None),
+    }
+}
+
+/// Rivers named by id on the command line, or every river when none is given.
+fn select_rivers(
+    conn: &DbConn<SecurityModel>,
+    ids: &[String],
+) -> Result<Vec<SecurityModel>, String> {
+    if ids.is_empty() {
+        return conn
+            .find("", ("id", "ASC"), (0, 0), &[])
+            .map_err(|error| error.to_string());
+    }
+    let mut models = Vec::new();
+    for id in ids {
+        let id = id
+            .parse::<u32>()
+            .map_err(|_| format!("无效的编号：{}", id))?;
+        models.push(
+            conn.find_by_id(id)
+                .map_err(|error| format!("找不到编号为{}的河道：{}", id, error))?,
+        );
+    }
+    Ok(models)
+}
+
+fn forecast_command(conn: &DbConn<SecurityModel>, args: &[String]) -> Result<(), String> {
+    println!(
+        "编号\t河道名称\t河道所属辖区\t测次\t模型\t淤积速率(m/年)\t淤积阈值(m)\t预测清淤年份\t置信区间(95%)"
+    );
+    for model in select_rivers(conn, args)? {
+        let forecast =
+            forecast::forecast_river(&conn.instance, &model).map_err(|error| error.to_string())?;
+        match forecast {
+            Some(forecast) => println!(
+                "{}\t{}\t{}\t{}\t{}\t{:.3}\t{}\t{}\t{}",
+                model.id,
+                model.name,
+                model.area,
+                forecast.fit.samples,
+                forecast.fit.curve.name(),
+                forecast.rate,
+                model.threshold,
+                forecast.year_text(),
+                forecast.interval_text()
+            ),
+            None => println!(
+                "{}\t{}\t{}\t\t\t\t{}\t测量次数不足\t",
+                model.id, model.name, model.area, model.threshold
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn estimate_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let mut options = EstimateOptions::default();
+    if let Some(method) = take_option(&mut args, "--method")? {
+        options.method =
+            DredgingMethod::parse(&method).ok_or(format!("未知的清淤方式：{}", method))?;
+    }
+    if let Some(distance) = take_number(&mut args, "--distance")? {
+        options.distance = distance;
+    }
+    if let Some(target) = take_number(&mut args, "--target")? {
+        options.target = target;
+    }
+    let catalog = PriceCatalog::load(&conn.instance).map_err(|error| error.to_string())?;
+
+    println!(
+        "清淤方式：{}，运距{}km，保留淤积深度{}m",
+        options.method.name(),
+        options.distance,
+        options.target
+    );
+    println!("编号\t河道名称\t河道所属辖区\t河段长度(m)\t超淤深度(m)\t清淤断面(m²)\t清淤方量(m³)\t清淤费(元)\t运输费(元)\t处置费(元)\t合计(元)");
+    let estimates: Vec<_> = select_rivers(conn, &args)?
+        .iter()
+        .map(|model| estimate::estimate(model, &catalog, &options))
+        .collect();
+    for estimate in estimates.iter() {
+        match (estimate.length, estimate.volume, &estimate.cost) {
+            (Some(length), Some(volume), Some(cost)) => println!(
+                "{}\t{}\t{}\t{:.0}\t{:.2}\t{:.2}\t{:.1}\t{:.2}\t{:.2}\t{:.2}\t{:.2}",
+                estimate.river_id,
+                estimate.name,
+                estimate.area,
+                length,
+                estimate.excess,
+                estimate.section,
+                volume,
+                cost.dredging,
+                cost.haulage,
+                cost.disposal,
+                cost.total()
+            ),
+            _ => println!(
+                "{}\t{}\t{}\t起止点不是桩号，无法计算河段长度\t{:.2}\t{:.2}\t\t\t\t\t",
+                estimate.river_id, estimate.name, estimate.area, estimate.excess, estimate.section
+            ),
+        }
+    }
+
+    println!();
+    println!("河道所属辖区\t河段数\t未计入\t河段长度(m)\t清淤方量(m³)\t合计(元)");
+    for total in estimate::totals_by_area(&estimates) {
+        println!(
+            "{}\t{}\t{}\t{:.0}\t{:.1}\t{:.2}",
+            total.area, total.reaches, total.missing, total.length, total.volume, total.cost
+        );
+    }
+    Ok(())
+}
+
+fn prices_command(conn: &DbConn<SecurityModel>, args: &[String]) -> Result<(), String> {
+    let mut catalog = PriceCatalog::load(&conn.instance).map_err(|error| error.to_string())?;
+    if !args.is_empty() {
+        for arg in args {
+            let (item, price) = arg
+                .split_once('=')
+                .ok_or(format!("格式应为<项目>=<单价>：{}", arg))?;
+            let price = price
+                .parse::<f64>()
+                .map_err(|_| format!("单价必须为数字：{}", price))?;
+            if !catalog.set(item, price) {
+                return Err(format!("未知的单价项目：{}", item));
+            }
+        }
+        catalog
+            .save(&conn.instance)
+            .map_err(|error| error.to_string())?;
+    }
+    for (item, name, unit) in PriceCatalog::ITEMS.iter() {
+        println!(
+            "{}\t{}\t{}\t{}",
+            item,
+            name,
+            catalog.get(item).unwrap(),
+            unit
+        );
+    }
+    Ok(())
+}
+
+fn import_command(conn: &mut DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let check_derived = take_flag(&mut args, "--check-derived");
+    let errors = take_option(&mut args, "--errors")?;
+    let sheet = take_option(&mut args, "--sheet")?;
+    let profile = take_option(&mut args, "--profile")?;
+    let policy = match take_option(&mut args, "--policy")? {
+        Some(policy) => {
+            ConflictPolicy::parse(&policy).ok_or(format!("未知的冲突处理方式：{}", policy))?
+        }
+        None => ConflictPolicy::Overwrite,
+    };
+    let path = match args.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("请指定一个导入文件")),
+    };
+
+    let (table, profile) = import::read_with_profile::<SecurityModel, _>(
+        &conn.instance,
+        path,
+        sheet.as_deref(),
+        profile.as_deref(),
+    )?;
+    println!("{}", table.describe());
+    if let Some(profile) = profile.as_ref() {
+        println!("映射方案：{}", profile.name);
+    }
+    let mut preview = import::preview::<SecurityModel>(&table, profile.as_ref());
+    import::recompute_derived(&mut preview, check_derived);
+    import::check_duplicates(&mut preview);
+
+    println!("行号\t列\t级别\t问题");
+    for issue in preview.all_issues() {
+        println!(
+            "{}\t{}\t{}\t{}",
+            issue.row,
+            issue.column,
+            issue.severity.name(),
+            issue.message
+        );
+    }
+    println!("{}", preview.summary());
+
+    if let Some(errors) = errors {
+        let num = preview.write_rejects(&errors)?;
+        println!("已将{}行无法导入的数据写入{}", num, errors);
+    }
+
+    println!();
+    println!("冲突处理：{}", policy.name());
+    println!("行号\t处理\t河道名称\t河道所属辖区\t字段\t原值\t新值");
+    for row in preview.valid_rows() {
+        let resolution = import::resolve(conn, row, policy);
+        let action = match &resolution.action {
+            Action::Insert => String::from("新增"),
+            Action::Update => String::from("更新"),
+            Action::Restore => String::from("从回收站恢复"),
+            Action::Skip(reason) => format!("跳过：{}", reason),
+            Action::Reject(reason) => format!("失败：{}", reason),
+        };
+        println!(
+            "{}\t{}\t{}\t{}",
+            resolution.row, action, resolution.model.name, resolution.model.area
+        );
+        for change in resolution.changes.iter() {
+            println!("\t\t\t\t{}\t{}\t{}", change.header, change.old, change.new);
+        }
+    }
+    if !dry_run {
+        audit::set_source(
+            &conn.instance,
+            &format!("{} {}", import::IMPORT_SOURCE, path),
+        )
+        .map_err(|error| error.to_string())?;
+        let summary = import::commit(conn, preview.valid_rows(), policy);
+        println!("{}", summary.to_message());
+    }
+    if preview
+        .all_issues()
+        .any(|issue| issue.severity == Severity::Error)
+    {
+        process::exit(2);
+    }
+    Ok(())
+}
+
+/// Removes the filter, column and sort options shared by `export` and
+/// `report` from `args`.
+fn take_export_options(args: &mut Vec<String>) -> Result<ExportOptions, String> {
+    let mut options = ExportOptions::default();
+    if let Some(keyword) = take_option(args, "--search")? {
+        options.set_keyword(&keyword);
+    }
+    if let Some(areas) = take_option(args, "--area")? {
+        options.set_areas(&areas);
+    }
+    if let Some(levels) = take_option(args, "--level")? {
+        options.set_levels(&levels)?;
+    }
+    if let Some(dredging) = take_option(args, "--dredging")? {
+        options.set_dredging(&dredging)?;
+    }
+    if let Some(since) = take_option(args, "--from")? {
+        options.set_since(&since)?;
+    }
+    if let Some(until) = take_option(args, "--to")? {
+        options.set_until(&until)?;
+    }
+    if let Some(columns) = take_option(args, "--columns")? {
+        options.set_columns(&columns)?;
+    }
+    if let Some(sort) = take_option(args, "--sort")? {
+        options.set_sort(&sort)?;
+    }
+    Ok(options)
+}
+
+fn search_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let mut options = take_export_options(&mut args)?;
+    if args.is_empty() {
+        return Err(String::from("请指定检索的关键字"));
+    }
+    options.set_keyword(&args.join(" "));
+    let models = options.rivers(conn)?;
+    println!("编号\t河道名称\t河道所属辖区\t起点\t终点\t清淤判断");
+    for model in models.iter() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            model.id, model.name, model.area, model.start, model.end, model.dredging
+        );
+    }
+    println!("共{}条", models.len());
+    Ok(())
+}
+
+fn export_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let encoding = match take_option(&mut args, "--encoding")? {
+        Some(encoding) => {
+            TextEncoding::parse(&encoding).ok_or(format!("未知的编码：{}", encoding))?
+        }
+        None => TextEncoding::Utf8,
+    };
+    let sections = take_option(&mut args, "--sections")?;
+    let options = take_export_options(&mut args)?;
+    let path = match args.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("请指定一个导出文件")),
+    };
+    let num = export::write_file(conn, path, encoding, &options)?;
+    println!("导出完成，共{}条数据", num);
+    if let Some(dir) = sections {
+        let num = section::write_sections(&options.rivers(conn)?, &dir)?;
+        println!("断面图已生成，共{}个文件", num);
+    }
+    Ok(())
+}
+
+fn report_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let group_by = take_option(&mut args, "--by")?;
+    let mut options = take_export_options(&mut args)?;
+    if let Some(group_by) = group_by {
+        options.set_group_by(&group_by)?;
+    }
+    let path = match args.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("请指定一个报表文件")),
+    };
+    let num = report::write_report(conn, path, &options)?;
+    println!("报表已生成，共{}条数据", num);
+    Ok(())
+}
+
+fn statistics_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let group_by = take_option(&mut args, "--by")?;
+    let mut options = take_export_options(&mut args)?;
+    options.set_group_by(group_by.as_deref().unwrap_or("area"))?;
+    if !args.is_empty() {
+        return Err(format!("无法识别的参数：{}", args.join(" ")));
+    }
+    let (rows, total) = statistics::statistics(conn, &options.group_by, &options)?;
+
+    let headers: Vec<&str> = options
+        .group_by
+        .iter()
+        .map(|group| group.header())
+        .chain(STATISTICS_HEADERS.iter().copied())
+        .collect();
+    println!("{}", headers.join("\t"));
+    for (index, row) in rows.iter().chain(Some(&total)).enumerate() {
+        let mut key = row.key.clone();
+        if index == rows.len() {
+            key = vec![String::new(); options.group_by.len()];
+            key[0] = String::from("合计");
+        }
+        println!(
+            "{}\t{}\t{}\t{}\t{:.3}\t{}\t{:.2}\t{:.2}\t{}\t{:.2}\t{:.1}%",
+            key.join("\t"),
+            row.rivers,
+            row.needed,
+            row.suggested,
+            row.length,
+            row.missing,
+            row.average_depth(),
+            row.max_depth,
+            row.exceeding,
+            row.max_exceedance,
+            row.exceeding_ratio() * 100.0
+        );
+    }
+    Ok(())
+}
+
+fn assessment_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let font = take_option(&mut args, "--font")?;
+    let options = take_export_options(&mut args)?;
+    if args.is_empty() {
+        return Err(String::from("请指定评估表文件"));
+    }
+    let path = args.remove(0);
+    let models = if args.is_empty() {
+        options.rivers(conn)?
+    } else {
+        select_rivers(conn, &args)?
+    };
+    let num = assessment::write_assessments(&models, &path, font.as_ref().map(Path::new))?;
+    println!("评估表已生成，共{}页", num);
+    Ok(())
+}
+
+fn section_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let options = take_export_options(&mut args)?;
+    if args.is_empty() {
+        return Err(String::from("请指定断面图目录"));
+    }
+    let dir = args.remove(0);
+    let models = if args.is_empty() {
+        options.rivers(conn)?
+    } else {
+        select_rivers(conn, &args)?
+    };
+    let num = section::write_sections(&models, &dir)?;
+    println!("断面图已生成，共{}个文件", num);
+    Ok(())
+}
+
+fn template_command(args: &[String]) -> Result<(), String> {
+    let path = match args {
+        [path] => path,
+        _ => return Err(String::from("请指定一个模板文件")),
+    };
+    if import::extension(Path::new(path)) != "xlsx" {
+        return Err(String::from("模板文件须为xlsx格式"));
+    }
+    template::write_template(path)?;
+    println!("模板已生成：{}", path);
+    Ok(())
+}
+
+fn mappings_command(conn: &DbConn<SecurityModel>, args: &[String]) -> Result<(), String> {
+    let fields = SecurityModel::fields();
+    let show = |profile: &MappingProfile| {
+        println!("表头\t字段");
+        for (header, column) in profile.entries.iter() {
+            match fields.iter().find(|field| field.column == column) {
+                Some(field) => println!("{}\t{}", header, field.header()),
+                None => println!("{}\t（忽略）", header),
+            }
+        }
+    };
+    let load = |name: &str| {
+        MappingProfile::load(&conn.instance, name)
+            .map_err(|error| error.to_string())?
+            .ok_or(format!("找不到映射方案{}", name))
+    };
+    match args {
+        [] => {
+            println!("方案\t表头数");
+            for profile in
+                MappingProfile::load_all(&conn.instance).map_err(|error| error.to_string())?
+            {
+                println!("{}\t{}", profile.name, profile.entries.len());
+            }
+        }
+        [command, name] if command == "show" => show(&load(name)?),
+        [command, name] if command == "delete" => {
+            if MappingProfile::delete(&conn.instance, name).map_err(|error| error.to_string())? == 0
+            {
+                return Err(format!("找不到映射方案{}", name));
+            }
+        }
+        [command, name, entries @ ..] if command == "set" && !entries.is_empty() => {
+            let mut profile = match MappingProfile::load(&conn.instance, name)
+                .map_err(|error| error.to_string())?
+            {
+                Some(profile) => profile,
+                None => MappingProfile {
+                    name: name.clone(),
+                    entries: Vec::new(),
+                },
+            };
+            for entry in entries {
+                let (header, column) = entry
+                    .split_once('=')
+                    .ok_or(format!("格式应为<表头>=<字段>：{}", entry))?;
+                profile.assign::<SecurityModel>(header, column)?;
+            }
+            profile
+                .save(&conn.instance)
+                .map_err(|error| error.to_string())?;
+            show(&profile);
+        }
+        _ => return Err(format!("mappings参数错误\n\n{}", USAGE)),
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &AuditEntry) {
+    println!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        entry.id,
+        entry.time.format(DATE_TIME_FORMAT),
+        entry.user,
+        entry.source,
+        entry.action.label(),
+        entry.river_id,
+        entry.name()
+    );
+    for (header, old, new) in entry.changes() {
+        println!("\t\t\t\t\t\t\t{}\t{}\t{}", header, old, new);
+    }
+}
+
+fn audit_command(conn: &DbConn<SecurityModel>, mut args: Vec<String>) -> Result<(), String> {
+    let since = match take_option(&mut args, "--from")? {
+        Some(since) => export::parse_since(&since)?,
+        None => None,
+    };
+    let before = match take_option(&mut args, "--to")? {
+        Some(until) => export::parse_until(&until)?,
+        None => None,
+    };
+    let river_id = match args.as_slice() {
+        [] => None,
+        [id] => Some(
+            id.parse::<u32>()
+                .map_err(|_| format!("无效的编号：{}", id))?,
+        ),
+        _ => return Err(format!("无法识别的参数：{}", args.join(" "))),
+    };
+    let entries = audit::entries(&conn.instance, river_id, since, before)
+        .map_err(|error| error.to_string())?;
+    println!("记录号\t时间\t用户\t来源\t操作\t编号\t河道名称\t字段\t原值\t新值");
+    for entry in entries.iter() {
+        print_entry(entry);
+    }
+    println!("共{}条", entries.len());
+    Ok(())
+}
+
+fn restore_command(conn: &mut DbConn<SecurityModel>, args: &[String]) -> Result<(), String> {
+    let id = match args {
+        [id] => id
+            .parse::<u32>()
+            .map_err(|_| format!("无效的记录号：{}", id))?,
+        _ => return Err(String::from("请指定一条修改记录的记录号")),
+    };
+    let model = audit::restore(conn, id)?;
+    println!("已恢复编号为{}的河道{}", model.id, model.name);
+    Ok(())
+}
+
+fn recycle_command(conn: &DbConn<SecurityModel>, args: &[String]) -> Result<(), String> {
+    let ids = |ids: &[String]| {
+        ids.iter()
+            .map(|id| id.parse::<u32>().map_err(|_| format!("无效的编号：{}", id)))
+            .collect::<Result<Vec<u32>, String>>()
+    };
+    match args {
+        [] => {
+            let rivers =
+                recycle::deleted_rivers(&conn.instance).map_err(|error| error.to_string())?;
+            println!("编号\t河道名称\t河道所属辖区\t起点\t终点\t删除时间");
+            for river in rivers.iter() {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    river.model.id,
+                    river.model.name,
+                    river.model.area,
+                    river.model.start,
+                    river.model.end,
+                    river.deleted.format(DATE_TIME_FORMAT)
+                );
+            }
+            println!("共{}条", rivers.len());
+        }
+        [command, rest @ ..] if command == "restore" && !rest.is_empty() => {
+            for id in ids(rest)? {
+                let model = recycle::restore(conn, id)?;
+                println!("已恢复编号为{}的河道{}", model.id, model.name);
+            }
+        }
+        [command, all] if command == "purge" && all == "--all" => {
+            let num = recycle::purge_all(&conn.instance).map_err(|error| error.to_string())?;
+            println!("已永久删除{}条河道", num);
+        }
+        [command, rest @ ..] if command == "purge" && !rest.is_empty() => {
+            for id in ids(rest)? {
+                if recycle::purge(&conn.instance, id).map_err(|error| error.to_string())? == 0 {
+                    return Err(format!("回收站中没有编号为{}的河道", id));
+                }
+                println!("已永久删除编号为{}的河道", id);
+            }
+        }
+        _ => return Err(format!("recycle参数错误\n\n{}", USAGE)),
+    }
+    Ok(())
+}
+
+fn duplicates_command(conn: &DbConn<SecurityModel>, args: &[String]) -> Result<(), String> {
+    match args {
+        [] => {}
+        [command, keep, others @ ..] if command == "merge" && !others.is_empty() => {
+            let id = |id: &String| id.parse::<u32>().map_err(|_| format!("无效的编号：{}", id));
+            let others = others
+                .iter()
+                .map(id)
+                .collect::<Result<Vec<u32>, String>>()?;
+            let enforced = duplicate::merge(conn, id(keep)?, &others)?;
+            println!("已将{}条河道并入编号为{}的河道", others.len(), keep);
+            if enforced {
+                println!("已禁止重复录入名称、辖区、起点和终点都相同的河道");
+            }
+            return Ok(());
+        }
+        _ => return Err(format!("duplicates参数错误\n\n{}", USAGE)),
+    }
+    let groups = duplicate::groups(&conn.instance).map_err(|error| error.to_string())?;
+    for (index, group) in groups.iter().enumerate() {
+        println!("第{}组：{}", index + 1, group.similarity.reason());
+        for model in group.rivers.iter() {
+            println!(
+                "    {}\t{}\t{}\t{}\t{}\t{}",
+                model.id,
+                model.name,
+                model.area,
+                model.start,
+                model.end,
+                model.time.format(DATE_TIME_FORMAT)
+            );
+        }
+    }
+    println!("共{}组疑似重复的河道", groups.len());
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::{env, process};
+
+use tiny_http::{Header, Response, Server};
+use wrs_nwg::{
+    api::{self, Request},
+    audit,
+    db::DbConn,
+    security_model::SecurityModel,
+};
+
+const USAGE: &str = r#"用法: wrs-server [--db <数据库文件>] [--port <端口>] [--allow-origin <来源>]
+
+在本机（127.0.0.1）提供河道数据的JSON接口，默认端口8020，接口说明见/openapi.json。
+    --allow-origin        允许该来源的网页调用接口，如http://localhost:3000"#;
+
+const DEFAULT_PORT: u16 = 8020;
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args
+        .iter()
+        .any(|arg| arg == "help" || arg == "--help" || arg == "-h")
+    {
+        println!("{}", USAGE);
+        return;
+    }
+    let db_path = take_option(&mut args, "--db")
+        .unwrap_or_else(|message| fail(&message))
+        .unwrap_or_else(|| String::from("./water-resources.db"));
+    let port = match take_option(&mut args, "--port").unwrap_or_else(|message| fail(&message)) {
+        Some(port) => port
+            .parse::<u16>()
+            .unwrap_or_else(|_| fail(&format!("无效的端口：{}", port))),
+        None => DEFAULT_PORT,
+    };
+    let origin = take_option(&mut args, "--allow-origin").unwrap_or_else(|message| fail(&message));
+    if !args.is_empty() {
+        fail(&format!("无法识别的参数：{}\n\n{}", args.join(" "), USAGE));
+    }
+
+    let mut conn = DbConn::<SecurityModel>::open(&db_path)
+        .unwrap_or_else(|error| fail(&format!("打开数据库{}失败：{}", db_path, error)));
+    audit::set_context(&conn.instance, &audit::system_user(), "接口")
+        .unwrap_or_else(|error| fail(&error.to_string()));
+    let server = Server::http(("127.0.0.1", port))
+        .unwrap_or_else(|error| fail(&format!("无法监听端口{}：{}", port, error)));
+    println!(
+        "服务已启动：http://127.0.0.1:{}，接口说明见/openapi.json",
+        port
+    );
+
+    // Requests are answered one at a time over the single connection.
+    for mut request in server.incoming_requests() {
+        let content_type = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Content-Type"))
+            .map(|header| header.value.to_string())
+            .unwrap_or_default();
+        let mut body = String::new();
+        let reply = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => api::handle(
+                &mut conn,
+                &Request::new(
+                    request.method().as_str(),
+                    request.url(),
+                    &content_type,
+                    &body,
+                ),
+            ),
+            Err(_) => api::Response::error(400, "请求内容须为UTF-8编码"),
+        };
+
+        let mut headers = reply.headers.clone();
+        if let Some(origin) = origin.as_ref() {
+            headers.push(("Access-Control-Allow-Origin", origin.clone()));
+            headers.push(("Access-Control-Allow-Headers", String::from("Content-Type")));
+            if let Some((_, allowed)) = reply.headers.iter().find(|(name, _)| *name == "Allow") {
+                headers.push(("Access-Control-Allow-Methods", allowed.clone()));
+            }
+        }
+        let mut response = Response::from_data(reply.body).with_status_code(reply.status);
+        for (name, value) in headers {
+            if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+                response.add_header(header);
+            }
+        }
+        if let Err(error) = request.respond(response) {
+            eprintln!("发送响应失败：{}", error);
+        }
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Removes `name <value>` from `args`, returning the value if present.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            if index + 1 >= args.len() {
+                return Err(format!("{} 需要指定参数值", name));
+            }
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,1183 @@
+use std::{env, io, mem, process};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
+    DefaultTerminal, Frame,
+};
+use wrs_nwg::{
+    audit::{self, AuditEntry},
+    db::{DbConn, Model, DATE_TIME_FORMAT},
+    duplicate,
+    export::{self, ExportOptions, TextEncoding},
+    form::RiverForm,
+    import::{self, Action, ConflictPolicy, ImportPreview},
+    recycle::{self, DeletedRiver},
+    report,
+    security_model::SecurityModel,
+};
+
+const USAGE: &str = r#"用法: wrs-tui [--db <数据库文件>]
+
+在终端中查看、搜索、录入、导入和导出河道数据，功能与Windows界面相同。
+a查看所选河道的修改记录，A查看全部修改记录（含已删除的河道），可恢复修改前的版本；
+b打开回收站，恢复或永久删除已删除的河道。"#;
+
+/// Columns of the list, a subset of the fields that fits a terminal.
+const LIST_COLUMNS: [&str; 10] = [
+    "id",
+    "level",
+    "name",
+    "area",
+    "start",
+    "end",
+    "depth",
+    "threshold",
+    "dredging",
+    "time",
+];
+/// Widest a list column is drawn.
+const MAX_COLUMN_WIDTH: usize = 24;
+
+/// Boxes of the list filter, which the export dialog starts from.
+const FILTER_LABELS: [&str; 6] = ["辖区", "等级", "清淤判断", "起始日期", "截止日期", "排序"];
+const EXPORT_LABELS: [&str; 11] = [
+    "文件",
+    "辖区",
+    "等级",
+    "清淤判断",
+    "起始日期",
+    "截止日期",
+    "排序",
+    "列",
+    "编码",
+    "报表",
+    "统计分组",
+];
+
+const POLICIES: [ConflictPolicy; 4] = [
+    ConflictPolicy::Skip,
+    ConflictPolicy::Overwrite,
+    ConflictPolicy::NewerWins,
+    ConflictPolicy::FillEmpty,
+];
+
+const LIST_KEYS: &str =
+    "↑↓选择 Enter编辑 n新增 d删除 h历史 a/A修改记录 b回收站 /搜索 f筛选 i导入 x导出 r刷新 q退出";
+const FORM_KEYS: &str = "↑↓/Tab切换 Ctrl+U清空 F2/Ctrl+S保存 Esc取消";
+const DIALOG_KEYS: &str = "↑↓/Tab切换 Ctrl+U清空 Enter确定 Esc取消";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args
+        .iter()
+        .any(|arg| arg == "help" || arg == "--help" || arg == "-h")
+    {
+        println!("{}", USAGE);
+        return;
+    }
+    let db_path = match args.iter().position(|arg| arg == "--db") {
+        Some(index) if index + 1 < args.len() => {
+            let path = args.remove(index + 1);
+            args.remove(index);
+            path
+        }
+        Some(_) => fail("--db 需要指定参数值"),
+        None => String::from("./water-resources.db"),
+    };
+    if !args.is_empty() {
+        fail(&format!("无法识别的参数：{}\n\n{}", args.join(" "), USAGE));
+    }
+
+    let conn = DbConn::<SecurityModel>::open(&db_path)
+        .unwrap_or_else(|error| fail(&format!("打开数据库{}失败：{}", db_path, error)));
+    if let Err(error) = audit::set_context(&conn.instance, &audit::system_user(), "终端") {
+        fail(&error.to_string());
+    }
+    let mut app = App::new(conn);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    if let Err(error) = result {
+        fail(&error.to_string());
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Single-line text boxes, edited one at a time.
+struct Entries {
+    labels: Vec<String>,
+    texts: Vec<String>,
+    selected: usize,
+}
+
+impl Entries {
+    fn new(labels: &[&str], texts: Vec<String>) -> Self {
+        Entries {
+            labels: labels.iter().map(|label| String::from(*label)).collect(),
+            texts,
+            selected: 0,
+        }
+    }
+
+    fn text(&self, label: &str) -> &str {
+        self.labels
+            .iter()
+            .position(|known| known == label)
+            .map(|index| self.texts[index].as_str())
+            .unwrap_or_default()
+    }
+
+    /// Moves between the boxes or edits the selected one. Returns false for
+    /// keys it does not use.
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        let count = self.texts.len();
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Up | KeyCode::BackTab => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Down | KeyCode::Tab => self.selected = (self.selected + 1) % count,
+            KeyCode::Backspace => {
+                self.texts[self.selected].pop();
+            }
+            KeyCode::Char('u') if control => self.texts[self.selected].clear(),
+            KeyCode::Char(c) if !control => self.texts[self.selected].push(c),
+            _ => return false,
+        }
+        true
+    }
+
+    /// One line per box, with `notes` beside the boxes they name.
+    fn lines(&self, notes: &[(String, String)]) -> Vec<Line<'static>> {
+        let width = self
+            .labels
+            .iter()
+            .map(|label| text_width(label))
+            .max()
+            .unwrap_or_default();
+        self.labels
+            .iter()
+            .zip(self.texts.iter())
+            .enumerate()
+            .map(|(index, (label, text))| {
+                let selected = index == self.selected;
+                let mut spans = vec![
+                    Span::raw(format!(
+                        "{}{}  ",
+                        " ".repeat(width - text_width(label)),
+                        label
+                    )),
+                    Span::styled(
+                        format!("{}{}", text, if selected { "▏" } else { "" }),
+                        if selected {
+                            Style::new().add_modifier(Modifier::REVERSED)
+                        } else {
+                            Style::new().add_modifier(Modifier::UNDERLINED)
+                        },
+                    ),
+                ];
+                if let Some((_, note)) = notes.iter().find(|(known, _)| known == label) {
+                    spans.push(Span::styled(
+                        format!("  {}", note),
+                        Style::new().fg(Color::Red),
+                    ));
+                }
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
+
+/// The river form: its boxes are the fields of `form`.
+struct FormView {
+    form: RiverForm,
+    entries: Entries,
+    /// Whether saving waits for confirmation.
+    confirming: bool,
+}
+
+impl FormView {
+    fn new(form: RiverForm) -> Self {
+        let labels: Vec<String> = form.fields.iter().map(|field| field.header()).collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        let entries = Entries::new(&labels, form.texts.clone());
+        FormView {
+            form,
+            entries,
+            confirming: false,
+        }
+    }
+}
+
+struct ImportView {
+    /// File being imported.
+    path: String,
+    preview: ImportPreview<SecurityModel>,
+    /// Source, counts and problems, shown above the outcome.
+    report: Vec<String>,
+    /// Index in `POLICIES`.
+    policy: usize,
+    /// What committing under the policy would do.
+    outcome: Vec<String>,
+    scroll: u16,
+    /// File name being entered for the rejected rows.
+    rejects: Option<String>,
+}
+
+/// Changes of one river or of every river, newest first.
+struct AuditView {
+    title: String,
+    entries: Vec<AuditEntry>,
+    selected: usize,
+    /// Whether restoring the selected change waits for confirmation.
+    confirming: bool,
+}
+
+/// Deleted rivers, the latest deleted first.
+struct RecycleView {
+    rivers: Vec<DeletedRiver>,
+    selected: usize,
+    /// Key of the restore or purge waiting for confirmation.
+    confirming: Option<char>,
+}
+
+enum Prompt {
+    Search,
+    Import,
+}
+
+enum Mode {
+    List,
+    Prompt(Prompt, String),
+    Filter(Entries),
+    Form(Box<FormView>),
+    Import(Box<ImportView>),
+    Export(Entries),
+    Audit(Box<AuditView>),
+    Recycle(RecycleView),
+    Message(String, Vec<String>, u16),
+}
+
+struct App {
+    conn: DbConn<SecurityModel>,
+    /// Rivers matching the filter and keyword.
+    rivers: Vec<SecurityModel>,
+    table: TableState,
+    keyword: String,
+    /// Texts of `FILTER_LABELS`.
+    filter: Vec<String>,
+    options: ExportOptions,
+    /// River whose deletion waits for confirmation.
+    deleting: Option<u32>,
+    status: String,
+    mode: Mode,
+    quit: bool,
+}
+
+impl App {
+    fn new(conn: DbConn<SecurityModel>) -> Self {
+        let mut app = App {
+            conn,
+            rivers: Vec::new(),
+            table: TableState::default(),
+            keyword: String::new(),
+            filter: vec![String::new(); FILTER_LABELS.len()],
+            options: ExportOptions::default(),
+            deleting: None,
+            status: String::new(),
+            mode: Mode::List,
+            quit: false,
+        };
+        app.reload();
+        if let Ok(false) = duplicate::is_enforced(&app.conn.instance) {
+            app.status = String::from(
+                "数据库中有完全相同的河道，合并或删除前不禁止重复录入，请用wrs-cli duplicates查看",
+            );
+        }
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                // Windows also reports releases.
+                if key.kind == KeyEventKind::Press {
+                    self.handle(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn reload(&mut self) {
+        self.options.set_keyword(&self.keyword);
+        match self.options.rivers(&self.conn) {
+            Ok(rivers) => self.rivers = rivers,
+            Err(error) => self.status = error,
+        }
+        self.table.select(match self.rivers.len() {
+            0 => None,
+            len => Some(self.table.selected().unwrap_or_default().min(len - 1)),
+        });
+    }
+
+    fn selected(&self) -> Option<&SecurityModel> {
+        self.table
+            .selected()
+            .and_then(|index| self.rivers.get(index))
+    }
+
+    fn handle(&mut self, key: KeyEvent) {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if control && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        self.mode = match mem::replace(&mut self.mode, Mode::List) {
+            Mode::List => {
+                self.list_key(key);
+                return;
+            }
+            Mode::Prompt(prompt, text) => self.prompt_key(prompt, text, key),
+            Mode::Filter(entries) => self.filter_key(entries, key),
+            Mode::Form(view) => self.form_key(view, key),
+            Mode::Import(view) => self.import_key(view, key),
+            Mode::Export(entries) => self.export_key(entries, key),
+            Mode::Audit(view) => self.audit_key(view, key),
+            Mode::Recycle(view) => self.recycle_key(view, key),
+            Mode::Message(title, lines, scroll) => match key.code {
+                KeyCode::Up => Mode::Message(title, lines, scroll.saturating_sub(1)),
+                KeyCode::Down => Mode::Message(title, lines, scroll + 1),
+                _ => Mode::List,
+            },
+        };
+    }
+
+    fn list_key(&mut self, key: KeyEvent) {
+        if let Some(id) = self.deleting.take() {
+            if key.code == KeyCode::Char('y') {
+                self.delete(id);
+            } else {
+                self.status = String::from("已取消删除");
+            }
+            return;
+        }
+        self.status.clear();
+        let last = self.rivers.len().saturating_sub(1);
+        let current = self.table.selected().unwrap_or_default();
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if !self.keyword.is_empty() => {
+                self.keyword.clear();
+                self.reload();
+            }
+            KeyCode::Up => self.table.select(Some(current.saturating_sub(1))),
+            KeyCode::Down => self.table.select(Some((current + 1).min(last))),
+            KeyCode::PageUp => self.table.select(Some(current.saturating_sub(20))),
+            KeyCode::PageDown => self.table.select(Some((current + 20).min(last))),
+            KeyCode::Home => self.table.select(Some(0)),
+            KeyCode::End => self.table.select(Some(last)),
+            KeyCode::Char('/') => self.mode = Mode::Prompt(Prompt::Search, self.keyword.clone()),
+            KeyCode::Char('f') => {
+                self.mode = Mode::Filter(Entries::new(&FILTER_LABELS, self.filter.clone()))
+            }
+            KeyCode::Char('n') => self.mode = Mode::Form(Box::new(FormView::new(RiverForm::new()))),
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some(id) = self.selected().map(|model| model.id) {
+                    match self.conn.find_by_id(id) {
+                        Ok(model) => {
+                            self.mode = Mode::Form(Box::new(FormView::new(RiverForm::edit(model))))
+                        }
+                        Err(error) => self.status = error.to_string(),
+                    }
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(model) = self.selected().cloned() {
+                    self.status = format!(
+                        "确定删除{}？删除的河道将移入回收站，可随时恢复(y/n)",
+                        model.name
+                    );
+                    self.deleting = Some(model.id);
+                }
+            }
+            KeyCode::Char('h') => {
+                if let Some(id) = self.selected().map(|model| model.id) {
+                    self.history(id);
+                }
+            }
+            KeyCode::Char('a') => {
+                if let Some(model) = self.selected().cloned() {
+                    self.audit(Some(model.id), format!("{}的修改记录", model.name));
+                }
+            }
+            KeyCode::Char('A') => self.audit(None, String::from("全部修改记录")),
+            KeyCode::Char('b') => self.recycle(0),
+            KeyCode::Char('i') => self.mode = Mode::Prompt(Prompt::Import, String::new()),
+            KeyCode::Char('x') => {
+                let mut texts = vec![String::new()];
+                texts.extend(self.filter.iter().cloned());
+                texts.extend(vec![
+                    String::new(),
+                    String::from("utf8"),
+                    String::from("否"),
+                    String::new(),
+                ]);
+                self.mode = Mode::Export(Entries::new(&EXPORT_LABELS, texts));
+            }
+            KeyCode::Char('r') => self.reload(),
+            _ => {}
+        }
+    }
+
+    fn delete(&mut self, id: u32) {
+        match self.conn.find_by_id(id) {
+            Ok(model) => {
+                self.conn.set(model);
+                self.status = match self.conn.delete() {
+                    Ok(1) => String::from("删除成功"),
+                    Ok(_) => String::from("删除失败"),
+                    Err(error) => error.to_string(),
+                };
+                *self.conn.model = None;
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+        self.reload();
+    }
+
+    fn history(&mut self, river_id: u32) {
+        match self
+            .conn
+            .find_by_id(river_id)
+            .and_then(|model| model.inspections(&self.conn.instance))
+        {
+            Ok(inspections) => {
+                let lines = inspections
+                    .iter()
+                    .map(|inspection| {
+                        format!(
+                            "{}  淤积深度{}m  淤积阈值{}m  {}",
+                            inspection.time.format("%Y-%m-%d"),
+                            inspection.depth,
+                            inspection.threshold,
+                            inspection.dredging
+                        )
+                    })
+                    .collect();
+                self.mode = Mode::Message(String::from("历史记录"), lines, 0);
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn audit(&mut self, river_id: Option<u32>, title: String) {
+        match audit::entries(&self.conn.instance, river_id, None, None) {
+            Ok(mut entries) if !entries.is_empty() => {
+                entries.reverse();
+                self.mode = Mode::Audit(Box::new(AuditView {
+                    title,
+                    entries,
+                    selected: 0,
+                    confirming: false,
+                }));
+            }
+            Ok(_) => self.status = String::from("没有修改记录"),
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn audit_key(&mut self, mut view: Box<AuditView>, key: KeyEvent) -> Mode {
+        if view.confirming {
+            view.confirming = false;
+            if key.code != KeyCode::Char('y') {
+                self.status = String::from("已取消恢复");
+                return Mode::Audit(view);
+            }
+            let id = view.entries[view.selected].id;
+            self.status = match audit::restore(&mut self.conn, id) {
+                Ok(model) => format!("已恢复编号为{}的河道{}", model.id, model.name),
+                Err(error) => error,
+            };
+            self.reload();
+            return Mode::List;
+        }
+        self.status.clear();
+        let last = view.entries.len() - 1;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Mode::List,
+            KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected = (view.selected + 1).min(last),
+            KeyCode::Home => view.selected = 0,
+            KeyCode::End => view.selected = last,
+            KeyCode::Char('u') => {
+                let entry = &view.entries[view.selected];
+                if entry.old.is_some() {
+                    self.status = format!(
+                        "确定将{}恢复为第{}条修改记录之前的版本？(y/n)",
+                        entry.name(),
+                        entry.id
+                    );
+                    view.confirming = true;
+                } else {
+                    self.status = format!("该记录之前没有{}的数据", entry.name());
+                }
+            }
+            _ => {}
+        }
+        Mode::Audit(view)
+    }
+
+    /// Opens the recycle bin with river `selected` chosen, or goes back to
+    /// the list when it is empty.
+    fn recycle(&mut self, selected: usize) {
+        self.mode = match recycle::deleted_rivers(&self.conn.instance) {
+            Ok(rivers) if rivers.is_empty() => {
+                if self.status.is_empty() {
+                    self.status = String::from("回收站为空");
+                }
+                Mode::List
+            }
+            Ok(rivers) => Mode::Recycle(RecycleView {
+                selected: selected.min(rivers.len() - 1),
+                rivers,
+                confirming: None,
+            }),
+            Err(error) => {
+                self.status = error.to_string();
+                Mode::List
+            }
+        };
+    }
+
+    fn recycle_key(&mut self, mut view: RecycleView, key: KeyEvent) -> Mode {
+        if let Some(action) = view.confirming.take() {
+            if key.code != KeyCode::Char('y') {
+                self.status = String::from("已取消");
+                return Mode::Recycle(view);
+            }
+            let id = view.rivers[view.selected].model.id;
+            self.status = if action == 'u' {
+                match recycle::restore(&self.conn, id) {
+                    Ok(model) => format!("已恢复编号为{}的河道{}", model.id, model.name),
+                    Err(error) => error,
+                }
+            } else {
+                match recycle::purge(&self.conn.instance, id) {
+                    Ok(_) => format!("已永久删除编号为{}的河道", id),
+                    Err(error) => error.to_string(),
+                }
+            };
+            self.reload();
+            self.recycle(view.selected);
+            return mem::replace(&mut self.mode, Mode::List);
+        }
+        self.status.clear();
+        let last = view.rivers.len() - 1;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Mode::List,
+            KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+            KeyCode::Down => view.selected = (view.selected + 1).min(last),
+            KeyCode::Home => view.selected = 0,
+            KeyCode::End => view.selected = last,
+            KeyCode::Char(action @ ('u' | 'p')) => {
+                let name = &view.rivers[view.selected].model.name;
+                self.status = if action == 'u' {
+                    format!("确定恢复{}？(y/n)", name)
+                } else {
+                    format!(
+                        "永久删除后无法恢复，确定永久删除{}及其巡查记录？(y/n)",
+                        name
+                    )
+                };
+                view.confirming = Some(action);
+            }
+            _ => {}
+        }
+        Mode::Recycle(view)
+    }
+
+    fn prompt_key(&mut self, prompt: Prompt, mut text: String, key: KeyEvent) -> Mode {
+        match key.code {
+            KeyCode::Esc => {
+                if let Prompt::Search = prompt {
+                    self.keyword.clear();
+                    self.reload();
+                }
+                return Mode::List;
+            }
+            KeyCode::Enter => {
+                return match prompt {
+                    Prompt::Search => Mode::List,
+                    Prompt::Import => self.read_import(text.trim()),
+                }
+            }
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => text.clear(),
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+        // The list follows the keyword as it is typed.
+        if let Prompt::Search = prompt {
+            self.keyword = text.clone();
+            self.reload();
+        }
+        Mode::Prompt(prompt, text)
+    }
+
+    fn filter_key(&mut self, mut entries: Entries, key: KeyEvent) -> Mode {
+        match key.code {
+            KeyCode::Esc => return Mode::List,
+            KeyCode::Enter => match filter_options(&entries) {
+                Ok(options) => {
+                    self.options = options;
+                    self.filter = entries.texts;
+                    self.reload();
+                    return Mode::List;
+                }
+                Err(error) => self.status = error,
+            },
+            _ => {
+                entries.handle(key);
+            }
+        }
+        Mode::Filter(entries)
+    }
+
+    fn form_key(&mut self, mut view: Box<FormView>, key: KeyEvent) -> Mode {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if view.confirming {
+            view.confirming = false;
+            if key.code != KeyCode::Char('y') {
+                self.status = String::from("已取消保存");
+                return Mode::Form(view);
+            }
+            return match view.form.save() {
+                Ok(model) => {
+                    let id = model.id;
+                    self.conn.set(model);
+                    let result = if id > 0 {
+                        self.conn.update()
+                    } else {
+                        self.conn.insert()
+                    };
+                    *self.conn.model = None;
+                    self.status = match result {
+                        Ok(1) => String::from("保存成功"),
+                        Ok(_) => String::from("保存失败"),
+                        // Kept open to correct, e.g. a duplicate river.
+                        Err(error) => {
+                            self.status = error.to_string();
+                            return Mode::Form(view);
+                        }
+                    };
+                    self.reload();
+                    Mode::List
+                }
+                Err(errors) => {
+                    self.status = errors[0].1.clone();
+                    Mode::Form(view)
+                }
+            };
+        }
+        self.status.clear();
+        match key.code {
+            KeyCode::Esc => return Mode::List,
+            KeyCode::F(2) | KeyCode::Enter => view.confirming = true,
+            KeyCode::Char('s') if control => view.confirming = true,
+            _ => {
+                view.entries.handle(key);
+                view.form.texts = view.entries.texts.clone();
+            }
+        }
+        if view.confirming {
+            match view.form.save() {
+                Ok(_) => self.status = String::from("确定保存？(y/n)"),
+                Err(errors) => {
+                    // Go to the first field in error, as the Windows form does.
+                    let (column, message) = &errors[0];
+                    if let Some(index) = view
+                        .form
+                        .fields
+                        .iter()
+                        .position(|field| field.column == *column)
+                    {
+                        view.entries.selected = index;
+                    }
+                    self.status = message.clone();
+                    view.confirming = false;
+                }
+            }
+        }
+        Mode::Form(view)
+    }
+
+    fn read_import(&mut self, path: &str) -> Mode {
+        let (table, profile) = match import::read_with_profile::<SecurityModel, _>(
+            &self.conn.instance,
+            path,
+            None,
+            None,
+        ) {
+            Ok(result) => result,
+            Err(error) => {
+                self.status = format!("导入失败：{}", error);
+                return Mode::List;
+            }
+        };
+        let mut preview = import::preview::<SecurityModel>(&table, profile.as_ref());
+        import::recompute_derived(&mut preview, true);
+        import::check_duplicates(&mut preview);
+
+        let mut report = vec![table.describe()];
+        if let Some(profile) = profile.as_ref() {
+            report.push(format!("映射方案：{}", profile.name));
+        }
+        report.push(preview.summary());
+        report.extend(preview.all_issues().map(|issue| issue.to_line()));
+        let mut view = ImportView {
+            path: String::from(path),
+            preview,
+            report,
+            policy: 1,
+            outcome: Vec::new(),
+            scroll: 0,
+            rejects: None,
+        };
+        self.resolve(&mut view);
+        Mode::Import(Box::new(view))
+    }
+
+    /// Fills in what committing `view` would do under its policy.
+    fn resolve(&self, view: &mut ImportView) {
+        view.outcome.clear();
+        for row in view.preview.valid_rows() {
+            let resolution = import::resolve(&self.conn, row, POLICIES[view.policy]);
+            let action = match &resolution.action {
+                Action::Insert => String::from("新增"),
+                Action::Update => String::from("更新"),
+                Action::Restore => String::from("从回收站恢复"),
+                Action::Skip(reason) => format!("跳过：{}", reason),
+                Action::Reject(reason) => format!("失败：{}", reason),
+            };
+            view.outcome.push(format!(
+                "第{}行  {}  {}  {}",
+                resolution.row, action, resolution.model.name, resolution.model.area
+            ));
+            for change in resolution.changes.iter() {
+                view.outcome.push(format!(
+                    "        {}：{} → {}",
+                    change.header, change.old, change.new
+                ));
+            }
+        }
+    }
+
+    fn import_key(&mut self, mut view: Box<ImportView>, key: KeyEvent) -> Mode {
+        if let Some(mut path) = view.rejects.take() {
+            match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    self.status = match view.preview.write_rejects(path.trim()) {
+                        Ok(num) => format!("已将{}行无法导入的数据写入{}", num, path.trim()),
+                        Err(error) => error,
+                    }
+                }
+                KeyCode::Backspace => {
+                    path.pop();
+                    view.rejects = Some(path);
+                }
+                KeyCode::Char(c) => {
+                    path.push(c);
+                    view.rejects = Some(path);
+                }
+                _ => view.rejects = Some(path),
+            }
+            return Mode::Import(view);
+        }
+        match key.code {
+            KeyCode::Esc => {
+                self.status = String::from("已取消导入");
+                return Mode::List;
+            }
+            KeyCode::Enter => {
+                let source = format!("{} {}", import::IMPORT_SOURCE, view.path);
+                if let Err(error) = audit::set_source(&self.conn.instance, &source) {
+                    self.status = error.to_string();
+                    return Mode::Import(view);
+                }
+                let summary = import::commit(
+                    &mut self.conn,
+                    view.preview.valid_rows(),
+                    POLICIES[view.policy],
+                );
+                self.status = summary.to_message();
+                self.reload();
+                return Mode::List;
+            }
+            KeyCode::Left | KeyCode::Right => {
+                view.policy = if key.code == KeyCode::Left {
+                    (view.policy + POLICIES.len() - 1) % POLICIES.len()
+                } else {
+                    (view.policy + 1) % POLICIES.len()
+                };
+                self.resolve(&mut view);
+            }
+            KeyCode::Up => view.scroll = view.scroll.saturating_sub(1),
+            KeyCode::Down => view.scroll += 1,
+            KeyCode::PageUp => view.scroll = view.scroll.saturating_sub(20),
+            KeyCode::PageDown => view.scroll += 20,
+            KeyCode::Char('w') if view.preview.rejected_rows().next().is_some() => {
+                view.rejects = Some(String::from("导入错误.xlsx"))
+            }
+            _ => {}
+        }
+        Mode::Import(view)
+    }
+
+    fn export_key(&mut self, mut entries: Entries, key: KeyEvent) -> Mode {
+        match key.code {
+            KeyCode::Esc => return Mode::List,
+            KeyCode::Enter => match self.export(&entries) {
+                Ok(message) => {
+                    self.status = message;
+                    return Mode::List;
+                }
+                Err(error) => self.status = error,
+            },
+            _ => {
+                entries.handle(key);
+            }
+        }
+        Mode::Export(entries)
+    }
+
+    fn export(&self, entries: &Entries) -> Result<String, String> {
+        // The export writes the rivers listed, so the keyword applies too.
+        let mut options = filter_options(entries)?;
+        options.set_keyword(&self.keyword);
+        options.set_columns(entries.text("列"))?;
+        let path = entries.text("文件").trim();
+        if path.is_empty() {
+            return Err(String::from("请填写导出文件"));
+        }
+        if ["是", "y", "yes"].contains(&entries.text("报表").trim()) {
+            options.set_group_by(entries.text("统计分组"))?;
+            let num = report::write_report(&self.conn, path, &options)?;
+            return Ok(format!("报表已生成，共{}条数据", num));
+        }
+        let encoding = TextEncoding::parse(entries.text("编码").trim())
+            .ok_or(format!("未知的编码：{}", entries.text("编码")))?;
+        let num = export::write_file(&self.conn, path, encoding, &options)?;
+        Ok(format!("导出完成，共{}条数据", num))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let keys = match &self.mode {
+            Mode::Form(view) => {
+                draw_form(frame, main, view);
+                FORM_KEYS
+            }
+            _ => {
+                self.draw_list(frame, main);
+                LIST_KEYS
+            }
+        };
+        let keys = match &self.mode {
+            Mode::Prompt(..) => "Enter确定 Esc取消",
+            Mode::Filter(entries) => {
+                draw_dialog(frame, "筛选", entries.lines(&[]));
+                DIALOG_KEYS
+            }
+            Mode::Export(entries) => {
+                let mut lines = entries.lines(&[]);
+                lines.push(Line::raw(""));
+                lines.push(Line::raw(
+                    "按扩展名导出xlsx、csv、json或ndjson；报表填“是”时生成带汇总表的报表，统计分组如辖区,年份时另加统计表；编码为utf8或gbk",
+                ));
+                draw_dialog(frame, "导出", lines);
+                DIALOG_KEYS
+            }
+            Mode::Import(view) => {
+                draw_import(frame, view);
+                if view.rejects.is_some() {
+                    "Enter写入 Esc取消"
+                } else {
+                    "←→冲突处理 ↑↓滚动 w导出错误行 Enter导入 Esc取消"
+                }
+            }
+            Mode::Recycle(view) => {
+                let lines = view
+                    .rivers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, river)| {
+                        let line = format!(
+                            "{}  {}  {}  {}～{}  删除于{}",
+                            river.model.id,
+                            river.model.name,
+                            river.model.area,
+                            river.model.start,
+                            river.model.end,
+                            river.deleted.format(DATE_TIME_FORMAT)
+                        );
+                        if index == view.selected {
+                            Line::styled(line, Style::new().add_modifier(Modifier::REVERSED))
+                        } else {
+                            Line::raw(line)
+                        }
+                    })
+                    .collect();
+                draw_dialog_scrolled(frame, "回收站", lines, view.selected as u16);
+                "↑↓选择 u恢复 p永久删除 Esc关闭"
+            }
+            Mode::Audit(view) => {
+                draw_audit(frame, view);
+                "↑↓选择 u恢复为此次修改前的版本 Esc关闭"
+            }
+            Mode::Message(title, lines, scroll) => {
+                let lines = lines.iter().map(|line| Line::raw(line.clone())).collect();
+                draw_dialog_scrolled(frame, title, lines, *scroll);
+                "↑↓滚动 其他键关闭"
+            }
+            _ => keys,
+        };
+        let footer_text = if self.status.is_empty() {
+            Line::styled(keys, Style::new().fg(Color::DarkGray))
+        } else {
+            Line::styled(self.status.clone(), Style::new().fg(Color::Yellow))
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let fields = SecurityModel::fields();
+        let columns: Vec<_> = LIST_COLUMNS
+            .iter()
+            .filter_map(|column| fields.iter().find(|field| field.column == *column))
+            .collect();
+        let cells: Vec<Vec<String>> = self
+            .rivers
+            .iter()
+            .map(|model| columns.iter().map(|field| (field.display)(model)).collect())
+            .collect();
+        let widths: Vec<Constraint> = columns
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let width = cells
+                    .iter()
+                    .map(|row| text_width(&row[index]))
+                    .chain(Some(text_width(&field.header())))
+                    .max()
+                    .unwrap_or_default();
+                Constraint::Length(width.min(MAX_COLUMN_WIDTH) as u16)
+            })
+            .collect();
+        let header = Row::new(columns.iter().map(|field| Cell::from(field.header())))
+            .style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = cells
+            .into_iter()
+            .zip(self.rivers.iter())
+            .map(|(row, model)| {
+                let style = match model.dredging.as_str() {
+                    wrs_nwg::dredging::NEEDED => Style::new().fg(Color::Red),
+                    wrs_nwg::dredging::SUGGESTED => Style::new().fg(Color::Yellow),
+                    _ => Style::new(),
+                };
+                Row::new(row).style(style)
+            });
+
+        let mut title = format!("河道数据（{}条）", self.rivers.len());
+        if !self.keyword.is_empty() {
+            title = format!("{} 搜索：{}", title, self.keyword);
+        }
+        let filter: Vec<String> = FILTER_LABELS
+            .iter()
+            .zip(self.filter.iter())
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(label, text)| format!("{}={}", label, text.trim()))
+            .collect();
+        if !filter.is_empty() {
+            title = format!("{} 筛选：{}", title, filter.join(" "));
+        }
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::new().borders(Borders::ALL).title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+
+        if let Mode::Prompt(prompt, text) = &self.mode {
+            let title = match prompt {
+                Prompt::Search => "搜索河道名称、辖区、起点、终点或清淤判断，可输入拼音或首字母",
+                Prompt::Import => "导入文件（xlsx、xls、ods、csv、json或ndjson）",
+            };
+            draw_dialog(frame, title, vec![Line::raw(format!("{}▏", text))]);
+        }
+    }
+}
+
+/// Options of the filter boxes in `entries`.
+fn filter_options(entries: &Entries) -> Result<ExportOptions, String> {
+    let mut options = ExportOptions::default();
+    options.set_areas(entries.text("辖区"));
+    options.set_levels(entries.text("等级"))?;
+    options.set_dredging(entries.text("清淤判断"))?;
+    options.set_since(entries.text("起始日期"))?;
+    options.set_until(entries.text("截止日期"))?;
+    options.set_sort(entries.text("排序"))?;
+    Ok(options)
+}
+
+fn draw_form(frame: &mut Frame, area: Rect, view: &FormView) {
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(area);
+    let title = if view.form.original.id > 0 {
+        format!("修改河道 {}", view.form.original.id)
+    } else {
+        String::from("新增河道")
+    };
+    let (_, errors) = view.form.model();
+    let notes: Vec<(String, String)> = errors
+        .into_iter()
+        .filter_map(|(column, message)| {
+            view.form
+                .fields
+                .iter()
+                .find(|field| field.column == column)
+                .map(|field| (field.header(), message))
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(view.entries.lines(&notes))
+            .block(Block::new().borders(Borders::ALL).title(title)),
+        left,
+    );
+
+    let lines = match view.form.assessment() {
+        Ok(assessment) => {
+            let mut lines = Vec::new();
+            for step in assessment.steps.iter() {
+                lines.push(Line::styled(
+                    step.label,
+                    Style::new().add_modifier(Modifier::BOLD),
+                ));
+                lines.push(Line::raw(format!("  {}", step.formula)));
+                lines.push(Line::raw(format!("  = {}", step.result)));
+            }
+            lines.push(Line::raw(""));
+            lines.push(Line::styled(
+                assessment.dredging,
+                Style::new().fg(Color::Cyan),
+            ));
+            lines
+        }
+        Err(message) => vec![Line::styled(message, Style::new().fg(Color::DarkGray))],
+    };
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::new().borders(Borders::ALL).title("计算")),
+        right,
+    );
+}
+
+fn draw_import(frame: &mut Frame, view: &ImportView) {
+    let mut lines: Vec<Line> = view
+        .report
+        .iter()
+        .map(|line| Line::raw(line.clone()))
+        .collect();
+    lines.push(Line::raw(""));
+    lines.push(Line::styled(
+        format!("冲突处理：{}", POLICIES[view.policy].name()),
+        Style::new().fg(Color::Cyan),
+    ));
+    lines.extend(view.outcome.iter().map(|line| Line::raw(line.clone())));
+    if let Some(path) = view.rejects.as_ref() {
+        lines.insert(
+            0,
+            Line::styled(
+                format!("错误工作簿：{}▏", path),
+                Style::new().add_modifier(Modifier::REVERSED),
+            ),
+        );
+    }
+    draw_dialog_scrolled(frame, "导入预览", lines, view.scroll);
+}
+
+/// Draws the changes with the selected one at the top.
+fn draw_audit(frame: &mut Frame, view: &AuditView) {
+    let mut lines = Vec::new();
+    let mut scroll = 0;
+    for (index, entry) in view.entries.iter().enumerate() {
+        if index == view.selected {
+            scroll = lines.len() as u16;
+        }
+        let style = if index == view.selected {
+            Style::new().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::new().add_modifier(Modifier::BOLD)
+        };
+        lines.push(Line::styled(
+            format!(
+                "第{}条  {}  {}  {}  {}  {} {}",
+                entry.id,
+                entry.time.format(DATE_TIME_FORMAT),
+                entry.user,
+                entry.source,
+                entry.action.label(),
+                entry.river_id,
+                entry.name()
+            ),
+            style,
+        ));
+        for (header, old, new) in entry.changes() {
+            lines.push(Line::raw(format!("        {}：{} → {}", header, old, new)));
+        }
+    }
+    draw_dialog_scrolled(frame, &view.title, lines, scroll);
+}
+
+fn draw_dialog(frame: &mut Frame, title: &str, lines: Vec<Line>) {
+    draw_dialog_scrolled(frame, title, lines, 0);
+}
+
+/// Draws `lines` in a box over the middle of the screen.
+fn draw_dialog_scrolled(frame: &mut Frame, title: &str, lines: Vec<Line>, scroll: u16) {
+    let height = (lines.len() as u16 + 2).min(frame.area().height.saturating_sub(2));
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(frame.area());
+    let [area] = Layout::horizontal([Constraint::Percentage(80)])
+        .flex(Flex::Center)
+        .areas(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0))
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .title(String::from(title)),
+            ),
+        area,
+    );
+}
+
+/// Columns `text` takes in a terminal.
+fn text_width(text: &str) -> usize {
+    Line::raw(text).width()
+}
@@ -0,0 +1,115 @@
+use std::{fmt, str::FromStr};
+
+/// A stake number (桩号) such as `K3+250`: kilometres, then metres, along a
+/// line identified by the letters before the kilometre figure.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chainage {
+    /// Line prefix, e.g. `K`, `ZK` or `YK`.
+    pub prefix: String,
+    /// Distance from the line origin in metres.
+    pub metres: f64,
+}
+
+impl Chainage {
+    /// Distance in metres from `start` to `end` when both are stakes on the
+    /// same line.
+    pub fn length(start: &str, end: &str) -> Option<f64> {
+        let start = start.parse::<Chainage>().ok()?;
+        let end = end.parse::<Chainage>().ok()?;
+        if start.prefix == end.prefix {
+            Some(end.metres - start.metres)
+        } else {
+            None
+        }
+    }
+
+    /// Metres of `text` if it is a stake number, for storing alongside the
+    /// original text.
+    pub fn metres_of(text: &str) -> Option<f64> {
+        text.parse::<Chainage>()
+            .ok()
+            .map(|chainage| chainage.metres)
+    }
+
+    /// Writes `text` in canonical stake notation, leaving place names as
+    /// they are.
+    pub fn normalize(text: &str) -> String {
+        match text.parse::<Chainage>() {
+            Ok(chainage) => chainage.to_string(),
+            Err(_) => String::from(text.trim()),
+        }
+    }
+}
+
+/// Checks that a reach given by two stake numbers runs downstream, i.e. the
+/// end lies beyond the start. Place names are not checked.
+pub fn check_reach(start: &str, end: &str) -> Result<(), String> {
+    let (start, end) = match (start.parse::<Chainage>(), end.parse::<Chainage>()) {
+        (Ok(start), Ok(end)) => (start, end),
+        _ => return Ok(()),
+    };
+    if start.prefix != end.prefix {
+        return Err(format!("河道起点{}与终点{}不在同一桩号线上", start, end));
+    }
+    if end.metres <= start.metres {
+        return Err(format!("河道终点桩号{}必须大于起点桩号{}", end, start));
+    }
+    Ok(())
+}
+
+/// Maps full-width characters typed with Chinese input methods to ASCII and
+/// drops whitespace.
+pub(crate) fn half_width(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+impl FromStr for Chainage {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let normalized = half_width(text).to_uppercase();
+        let invalid = || format!("无法识别的桩号：{}", text);
+
+        let digits = normalized
+            .find(|c: char| c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (prefix, rest) = normalized.split_at(digits);
+        if !prefix.ends_with('K') || !prefix.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(invalid());
+        }
+        let (km, m) = rest.split_once('+').ok_or_else(invalid)?;
+        if km.is_empty() || !km.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let km = km.parse::<u32>().map_err(|_| invalid())?;
+        let m = m.parse::<f64>().map_err(|_| invalid())?;
+        if !(0.0..1000.0).contains(&m) {
+            return Err(format!("桩号{}的米数必须小于1000", text));
+        }
+        Ok(Chainage {
+            prefix: String::from(prefix),
+            metres: km as f64 * 1000.0 + m,
+        })
+    }
+}
+
+impl fmt::Display for Chainage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metres = (self.metres * 1000.0).round() / 1000.0;
+        let km = (metres / 1000.0).floor();
+        let m = metres - km * 1000.0;
+        write!(f, "{}{}+{:03}", self.prefix, km, m.trunc())?;
+        let fraction = format!("{:.3}", m.fract());
+        let fraction = fraction.trim_end_matches('0').trim_start_matches('0');
+        if fraction != "." {
+            write!(f, "{}", fraction)?;
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, path::Path};
 
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
 use rusqlite::{Connection, Result, Row, Statement, ToSql};
 
+use crate::{chainage::half_width, migration};
+
+#[derive(Clone, Copy)]
 pub enum DbOpt {
     Create,
     Insert,
     Update,
+    /// Insert, or update the stored record with the same unique key.
+    Upsert,
     Delete,
     Select,
 }
@@ -15,14 +21,23 @@ pub struct DbConn<T: Model> {
     pub model: Box<Option<T>>,
 }
 
+impl<T: Model> Default for DbConn<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Model> DbConn<T> {
     pub fn new() -> Self {
-        Self {
-            instance: Box::new(
-                Connection::open("./water-resources.db").expect("Connect database failed."),
-            ),
+        Self::open("./water-resources.db").expect("Connect database failed.")
+    }
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let instance = Connection::open(path)?;
+        migration::migrate(&instance)?;
+        Ok(Self {
+            instance: Box::new(instance),
             model: Box::new(None),
-        }
+        })
     }
     pub fn set(&mut self, model: T) {
         *self.model = Some(model);
@@ -35,25 +50,31 @@ impl<T: Model> DbConn<T> {
             .execute([])
     }
     pub fn insert(&self) -> Result<usize> {
-        let mut stmt = self.prepare(T::get_sql(DbOpt::Insert).as_str())?;
         if let Some(model) = self.model.borrow() {
-            model.execute(&mut stmt, DbOpt::Insert)
+            model.persist(&self.instance, DbOpt::Insert)
         } else {
             Ok(0)
         }
     }
     pub fn update(&self) -> Result<usize> {
-        let mut stmt = self.prepare(T::get_sql(DbOpt::Update).as_str())?;
         if let Some(model) = self.model.borrow() {
-            model.execute(&mut stmt, DbOpt::Update)
+            model.persist(&self.instance, DbOpt::Update)
+        } else {
+            Ok(0)
+        }
+    }
+    /// Inserts the model, or updates the stored record it conflicts with on
+    /// the model's unique key, in a single `INSERT ... ON CONFLICT` statement.
+    pub fn upsert(&self) -> Result<usize> {
+        if let Some(model) = self.model.borrow() {
+            model.persist(&self.instance, DbOpt::Upsert)
         } else {
             Ok(0)
         }
     }
     pub fn delete(&self) -> Result<usize> {
-        let mut stmt = self.prepare(T::get_sql(DbOpt::Delete).as_str())?;
         if let Some(model) = self.model.borrow() {
-            model.execute(&mut stmt, DbOpt::Delete)
+            model.persist(&self.instance, DbOpt::Delete)
         } else {
             Ok(0)
         }
@@ -100,18 +121,310 @@ impl<T: Model> DbConn<T> {
     }
 }
 
+/// How times are written as text in exports and read back on import.
+pub const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Time layouts accepted on import besides `DATE_TIME_FORMAT`.
+const DATE_TIME_FORMATS: [&str; 7] = [
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d %H:%M",
+    "%Y.%m.%d %H:%M:%S",
+    "%Y年%m月%d日%H:%M:%S",
+    "%Y年%m月%d日%H:%M",
+    "%Y年%m月%d日%H时%M分%S秒",
+];
+
+/// Date layouts accepted on import, read as midnight.
+const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d", "%Y年%m月%d日", "%Y%m%d"];
+
+/// Time of an Excel serial date, counted in days from 1899-12-30, to the
+/// nearest second.
+pub fn excel_time(serial: f64) -> Option<NaiveDateTime> {
+    let seconds = (serial * 86400.0).round() as i64;
+    NaiveDate::from_ymd_opt(1899, 12, 30)?
+        .and_hms_opt(0, 0, 0)?
+        .checked_add_signed(Duration::seconds(seconds))
+}
+
+/// Reads a local time written as `2021-05-03 08:30:00`, `2021/5/3 8:30`,
+/// `2021年5月3日`, a date alone, or an Excel serial date such as `44319`.
+pub fn parse_date_time(text: &str) -> std::result::Result<DateTime<Local>, String> {
+    let invalid = || format!("无法识别的时间：{}", text);
+    let normalized = text
+        .split_whitespace()
+        .map(half_width)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("日 ", "日");
+    if normalized.is_empty() {
+        return Err(invalid());
+    }
+    let naive = std::iter::once(DATE_TIME_FORMAT)
+        .chain(DATE_TIME_FORMATS.iter().copied())
+        .find_map(|format| NaiveDateTime::parse_from_str(&normalized, format).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(&normalized, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .or_else(|| match normalized.parse::<f64>() {
+            // Serial dates from 1900-01-01 to 9999-12-31.
+            Ok(serial) if (1.0..2958466.0).contains(&serial) => excel_time(serial),
+            _ => None,
+        })
+        .ok_or_else(invalid)?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(invalid)
+}
+
 pub enum ModelNameType {
     Column,
     Header,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum FieldType {
+    Integer,
+    Real,
+    Text,
+    DateTime,
+}
+
+#[derive(Clone, Copy)]
+pub enum Bound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+/// Typed value of a field for spreadsheet cells.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FieldValue {
+    Empty,
+    Number(f64),
+    Text(String),
+    DateTime(NaiveDateTime),
+}
+
+/// Describes one column of a model: how it is named in the database and in
+/// spreadsheets, what it holds, and how it is converted from and to text.
+pub struct Field<T> {
+    pub column: &'static str,
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub field_type: FieldType,
+    pub unit: Option<&'static str>,
+    pub required: bool,
+    pub min: Option<Bound>,
+    pub max: Option<Bound>,
+    pub width: f32,
+    /// Text written to exports, read back by `parser`.
+    pub formatter: fn(&T) -> String,
+    /// Text shown in the list view.
+    pub display: fn(&T) -> String,
+    pub parser: fn(&mut T, &str) -> std::result::Result<(), String>,
+}
+
+impl<T> Field<T> {
+    pub fn header(&self) -> String {
+        match self.unit {
+            Some(unit) => format!("{}({})", self.name, unit),
+            None => String::from(self.name),
+        }
+    }
+
+    /// Whether a spreadsheet header names this field. Headers are compared
+    /// after `normalize_header`, so width, case, bracket style and unit
+    /// spelling do not matter, but a header giving a different unit does not
+    /// match.
+    pub fn matches(&self, header: &str) -> bool {
+        let (name, unit) = normalize_header(header);
+        let field_unit = self.unit.map(normalize_unit);
+        if unit.is_some() && field_unit.is_some() && unit != field_unit {
+            return false;
+        }
+        [self.name, self.column]
+            .iter()
+            .chain(self.aliases.iter())
+            .any(|candidate| normalize_header(candidate).0 == name)
+    }
+
+    /// Value to write to a spreadsheet cell: numbers as numbers, times as
+    /// dates, and numbers with a label, such as levels, as the label when the
+    /// parser reads it back to the same value.
+    pub fn value(&self, model: &T) -> FieldValue
+    where
+        T: Clone,
+    {
+        let text = (self.formatter)(model);
+        if text.is_empty() {
+            return FieldValue::Empty;
+        }
+        match self.field_type {
+            FieldType::Integer | FieldType::Real => {
+                let label = self.text(model);
+                if label != text {
+                    return FieldValue::Text(label);
+                }
+                match text.parse::<f64>() {
+                    Ok(number) => FieldValue::Number(number),
+                    Err(_) => FieldValue::Text(text),
+                }
+            }
+            FieldType::DateTime => match NaiveDateTime::parse_from_str(&text, DATE_TIME_FORMAT) {
+                Ok(time) => FieldValue::DateTime(time),
+                Err(_) => FieldValue::Text(text),
+            },
+            FieldType::Text => FieldValue::Text(text),
+        }
+    }
+
+    /// Text to enter the field with: the label of a number with one, such as
+    /// a level, when the parser reads it back to the same value, and the
+    /// formatted value otherwise.
+    pub fn text(&self, model: &T) -> String
+    where
+        T: Clone,
+    {
+        let text = (self.formatter)(model);
+        if self.field_type == FieldType::Integer || self.field_type == FieldType::Real {
+            let label = (self.display)(model);
+            let mut parsed = model.clone();
+            if label != text
+                && (self.parser)(&mut parsed, &label).is_ok()
+                && (self.formatter)(&parsed) == text
+            {
+                return label;
+            }
+        }
+        text
+    }
+
+    /// Whether the field holds nothing worth keeping: no text, or zero for a
+    /// number.
+    pub fn is_blank(&self, model: &T) -> bool {
+        let text = (self.formatter)(model);
+        let text = text.trim();
+        text.is_empty()
+            || ((self.field_type == FieldType::Integer || self.field_type == FieldType::Real)
+                && text.parse::<f64>() == Ok(0.0))
+    }
+
+    pub fn check(&self, text: &str) -> std::result::Result<(), String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return if self.required {
+                Err(format!("请填写{}", self.name))
+            } else {
+                Ok(())
+            };
+        }
+        if self.field_type == FieldType::Integer || self.field_type == FieldType::Real {
+            let value = match text.parse::<f64>() {
+                Ok(value) => value,
+                Err(_) => return Err(format!("{}必须为数字", self.name)),
+            };
+            match self.min {
+                Some(Bound::Inclusive(min)) if value < min => {
+                    return Err(format!("{}不能小于{}", self.name, min))
+                }
+                Some(Bound::Exclusive(min)) if value <= min => {
+                    return Err(format!("{}必须大于{}", self.name, min))
+                }
+                _ => {}
+            }
+            match self.max {
+                Some(Bound::Inclusive(max)) if value > max => {
+                    return Err(format!("{}不能大于{}", self.name, max))
+                }
+                Some(Bound::Exclusive(max)) if value >= max => {
+                    return Err(format!("{}必须小于{}", self.name, max))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Spellings of the same unit found in spreadsheets from other departments.
+const UNIT_SYNONYMS: [(&str, &[&str]); 4] = [
+    ("m", &["米"]),
+    ("km", &["公里", "千米"]),
+    ("m²", &["m2", "㎡", "平方米"]),
+    ("m³", &["m3", "立方米", "方"]),
+];
+
+fn normalize_unit(unit: &str) -> String {
+    let unit = half_width(unit).to_lowercase();
+    UNIT_SYNONYMS
+        .iter()
+        .find(|(canonical, synonyms)| *canonical == unit || synonyms.contains(&unit.as_str()))
+        .map(|(canonical, _)| String::from(*canonical))
+        .unwrap_or(unit)
+}
+
+/// Splits a header such as `河宽（米）` or `River Width [m]` into a
+/// comparable name and unit: full-width characters become ASCII, whitespace,
+/// `_` and `-` are dropped, letters are lower-cased and the unit in trailing
+/// brackets is spelled one way.
+pub fn normalize_header(header: &str) -> (String, Option<String>) {
+    let text: String = half_width(header)
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .map(|c| match c {
+            '[' | '【' | '〔' => '(',
+            ']' | '】' | '〕' => ')',
+            _ => c,
+        })
+        .collect::<String>()
+        .to_lowercase();
+    if text.ends_with(')') {
+        if let Some(open) = text.rfind('(') {
+            let unit = &text[open + 1..text.len() - 1];
+            return (
+                String::from(&text[..open]),
+                if unit.is_empty() {
+                    None
+                } else {
+                    Some(normalize_unit(unit))
+                },
+            );
+        }
+    }
+    (text, None)
+}
+
 pub trait Model<T = Self> {
-    fn get_names(name_type: ModelNameType) -> Vec<String>;
+    fn fields() -> Vec<Field<T>>;
+    fn get_names(name_type: ModelNameType) -> Vec<String> {
+        Self::fields()
+            .iter()
+            .map(|field| match name_type {
+                ModelNameType::Column => String::from(field.column),
+                ModelNameType::Header => field.header(),
+            })
+            .collect()
+    }
+    fn field_by_header(header: &str) -> Option<Field<T>> {
+        Self::fields()
+            .into_iter()
+            .find(|field| field.matches(header))
+    }
+    /// Checks every field value against its descriptor, returning the column
+    /// and message of each violation. Models with rules spanning several
+    /// fields override this and extend `check_fields`.
+    fn validate(model: &T) -> Vec<(&'static str, String)> {
+        check_fields(&Self::fields(), model)
+    }
     fn get_sql(opt: DbOpt) -> String;
     fn get_sql_with_condition(condition: &str, order: (&str, &str), limit: (u32, u32)) -> String {
         let mut condition = condition.trim_start().to_string();
         if condition.len() < 5 {
-            condition = String::from(" WHERE 1=1 ");
+            condition = String::from("WHERE 1=1");
         }
         if condition[..5].to_uppercase() != "WHERE" {
             condition = format!(" WHERE {} ", condition);
@@ -133,4 +446,20 @@ pub trait Model<T = Self> {
     }
     fn from_row(row: &Row) -> Result<T>;
     fn execute(&self, stmt: &mut Statement, opt: DbOpt) -> Result<usize>;
+    /// Writes the model to the database. Models stored across several tables
+    /// override this to run more than the single statement of `get_sql`.
+    fn persist(&self, conn: &Connection, opt: DbOpt) -> Result<usize> {
+        let mut stmt = conn.prepare(Self::get_sql(opt).as_str())?;
+        self.execute(&mut stmt, opt)
+    }
+}
+
+pub fn check_fields<T>(fields: &[Field<T>], model: &T) -> Vec<(&'static str, String)> {
+    fields
+        .iter()
+        .filter_map(|field| match field.check(&(field.formatter)(model)) {
+            Ok(()) => None,
+            Err(message) => Some((field.column, message)),
+        })
+        .collect()
 }
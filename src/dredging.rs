@@ -0,0 +1,216 @@
+use crate::security_model::SecurityModel;
+
+/// Columns computed from the other inputs rather than entered.
+pub const DERIVED_COLUMNS: [&str; 3] = ["channel_width", "threshold", "dredging"];
+/// Columns `assess` reads.
+pub const INPUT_COLUMNS: [&str; 6] = ["area", "ratio", "river_width", "line", "safe", "depth"];
+
+pub const NOT_NEEDED: &str = "不需要清淤.";
+pub const SUGGESTED: &str = "建议对该河道进行清淤.";
+pub const NEEDED: &str = "需要对该河道进行清淤.";
+
+/// Verdict named by its full text or a short form such as "需要" or "needed".
+pub fn parse_verdict(text: &str) -> Option<&'static str> {
+    let text = text.trim();
+    match text.to_lowercase().as_str() {
+        "不需要" | "不需要清淤" | "none" | "no" => Some(NOT_NEEDED),
+        "建议" | "建议清淤" | "suggested" => Some(SUGGESTED),
+        "需要" | "需要清淤" | "needed" | "yes" => Some(NEEDED),
+        _ => [NOT_NEEDED, SUGGESTED, NEEDED]
+            .iter()
+            .find(|verdict| verdict.trim_end_matches('.') == text.trim_end_matches('.'))
+            .copied(),
+    }
+}
+
+/// Administrative level of the jurisdiction, which sets the siltation limits
+/// of channels without side slopes.
+#[derive(Clone, Copy, PartialEq)]
+enum AreaClass {
+    City,
+    County,
+    General,
+}
+
+impl AreaClass {
+    fn name(self) -> &'static str {
+        match self {
+            AreaClass::City => "市级",
+            AreaClass::County => "县（区）级",
+            AreaClass::General => "一般地区",
+        }
+    }
+}
+
+fn area_class(area: &str) -> AreaClass {
+    if area.contains("一般") || area.contains("乡") {
+        AreaClass::General
+    } else if area.contains("县") || area.contains("区") {
+        AreaClass::County
+    } else if area.contains("市") {
+        AreaClass::City
+    } else {
+        AreaClass::General
+    }
+}
+
+/// One line of the calculation, as shown in assessment reports.
+pub struct Step {
+    pub label: &'static str,
+    /// Formula with the inputs substituted.
+    pub formula: String,
+    pub result: String,
+}
+
+impl Step {
+    fn new(label: &'static str, formula: String, result: String) -> Self {
+        Step {
+            label,
+            formula,
+            result,
+        }
+    }
+}
+
+/// `value` to at most three decimals, without trailing zeros.
+pub fn number_text(value: f32) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        String::from("0")
+    } else {
+        String::from(text)
+    }
+}
+
+/// Channel width, siltation threshold and verdict of one river, with the
+/// steps that led to them.
+pub struct Assessment {
+    pub channel_width: f32,
+    pub threshold: f32,
+    pub dredging: &'static str,
+    pub steps: Vec<Step>,
+}
+
+/// Runs the dredging rules on the measured inputs of `model`: jurisdiction,
+/// slope ratio, river width, design flood level, freeboard and depth.
+pub fn assess(model: &SecurityModel) -> Assessment {
+    let area = area_class(&model.area);
+    let ratio = model.ratio;
+    let depth = model.depth;
+    let river_width = model.river_width;
+    let mut steps = Vec::new();
+
+    if ratio == 0.0 {
+        // Verdicts by the depth they apply up to; the last applies above.
+        let (threshold, limits): (f32, &[(f32, &'static str)]) = match area {
+            AreaClass::General => (47.0, &[(47.0, NOT_NEEDED), (61.0, SUGGESTED)]),
+            AreaClass::County => (19.0, &[(33.0, NOT_NEEDED)]),
+            AreaClass::City => (19.0, &[(19.0, NOT_NEEDED)]),
+        };
+        let (rule, dredging) = match limits.iter().position(|(limit, _)| depth <= *limit) {
+            Some(index) => (format!("d ≤ {} m", limits[index].0), limits[index].1),
+            None => (format!("d > {} m", limits[limits.len() - 1].0), NEEDED),
+        };
+        // Beyond the first limit the threshold is the limit the depth falls under.
+        let threshold = match area {
+            AreaClass::General if depth > 47.0 => 61.0,
+            _ => threshold,
+        };
+        steps.push(Step::new(
+            "辖区类别",
+            format!("无边坡河道，{}", model.area),
+            String::from(area.name()),
+        ));
+        steps.push(Step::new(
+            "河槽宽度 b",
+            format!("b = B = {}", number_text(river_width)),
+            format!("{} m", number_text(river_width)),
+        ));
+        steps.push(Step::new(
+            "淤积阈值 T",
+            format!("按{}取值", area.name()),
+            format!("{} m", number_text(threshold)),
+        ));
+        steps.push(Step::new(
+            "清淤判断",
+            format!("d = {} m，{}", number_text(depth), rule),
+            String::from(dredging),
+        ));
+        return Assessment {
+            channel_width: river_width,
+            threshold,
+            dredging,
+            steps,
+        };
+    }
+
+    let height = model.line + model.safe;
+    let slope_ratio = 1.0 / ratio;
+    let channel_width = river_width - 2.0 * height * ratio;
+    let threshold = ((((0.04 / ratio) * (height.powi(2) * slope_ratio + channel_width * height)
+        + channel_width.powi(2) / 4.0 * slope_ratio.powi(2))
+        - channel_width / 2.0 * slope_ratio)
+        * 100.0)
+        .round()
+        / 100.0;
+    let dredging = if depth <= threshold {
+        NOT_NEEDED
+    } else {
+        NEEDED
+    };
+    steps.push(Step::new(
+        "计算高度 h",
+        format!(
+            "h = H + Δ = {} + {}",
+            number_text(model.line),
+            number_text(model.safe)
+        ),
+        format!("{} m", number_text(height)),
+    ));
+    steps.push(Step::new(
+        "边坡系数 s",
+        format!("s = 1 / m = 1 / {}", number_text(ratio)),
+        number_text(slope_ratio),
+    ));
+    steps.push(Step::new(
+        "河槽宽度 b",
+        format!(
+            "b = B − 2hm = {} − 2 × {} × {}",
+            number_text(river_width),
+            number_text(height),
+            number_text(ratio)
+        ),
+        format!("{} m", number_text(channel_width)),
+    ));
+    steps.push(Step::new(
+        "淤积阈值 T",
+        String::from("T = (0.04 / m)(h²s + bh) + b²s² / 4 − bs / 2"),
+        format!("{} m", number_text(threshold)),
+    ));
+    steps.push(Step::new(
+        "清淤判断",
+        format!(
+            "d = {} m {} T",
+            number_text(depth),
+            if depth <= threshold { "≤" } else { ">" }
+        ),
+        String::from(dredging),
+    ));
+    Assessment {
+        channel_width,
+        threshold,
+        dredging,
+        steps,
+    }
+}
+
+impl SecurityModel {
+    /// Replaces the derived columns with values computed from the inputs.
+    pub fn recompute(&mut self) {
+        let assessment = assess(self);
+        self.channel_width = assessment.channel_width;
+        self.threshold = assessment.threshold;
+        self.dredging = String::from(assessment.dredging);
+    }
+}
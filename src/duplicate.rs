@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use chrono::Local;
+use rusqlite::{params, Connection, Result};
+
+use crate::{
+    audit,
+    chainage::{half_width, Chainage},
+    db::{DbConn, DbOpt, Model},
+    search,
+    security_model::SecurityModel,
+};
+
+/// Unique index on the name, jurisdiction and ends of the rivers outside the
+/// recycle bin, the key `DbConn::upsert` writes rivers by.
+pub const IDENTITY_INDEX: &str = "rivers_identity";
+
+/// Source logged for the changes made by `merge`.
+const MERGE_SOURCE: &str = "合并重复河道";
+
+/// Whether the identity index exists, i.e. no two rivers outside the
+/// recycle bin can share their name, jurisdiction and ends.
+pub fn is_enforced(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type='index' AND name=?1",
+        [IDENTITY_INDEX],
+        |row| row.get::<_, u32>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Creates the identity index unless rivers outside the recycle bin are
+/// still stored more than once; those are left as they are, listed by
+/// `groups` for the user to merge or delete. Returns whether the index
+/// exists.
+pub(crate) fn create_index(conn: &Connection) -> Result<bool> {
+    if is_enforced(conn)? {
+        return Ok(true);
+    }
+    let stored_twice: bool = conn.query_row(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM rivers
+            WHERE deleted IS NULL AND name IS NOT NULL AND area IS NOT NULL
+                AND start IS NOT NULL AND end IS NOT NULL
+            GROUP BY name, area, start, end
+            HAVING count(*) > 1
+        )"#,
+        [],
+        |row| row.get(0),
+    )?;
+    if stored_twice {
+        return Ok(false);
+    }
+    conn.execute_batch(&format!(
+        "CREATE UNIQUE INDEX {} ON rivers (name, area, start, end) WHERE deleted IS NULL",
+        IDENTITY_INDEX
+    ))?;
+    Ok(true)
+}
+
+/// How the rivers of a group resemble each other.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Similarity {
+    /// Some of them share their name, jurisdiction and ends, stored before
+    /// the identity was enforced; the index waits until they are merged.
+    Identical,
+    /// Same name, jurisdiction and ends once width, whitespace and case are
+    /// ignored.
+    Spelling,
+    /// Same name and jurisdiction with overlapping stake reaches.
+    Overlapping,
+}
+
+impl Similarity {
+    /// Name given by the API.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Similarity::Identical => "identical",
+            Similarity::Spelling => "spelling",
+            Similarity::Overlapping => "overlapping",
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Similarity::Identical => "其中有河道名称、辖区、起点和终点完全相同",
+            Similarity::Spelling => "名称、辖区、起点和终点仅写法不同",
+            Similarity::Overlapping => "同名同辖区的河段重叠",
+        }
+    }
+}
+
+/// Rivers that are likely the same river entered more than once.
+#[derive(Clone)]
+pub struct DuplicateGroup {
+    /// By id.
+    pub rivers: Vec<SecurityModel>,
+    pub similarity: Similarity,
+}
+
+/// `text` with width, whitespace and case evened out.
+fn comparable(text: &str) -> String {
+    half_width(text).to_lowercase()
+}
+
+fn comparable_end(text: &str) -> String {
+    Chainage::normalize(&half_width(text)).to_lowercase()
+}
+
+/// Whether both reaches are given by stakes on the same line and share some
+/// length.
+fn overlap(a: &SecurityModel, b: &SecurityModel) -> bool {
+    let reach = |model: &SecurityModel| {
+        let start = model.start.parse::<Chainage>().ok()?;
+        let end = model.end.parse::<Chainage>().ok()?;
+        if start.prefix == end.prefix {
+            Some((start.prefix, start.metres, end.metres))
+        } else {
+            None
+        }
+    };
+    match (reach(a), reach(b)) {
+        (Some((line_a, start_a, end_a)), Some((line_b, start_b, end_b))) => {
+            line_a == line_b && start_a.max(start_b) < end_a.min(end_b)
+        }
+        _ => false,
+    }
+}
+
+/// Groups of rivers outside the recycle bin whose name and jurisdiction
+/// match once width, whitespace and case are ignored, and whose ends match
+/// the same way or whose stake reaches overlap, including rivers stored
+/// twice before the identity was enforced. Ordered by the first id of each
+/// group.
+pub fn groups(conn: &Connection) -> Result<Vec<DuplicateGroup>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} ORDER BY id ASC",
+        SecurityModel::get_sql(DbOpt::Select)
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut named: BTreeMap<(String, String), Vec<SecurityModel>> = BTreeMap::new();
+    while let Some(row) = rows.next()? {
+        let model = SecurityModel::from_row(row)?;
+        named
+            .entry((comparable(&model.name), comparable(&model.area)))
+            .or_default()
+            .push(model);
+    }
+
+    let mut groups = Vec::new();
+    for rivers in named.into_values().filter(|rivers| rivers.len() > 1) {
+        let ends: Vec<(String, String)> = rivers
+            .iter()
+            .map(|model| (comparable_end(&model.start), comparable_end(&model.end)))
+            .collect();
+        // Group of each river, merged pairwise.
+        let mut group: Vec<usize> = (0..rivers.len()).collect();
+        for i in 0..rivers.len() {
+            for j in i + 1..rivers.len() {
+                if ends[i] == ends[j] || overlap(&rivers[i], &rivers[j]) {
+                    let (from, to) = (group[j], group[i]);
+                    for index in group.iter_mut() {
+                        if *index == from {
+                            *index = to;
+                        }
+                    }
+                }
+            }
+        }
+        for (first, (start, end)) in ends.iter().enumerate() {
+            let members: Vec<usize> = (0..rivers.len())
+                .filter(|index| group[*index] == first)
+                .collect();
+            if members.len() > 1 {
+                let members: Vec<SecurityModel> = members
+                    .into_iter()
+                    .map(|index| rivers[index].clone())
+                    .collect();
+                let similarity = if members.iter().any(|model| {
+                    comparable_end(&model.start) != *start || comparable_end(&model.end) != *end
+                }) {
+                    Similarity::Overlapping
+                } else if members.iter().enumerate().any(|(index, model)| {
+                    members[index + 1..]
+                        .iter()
+                        .any(|other| model.is_same_river(other))
+                }) {
+                    Similarity::Identical
+                } else {
+                    Similarity::Spelling
+                };
+                groups.push(DuplicateGroup {
+                    rivers: members,
+                    similarity,
+                });
+            }
+        }
+    }
+    groups.sort_by_key(|group| group.rivers[0].id);
+    Ok(groups)
+}
+
+/// Merges rivers `others` into river `keep`, which must share their name and
+/// jurisdiction once width, whitespace and case are ignored: inspections of
+/// the others at times `keep` has none are copied to it, and the others are
+/// moved to the recycle bin with their own inspections. Creates the identity
+/// index if no river is stored twice any more, and returns whether it
+/// exists.
+pub fn merge(conn: &DbConn<SecurityModel>, keep: u32, others: &[u32]) -> Result<bool, String> {
+    let not_found = |id: u32| format!("找不到编号为{}的河道", id);
+    let kept = conn.find_by_id(keep).map_err(|_| not_found(keep))?;
+    for id in others {
+        let other = conn.find_by_id(*id).map_err(|_| not_found(*id))?;
+        if *id == keep {
+            return Err(format!("编号为{}的河道既要保留又要合并", keep));
+        }
+        if comparable(&other.name) != comparable(&kept.name)
+            || comparable(&other.area) != comparable(&kept.area)
+        {
+            return Err(format!(
+                "编号为{}的河道与编号为{}的河道名称或辖区不同，不能合并",
+                id, keep
+            ));
+        }
+    }
+
+    let sql_error = |error: rusqlite::Error| error.to_string();
+    let instance = &conn.instance;
+    let (user, source) = audit::context(instance).map_err(sql_error)?;
+    audit::set_context(instance, &user, MERGE_SOURCE).map_err(sql_error)?;
+    let result = (|| -> Result<()> {
+        let tx = instance.unchecked_transaction()?;
+        let old = audit::snapshot(instance, keep)?;
+        for id in others {
+            instance.execute(
+                r#"INSERT INTO inspections(river_id, time, depth, threshold, dredging)
+                SELECT ?1, time, depth, threshold, dredging FROM inspections AS other
+                WHERE river_id = ?2 AND NOT EXISTS (
+                    SELECT 1 FROM inspections WHERE river_id = ?1 AND time = other.time
+                )"#,
+                params![keep, id],
+            )?;
+            let deleted = audit::snapshot(instance, *id)?;
+            instance.execute(
+                &SecurityModel::get_sql(DbOpt::Delete),
+                params![id, Local::now()],
+            )?;
+            search::unindex(instance, *id)?;
+            audit::record(instance, *id, deleted, None)?;
+        }
+        search::index(instance, keep)?;
+        audit::record(instance, keep, old, audit::snapshot(instance, keep)?)?;
+        tx.commit()
+    })();
+    audit::set_context(instance, &user, &source).map_err(sql_error)?;
+    result.map_err(sql_error)?;
+    create_index(instance).map_err(sql_error)
+}
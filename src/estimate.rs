@@ -0,0 +1,245 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::security_model::SecurityModel;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DredgingMethod {
+    Mechanical,
+    Hydraulic,
+}
+
+impl DredgingMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DredgingMethod::Mechanical => "机械清淤",
+            DredgingMethod::Hydraulic => "水力冲挖",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "mechanical" | "机械" | "机械清淤" => Some(DredgingMethod::Mechanical),
+            "hydraulic" | "水力" | "水力冲挖" => Some(DredgingMethod::Hydraulic),
+            _ => None,
+        }
+    }
+}
+
+/// Unit prices used to turn dredged volume into cost, kept in the
+/// `unit_prices` table so each office can maintain its own rates.
+#[derive(Clone, Copy)]
+pub struct PriceCatalog {
+    /// 元/m³
+    pub mechanical: f64,
+    /// 元/m³
+    pub hydraulic: f64,
+    /// 元/(m³·km)
+    pub haulage: f64,
+    /// 元/m³
+    pub disposal: f64,
+}
+
+impl Default for PriceCatalog {
+    fn default() -> Self {
+        Self {
+            mechanical: 18.0,
+            hydraulic: 25.0,
+            haulage: 1.2,
+            disposal: 10.0,
+        }
+    }
+}
+
+impl PriceCatalog {
+    /// Item key, name and unit of every price, in display order.
+    pub const ITEMS: [(&'static str, &'static str, &'static str); 4] = [
+        ("mechanical", "机械清淤", "元/m³"),
+        ("hydraulic", "水力冲挖", "元/m³"),
+        ("haulage", "淤泥运输", "元/(m³·km)"),
+        ("disposal", "淤泥处置", "元/m³"),
+    ];
+
+    pub fn get(&self, item: &str) -> Option<f64> {
+        match item {
+            "mechanical" => Some(self.mechanical),
+            "hydraulic" => Some(self.hydraulic),
+            "haulage" => Some(self.haulage),
+            "disposal" => Some(self.disposal),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, item: &str, price: f64) -> bool {
+        match item {
+            "mechanical" => self.mechanical = price,
+            "hydraulic" => self.hydraulic = price,
+            "haulage" => self.haulage = price,
+            "disposal" => self.disposal = price,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Stored prices, falling back to the defaults for items never set.
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let mut catalog = Self::default();
+        for (item, _, _) in Self::ITEMS.iter() {
+            if let Some(price) = conn
+                .query_row(
+                    "SELECT price FROM unit_prices WHERE item=?",
+                    [item],
+                    |row| row.get(0),
+                )
+                .optional()?
+            {
+                catalog.set(item, price);
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn save(&self, conn: &Connection) -> Result<()> {
+        for (item, _, _) in Self::ITEMS.iter() {
+            conn.execute(
+                "INSERT OR REPLACE INTO unit_prices(item, price) VALUES(?1, ?2)",
+                params![item, self.get(item)],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EstimateOptions {
+    pub method: DredgingMethod,
+    /// Haulage distance to the disposal site in km.
+    pub distance: f64,
+    /// Siltation depth left in the channel after dredging, in m. Zero
+    /// restores the design bed.
+    pub target: f64,
+}
+
+impl Default for EstimateOptions {
+    fn default() -> Self {
+        Self {
+            method: DredgingMethod::Mechanical,
+            distance: 5.0,
+            target: 0.0,
+        }
+    }
+}
+
+pub struct Cost {
+    pub dredging: f64,
+    pub haulage: f64,
+    pub disposal: f64,
+}
+
+impl Cost {
+    pub fn total(&self) -> f64 {
+        self.dredging + self.haulage + self.disposal
+    }
+}
+
+pub struct Estimate {
+    pub river_id: u32,
+    pub name: String,
+    pub area: String,
+    /// Reach length in m, known only when both ends are stake numbers.
+    pub length: Option<f64>,
+    /// Siltation above the target depth, in m.
+    pub excess: f64,
+    /// Silt cross-section to remove, in m².
+    pub section: f64,
+    /// Dredged volume in m³.
+    pub volume: Option<f64>,
+    pub cost: Option<Cost>,
+}
+
+/// Area in m² of the trapezoidal section filled from the bed up to `height`.
+/// Rectangular sections (no slope ratio) use the full river width.
+pub fn section_area(model: &SecurityModel, height: f64) -> f64 {
+    if height <= 0.0 {
+        return 0.0;
+    }
+    let ratio = model.ratio as f64;
+    let bottom = if ratio == 0.0 {
+        model.river_width as f64
+    } else {
+        model.channel_width as f64
+    };
+    (bottom.max(0.0) + ratio * height) * height
+}
+
+pub fn estimate(
+    model: &SecurityModel,
+    catalog: &PriceCatalog,
+    options: &EstimateOptions,
+) -> Estimate {
+    let depth = model.depth as f64;
+    let excess = (depth - options.target).max(0.0);
+    let section = section_area(model, depth) - section_area(model, depth.min(options.target));
+    let length = model.reach_length();
+    let volume = length.map(|length| section * length);
+    let cost = volume.map(|volume| Cost {
+        dredging: volume
+            * match options.method {
+                DredgingMethod::Mechanical => catalog.mechanical,
+                DredgingMethod::Hydraulic => catalog.hydraulic,
+            },
+        haulage: volume * catalog.haulage * options.distance,
+        disposal: volume * catalog.disposal,
+    });
+    Estimate {
+        river_id: model.id,
+        name: model.name.clone(),
+        area: model.area.clone(),
+        length,
+        excess,
+        section,
+        volume,
+        cost,
+    }
+}
+
+pub struct AreaTotal {
+    pub area: String,
+    pub reaches: usize,
+    /// Reaches left out of the totals because their length is unknown.
+    pub missing: usize,
+    pub length: f64,
+    pub volume: f64,
+    pub cost: f64,
+}
+
+/// Totals per jurisdiction, in order of first appearance.
+pub fn totals_by_area(estimates: &[Estimate]) -> Vec<AreaTotal> {
+    let mut totals: Vec<AreaTotal> = Vec::new();
+    for estimate in estimates {
+        let index = match totals.iter().position(|total| total.area == estimate.area) {
+            Some(index) => index,
+            None => {
+                totals.push(AreaTotal {
+                    area: estimate.area.clone(),
+                    reaches: 0,
+                    missing: 0,
+                    length: 0.0,
+                    volume: 0.0,
+                    cost: 0.0,
+                });
+                totals.len() - 1
+            }
+        };
+        let total = &mut totals[index];
+        total.reaches += 1;
+        match (estimate.length, estimate.volume, &estimate.cost) {
+            (Some(length), Some(volume), Some(cost)) => {
+                total.length += length;
+                total.volume += volume;
+                total.cost += cost.total();
+            }
+            _ => total.missing += 1,
+        }
+    }
+    totals
+}
@@ -0,0 +1,520 @@
+use std::{fs, path::Path};
+
+use chrono::{DateTime, Duration, Local, Timelike};
+use encoding_rs::GBK;
+use rusqlite::ToSql;
+use serde_json::{Map, Value};
+use simple_excel_writer::{CellValue, Column, Row, ToCellValue, Workbook};
+
+use crate::{
+    db::{normalize_header, parse_date_time, DbConn, Field, FieldValue, Model, DATE_TIME_FORMAT},
+    dredging, forecast, import, search,
+    security_model::{parse_level, SecurityModel},
+    statistics::GroupBy,
+};
+
+/// Columns appended after the fields, computed on export and ignored on
+/// import.
+pub const FORECAST_HEADERS: [&str; 2] = ["预测清淤年份", "置信区间(95%)"];
+
+fn cell(value: FieldValue) -> CellValue {
+    match value {
+        FieldValue::Empty => CellValue::Blank(1),
+        FieldValue::Number(number) => number.to_cell_value(),
+        FieldValue::Text(text) => text.to_cell_value(),
+        FieldValue::DateTime(time) => time.to_cell_value(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextEncoding {
+    Utf8,
+    /// For Excel on Chinese Windows, which opens CSV files as GBK.
+    Gbk,
+}
+
+impl TextEncoding {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.to_lowercase().as_str() {
+            "utf8" | "utf-8" => Some(TextEncoding::Utf8),
+            "gbk" | "gb2312" | "gb18030" => Some(TextEncoding::Gbk),
+            _ => None,
+        }
+    }
+}
+
+/// A column of an export: a field, by its index in `SecurityModel::fields`,
+/// or one of `FORECAST_HEADERS`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportColumn {
+    Field(usize),
+    Forecast(usize),
+}
+
+impl ExportColumn {
+    /// Column named by its header, alias or column name.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(index) = SecurityModel::fields()
+            .iter()
+            .position(|field| field.matches(text))
+        {
+            return Ok(ExportColumn::Field(index));
+        }
+        let (name, _) = normalize_header(text);
+        FORECAST_HEADERS
+            .iter()
+            .position(|header| normalize_header(header).0 == name)
+            .map(ExportColumn::Forecast)
+            .ok_or_else(|| format!("无法识别的列：{}", text))
+    }
+
+    pub(crate) fn header(self, fields: &[Field<SecurityModel>]) -> String {
+        match self {
+            ExportColumn::Field(index) => fields[index].header(),
+            ExportColumn::Forecast(index) => String::from(FORECAST_HEADERS[index]),
+        }
+    }
+
+    /// Key of the column in JSON records.
+    fn key(self, fields: &[Field<SecurityModel>]) -> String {
+        match self {
+            ExportColumn::Field(index) => String::from(fields[index].column),
+            ExportColumn::Forecast(index) => String::from(FORECAST_HEADERS[index]),
+        }
+    }
+
+    fn width(self, fields: &[Field<SecurityModel>]) -> f32 {
+        match self {
+            ExportColumn::Field(index) => fields[index].width,
+            ExportColumn::Forecast(_) => 15.0,
+        }
+    }
+}
+
+/// Time written as `text`, `None` when it is blank.
+pub fn parse_since(text: &str) -> Result<Option<DateTime<Local>>, String> {
+    match text.trim() {
+        "" => Ok(None),
+        text => parse_date_time(text).map(Some),
+    }
+}
+
+/// Time just after `text`, for a range that includes it to the second, or
+/// the whole day for a date alone. `None` when it is blank.
+pub fn parse_until(text: &str) -> Result<Option<DateTime<Local>>, String> {
+    match text.trim() {
+        "" => Ok(None),
+        text => {
+            let time = parse_date_time(text)?;
+            Ok(Some(if time.num_seconds_from_midnight() == 0 {
+                time + Duration::days(1)
+            } else {
+                time + Duration::seconds(1)
+            }))
+        }
+    }
+}
+
+/// Splits a list typed as "a,b" or "a，b、c", dropping blank items.
+fn split_list(text: &str) -> impl Iterator<Item = &str> {
+    text.split(&[',', '，', '、', ';', '；'][..])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// Which rivers an export writes, with which columns and in which order.
+/// The default writes every river and column, ordered by id.
+#[derive(Clone, Default)]
+pub struct ExportOptions {
+    /// Words each river's text or pinyin has to contain, see
+    /// `search::expression`.
+    pub keyword: String,
+    /// Rivers whose jurisdiction contains any of these, e.g. a county.
+    pub areas: Vec<String>,
+    pub levels: Vec<u32>,
+    /// Dredging verdicts, see `dredging::parse_verdict`.
+    pub dredging: Vec<&'static str>,
+    /// Latest inspection at or after this time.
+    pub since: Option<DateTime<Local>>,
+    /// Latest inspection before this time.
+    pub before: Option<DateTime<Local>>,
+    /// Columns in the order written, every column when empty.
+    pub columns: Vec<ExportColumn>,
+    /// Field columns to sort by, descending when set, before the id.
+    pub sort: Vec<(&'static str, bool)>,
+    /// Columns of the statistics sheet of a report, which has none when
+    /// empty.
+    pub group_by: Vec<GroupBy>,
+}
+
+impl ExportOptions {
+    pub fn set_keyword(&mut self, text: &str) {
+        self.keyword = String::from(text.trim());
+    }
+
+    /// Sets the jurisdictions from a list such as "望江县，太湖县".
+    pub fn set_areas(&mut self, text: &str) {
+        self.areas = split_list(text).map(String::from).collect();
+    }
+
+    /// Sets the levels from a list of numbers or labels such as "1,第二级".
+    pub fn set_levels(&mut self, text: &str) -> Result<(), String> {
+        self.levels = split_list(text)
+            .map(parse_level)
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Sets the verdicts from a list such as "需要,建议".
+    pub fn set_dredging(&mut self, text: &str) -> Result<(), String> {
+        self.dredging = split_list(text)
+            .map(|item| {
+                dredging::parse_verdict(item).ok_or_else(|| format!("无法识别的清淤判断：{}", item))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Sets the earliest inspection time, clearing it when `text` is blank.
+    pub fn set_since(&mut self, text: &str) -> Result<(), String> {
+        self.since = parse_since(text)?;
+        Ok(())
+    }
+
+    /// Sets the latest inspection time, clearing it when `text` is blank,
+    /// see `parse_until`.
+    pub fn set_until(&mut self, text: &str) -> Result<(), String> {
+        self.before = parse_until(text)?;
+        Ok(())
+    }
+
+    /// Sets the columns from a list of headers or column names.
+    pub fn set_columns(&mut self, text: &str) -> Result<(), String> {
+        self.columns = split_list(text)
+            .map(ExportColumn::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Sets the sort from a list of columns, each optionally followed by
+    /// ":desc" or ":降序", e.g. "辖区,淤积深度:desc".
+    pub fn set_sort(&mut self, text: &str) -> Result<(), String> {
+        let fields = SecurityModel::fields();
+        self.sort = split_list(text)
+            .map(|item| {
+                let (name, order) = match item.rfind(&[':', '：'][..]) {
+                    Some(index) => {
+                        let (name, order) = item.split_at(index);
+                        (name, order.chars().skip(1).collect::<String>())
+                    }
+                    None => (item, String::new()),
+                };
+                let descending = match order.trim().to_lowercase().as_str() {
+                    "" | "asc" | "升序" => false,
+                    "desc" | "降序" => true,
+                    order => return Err(format!("无法识别的排序方式：{}", order)),
+                };
+                match ExportColumn::parse(name)? {
+                    ExportColumn::Field(index) => Ok((fields[index].column, descending)),
+                    ExportColumn::Forecast(index) => {
+                        Err(format!("不能按{}排序", FORECAST_HEADERS[index]))
+                    }
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Sets the statistics groups from a list such as "辖区,year".
+    pub fn set_group_by(&mut self, text: &str) -> Result<(), String> {
+        self.group_by = split_list(text)
+            .map(GroupBy::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    pub(crate) fn columns(&self) -> Vec<ExportColumn> {
+        if self.columns.is_empty() {
+            (0..SecurityModel::fields().len())
+                .map(ExportColumn::Field)
+                .chain((0..FORECAST_HEADERS.len()).map(ExportColumn::Forecast))
+                .collect()
+        } else {
+            self.columns.clone()
+        }
+    }
+
+    /// Rivers matching the filters, in export order.
+    pub fn rivers(&self, conn: &DbConn<SecurityModel>) -> Result<Vec<SecurityModel>, String> {
+        let expression = search::expression(&self.keyword);
+        let mut conditions = vec![String::from("1=1")];
+        let mut params: Vec<(String, &dyn ToSql)> = Vec::new();
+
+        if let Some(expression) = expression.as_ref() {
+            conditions.push(format!(
+                "id IN (SELECT rowid FROM {} WHERE {0} MATCH :keyword)",
+                search::SEARCH_TABLE
+            ));
+            params.push((String::from(":keyword"), expression));
+        }
+
+        if !self.areas.is_empty() {
+            let mut any = Vec::new();
+            for (index, area) in self.areas.iter().enumerate() {
+                let name = format!(":area{}", index);
+                any.push(format!("instr(area, {}) > 0", name));
+                params.push((name, area));
+            }
+            conditions.push(format!("({})", any.join(" OR ")));
+        }
+        push_in("level", &self.levels, &mut conditions, &mut params);
+        push_in("dredging", &self.dredging, &mut conditions, &mut params);
+        if let Some(since) = self.since.as_ref() {
+            conditions.push(String::from("time >= :since"));
+            params.push((String::from(":since"), since));
+        }
+        if let Some(before) = self.before.as_ref() {
+            conditions.push(String::from("time < :before"));
+            params.push((String::from(":before"), before));
+        }
+
+        let mut order: Vec<String> = self
+            .sort
+            .iter()
+            .map(|(column, descending)| {
+                format!("{} {}", column, if *descending { "DESC" } else { "ASC" })
+            })
+            .collect();
+        order.push(String::from("id ASC"));
+        let condition = format!(
+            "WHERE {} ORDER BY {}",
+            conditions.join(" AND "),
+            order.join(", ")
+        );
+        let params: Vec<(&str, &dyn ToSql)> = params
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        conn.find(condition.as_str(), ("id", "ASC"), (0, 0), &params)
+            .map_err(|error| error.to_string())
+    }
+}
+
+/// Adds `column IN (...)` over `values`, unless there are none.
+fn push_in<'a, V: ToSql>(
+    column: &str,
+    values: &'a [V],
+    conditions: &mut Vec<String>,
+    params: &mut Vec<(String, &'a dyn ToSql)>,
+) {
+    if values.is_empty() {
+        return;
+    }
+    let mut names = Vec::new();
+    for (index, value) in values.iter().enumerate() {
+        let name = format!(":{}{}", column, index);
+        names.push(name.clone());
+        params.push((name, value));
+    }
+    conditions.push(format!("{} IN ({})", column, names.join(", ")));
+}
+
+/// Values of `columns` for one river. The forecast is only computed when a
+/// forecast column is written.
+pub(crate) fn values(
+    conn: &DbConn<SecurityModel>,
+    fields: &[Field<SecurityModel>],
+    columns: &[ExportColumn],
+    model: &SecurityModel,
+) -> Vec<FieldValue> {
+    let forecast = if columns
+        .iter()
+        .any(|column| matches!(column, ExportColumn::Forecast(_)))
+    {
+        forecast_texts(conn, model)
+    } else {
+        Default::default()
+    };
+    columns
+        .iter()
+        .map(|column| match *column {
+            ExportColumn::Field(index) => fields[index].value(model),
+            ExportColumn::Forecast(index) if forecast[index].is_empty() => FieldValue::Empty,
+            ExportColumn::Forecast(index) => FieldValue::Text(forecast[index].clone()),
+        })
+        .collect()
+}
+
+/// Forecast year and interval of a river, empty when it cannot be forecast.
+fn forecast_texts(conn: &DbConn<SecurityModel>, model: &SecurityModel) -> [String; 2] {
+    match forecast::forecast_river(&conn.instance, model) {
+        Ok(Some(forecast)) => [forecast.year_text(), forecast.interval_text()],
+        _ => [String::new(), String::new()],
+    }
+}
+
+pub(crate) fn text(value: FieldValue) -> String {
+    match value {
+        FieldValue::Empty => String::new(),
+        FieldValue::Number(number) => number.to_string(),
+        FieldValue::Text(text) => text,
+        FieldValue::DateTime(time) => time.format(DATE_TIME_FORMAT).to_string(),
+    }
+}
+
+/// Object of `model` keyed by column name, with values as exported to JSON.
+pub fn json_record<T: Model + Clone>(fields: &[Field<T>], model: &T) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|field| (String::from(field.column), json(field.value(model))))
+            .collect::<Map<String, Value>>(),
+    )
+}
+
+pub(crate) fn json(value: FieldValue) -> Value {
+    match value {
+        FieldValue::Empty => Value::Null,
+        FieldValue::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+            Value::from(number as i64)
+        }
+        FieldValue::Number(number) => Value::from(number),
+        FieldValue::Text(text) => Value::from(text),
+        FieldValue::DateTime(time) => Value::from(time.format(DATE_TIME_FORMAT).to_string()),
+    }
+}
+
+/// Writes the rivers chosen by `options` as CSV with the same headers and
+/// values as the workbook. Returns the number of rivers written.
+pub fn write_csv<P: AsRef<Path>>(
+    conn: &DbConn<SecurityModel>,
+    path: P,
+    encoding: TextEncoding,
+    options: &ExportOptions,
+) -> Result<usize, String> {
+    let models = options.rivers(conn)?;
+    let fields = SecurityModel::fields();
+    let columns = options.columns();
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let write_error = |error: csv::Error| error.to_string();
+
+    let headers: Vec<String> = columns
+        .iter()
+        .map(|column| column.header(&fields))
+        .collect();
+    writer.write_record(&headers).map_err(write_error)?;
+    for model in models.iter() {
+        let record: Vec<String> = values(conn, &fields, &columns, model)
+            .into_iter()
+            .map(text)
+            .collect();
+        writer.write_record(&record).map_err(write_error)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|error| error.error().to_string())?;
+    let bytes = match encoding {
+        TextEncoding::Utf8 => bytes,
+        TextEncoding::Gbk => GBK.encode(&String::from_utf8_lossy(&bytes)).0.into_owned(),
+    };
+    fs::write(path, bytes).map_err(|error| error.to_string())?;
+    Ok(models.len())
+}
+
+/// Writes the rivers chosen by `options` as a JSON array of objects keyed by
+/// column name, or as one object per line when `lines` is set. Returns the
+/// number of rivers written.
+pub fn write_json<P: AsRef<Path>>(
+    conn: &DbConn<SecurityModel>,
+    path: P,
+    lines: bool,
+    options: &ExportOptions,
+) -> Result<usize, String> {
+    let models = options.rivers(conn)?;
+    let fields = SecurityModel::fields();
+    let columns = options.columns();
+    let keys: Vec<String> = columns.iter().map(|column| column.key(&fields)).collect();
+    let records: Vec<Value> = models
+        .iter()
+        .map(|model| {
+            Value::Object(
+                keys.iter()
+                    .cloned()
+                    .zip(values(conn, &fields, &columns, model).into_iter().map(json))
+                    .collect::<Map<String, Value>>(),
+            )
+        })
+        .collect();
+    let text = if lines {
+        records
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect::<String>()
+    } else {
+        serde_json::to_string_pretty(&records).map_err(|error| error.to_string())?
+    };
+    fs::write(path, text).map_err(|error| error.to_string())?;
+    Ok(models.len())
+}
+
+/// Writes the rivers chosen by `options` in the format named by the
+/// extension of `path`: CSV, JSON, NDJSON, or a workbook otherwise.
+pub fn write_file<P: AsRef<Path>>(
+    conn: &DbConn<SecurityModel>,
+    path: P,
+    encoding: TextEncoding,
+    options: &ExportOptions,
+) -> Result<usize, String> {
+    match import::extension(path.as_ref()).as_str() {
+        "csv" | "txt" => write_csv(conn, path, encoding, options),
+        "json" => write_json(conn, path, false, options),
+        "ndjson" | "jsonl" => write_json(conn, path, true, options),
+        _ => write_workbook(conn, path, options),
+    }
+}
+
+/// Writes the rivers chosen by `options` with typed cells that import reads
+/// back unchanged; by default every field followed by the siltation forecast.
+/// Returns the number of rivers written.
+pub fn write_workbook<P: AsRef<Path>>(
+    conn: &DbConn<SecurityModel>,
+    path: P,
+    options: &ExportOptions,
+) -> Result<usize, String> {
+    let models = options.rivers(conn)?;
+    let path = path
+        .as_ref()
+        .to_str()
+        .ok_or_else(|| String::from("文件路径无效"))?;
+    let mut workbook = Workbook::create(path);
+    let mut sheet = workbook.create_sheet("Sheet1");
+
+    let fields = SecurityModel::fields();
+    let columns = options.columns();
+    for column in columns.iter() {
+        sheet.add_column(Column {
+            width: column.width(&fields),
+        });
+    }
+
+    workbook
+        .write_sheet(&mut sheet, |sheet_writer| {
+            let mut header_row = Row::new();
+            for column in columns.iter() {
+                header_row.add_cell(column.header(&fields));
+            }
+            sheet_writer.append_row(header_row)?;
+            for model in models.iter() {
+                let mut data_row = Row::new();
+                for value in values(conn, &fields, &columns, model) {
+                    data_row.add_cell(cell(value));
+                }
+                sheet_writer.append_row(data_row)?;
+            }
+            Ok(())
+        })
+        .map_err(|error| error.to_string())?;
+    workbook.close().map_err(|error| error.to_string())?;
+    Ok(models.len())
+}
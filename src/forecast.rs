@@ -0,0 +1,301 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike};
+use rusqlite::{Connection, Result};
+
+use crate::{inspection_model::InspectionModel, security_model::SecurityModel};
+
+/// Two-sided 95% quantiles of Student's t distribution for 1 to 30 degrees
+/// of freedom; larger samples use the normal quantile.
+const T_QUANTILES: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Curve {
+    /// `depth = a + b * t`
+    Linear { a: f64, b: f64 },
+    /// `depth = c + beta * exp(-k * t)`, levelling off at `c` as the channel
+    /// approaches equilibrium.
+    Saturating { c: f64, beta: f64, k: f64 },
+}
+
+impl Curve {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Curve::Linear { .. } => "线性",
+            Curve::Saturating { .. } => "饱和",
+        }
+    }
+
+    fn parameters(&self) -> usize {
+        match self {
+            Curve::Linear { .. } => 2,
+            Curve::Saturating { .. } => 3,
+        }
+    }
+
+    pub fn predict(&self, t: f64) -> f64 {
+        match *self {
+            Curve::Linear { a, b } => a + b * t,
+            Curve::Saturating { c, beta, k } => c + beta * (-k * t).exp(),
+        }
+    }
+
+    /// Siltation rate in metres per year at `t`.
+    pub fn rate(&self, t: f64) -> f64 {
+        match *self {
+            Curve::Linear { b, .. } => b,
+            Curve::Saturating { beta, k, .. } => -beta * k * (-k * t).exp(),
+        }
+    }
+
+    /// Time at which the curve rises through `level`, or `None` if it never
+    /// reaches it.
+    pub fn crossing(&self, level: f64) -> Option<f64> {
+        match *self {
+            Curve::Linear { a, b } => {
+                if b > 0.0 {
+                    Some((level - a) / b)
+                } else {
+                    None
+                }
+            }
+            Curve::Saturating { c, beta, k } => {
+                if beta < 0.0 && level < c {
+                    Some(-((level - c) / beta).ln() / k)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Fit {
+    pub curve: Curve,
+    pub sse: f64,
+    pub samples: usize,
+}
+
+impl Fit {
+    /// Residual standard error, undefined until there are more samples than
+    /// curve parameters.
+    pub fn residual_error(&self) -> Option<f64> {
+        let dof = self.samples.checked_sub(self.curve.parameters())?;
+        if dof == 0 {
+            None
+        } else {
+            Some((self.sse / dof as f64).sqrt())
+        }
+    }
+
+    /// Small-sample corrected Akaike information criterion, used to choose
+    /// between the linear and the saturating curve.
+    fn aicc(&self) -> f64 {
+        let n = self.samples as f64;
+        let p = self.curve.parameters() as f64;
+        let sse = self.sse.max(1e-12);
+        let aic = n * (sse / n).ln() + 2.0 * p;
+        if n - p - 1.0 > 0.0 {
+            aic + 2.0 * p * (p + 1.0) / (n - p - 1.0)
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+fn sse(curve: &Curve, points: &[(f64, f64)]) -> f64 {
+    points
+        .iter()
+        .map(|(t, depth)| (depth - curve.predict(*t)).powi(2))
+        .sum()
+}
+
+/// Least-squares line through `(t, depth)` points.
+pub fn fit_linear(points: &[(f64, f64)]) -> Option<Fit> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let mean_depth = points.iter().map(|(_, depth)| depth).sum::<f64>() / n;
+    let stt: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+    if stt == 0.0 {
+        return None;
+    }
+    let std: f64 = points
+        .iter()
+        .map(|(t, depth)| (t - mean_t) * (depth - mean_depth))
+        .sum();
+    let b = std / stt;
+    let curve = Curve::Linear {
+        a: mean_depth - b * mean_t,
+        b,
+    };
+    Some(Fit {
+        curve,
+        sse: sse(&curve, points),
+        samples: points.len(),
+    })
+}
+
+/// Least-squares saturating curve. For a fixed decay rate `k` the curve is
+/// linear in `c` and `beta`, so `k` is searched on a logarithmic grid and
+/// refined by golden-section search.
+pub fn fit_saturating(points: &[(f64, f64)]) -> Option<Fit> {
+    if points.len() < 4 {
+        return None;
+    }
+    let fit_with = |k: f64| -> Option<Fit> {
+        let xs: Vec<f64> = points.iter().map(|(t, _)| (-k * t).exp()).collect();
+        let transformed: Vec<(f64, f64)> = xs
+            .iter()
+            .zip(points.iter())
+            .map(|(x, (_, depth))| (*x, *depth))
+            .collect();
+        match fit_linear(&transformed)?.curve {
+            Curve::Linear { a, b } => {
+                let curve = Curve::Saturating { c: a, beta: b, k };
+                Some(Fit {
+                    curve,
+                    sse: sse(&curve, points),
+                    samples: points.len(),
+                })
+            }
+            Curve::Saturating { .. } => None,
+        }
+    };
+    // Degenerate or overflowing inputs give no usable fit for some rates.
+    let cost = |k: f64| {
+        fit_with(k)
+            .map(|fit| fit.sse)
+            .filter(|sse| sse.is_finite())
+            .unwrap_or(f64::INFINITY)
+    };
+
+    let (low, high, steps) = (1e-3f64.ln(), 5f64.ln(), 200);
+    let step = (high - low) / steps as f64;
+    let best = (0..=steps)
+        .map(|index| low + step * index as f64)
+        .min_by(|a, b| cost(a.exp()).total_cmp(&cost(b.exp())))?;
+
+    let golden = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = (best - step, best + step);
+    for _ in 0..60 {
+        let c = b - golden * (b - a);
+        let d = a + golden * (b - a);
+        if cost(c.exp()) < cost(d.exp()) {
+            b = d;
+        } else {
+            a = c;
+        }
+    }
+    fit_with(((a + b) / 2.0).exp()).filter(|fit| fit.sse.is_finite())
+}
+
+fn t_quantile(dof: usize) -> f64 {
+    match dof {
+        1..=30 => T_QUANTILES[dof - 1],
+        _ => 1.96,
+    }
+}
+
+/// Calendar time as a fractional year, e.g. 2021.5 for early July 2021.
+pub fn decimal_year(time: &DateTime<Local>) -> f64 {
+    let days = if NaiveDate::from_ymd_opt(time.year(), 2, 29).is_some() {
+        366.0
+    } else {
+        365.0
+    };
+    let seconds = time.num_seconds_from_midnight() as f64 / 86400.0;
+    time.year() as f64 + (time.ordinal0() as f64 + seconds) / days
+}
+
+pub struct Forecast {
+    pub fit: Fit,
+    pub threshold: f64,
+    pub latest_depth: f64,
+    /// Siltation rate at the latest inspection, in metres per year.
+    pub rate: f64,
+    /// Fractional year at which the fitted depth reaches the threshold.
+    pub year: Option<f64>,
+    /// 95% interval of `year`, from the years at which the fitted curve
+    /// shifted by the prediction error reaches the threshold. An open upper
+    /// end means the threshold may never be reached.
+    pub interval: Option<(f64, Option<f64>)>,
+}
+
+impl Forecast {
+    pub fn exceeded(&self) -> bool {
+        self.latest_depth > self.threshold
+    }
+
+    pub fn year_text(&self) -> String {
+        if self.exceeded() {
+            String::from("已超过阈值")
+        } else {
+            match self.year {
+                Some(year) => format!("{}", year.floor()),
+                None => String::from("不会超过阈值"),
+            }
+        }
+    }
+
+    pub fn interval_text(&self) -> String {
+        match self.interval {
+            Some((low, Some(high))) => format!("{}~{}", low.floor(), high.floor()),
+            Some((low, None)) => format!("{}~", low.floor()),
+            None => String::new(),
+        }
+    }
+}
+
+/// Fits both curves to the inspections of one reach and predicts when the
+/// siltation depth crosses `threshold`. Needs at least two inspections taken
+/// at different times.
+pub fn forecast(inspections: &[InspectionModel], threshold: f32) -> Option<Forecast> {
+    let mut inspections = inspections.to_vec();
+    inspections.sort_by_key(|inspection| inspection.time);
+    let origin = decimal_year(&inspections.first()?.time);
+    let points: Vec<(f64, f64)> = inspections
+        .iter()
+        .map(|inspection| {
+            (
+                decimal_year(&inspection.time) - origin,
+                inspection.depth as f64,
+            )
+        })
+        .collect();
+    let last_t = points.last()?.0;
+    let threshold = threshold as f64;
+
+    let fit = match (fit_linear(&points), fit_saturating(&points)) {
+        (Some(linear), Some(saturating)) if saturating.aicc() < linear.aicc() => saturating,
+        (Some(linear), _) => linear,
+        (None, _) => return None,
+    };
+
+    let year = fit.curve.crossing(threshold).map(|t| t + origin);
+    let interval = fit.residual_error().and_then(|error| {
+        let margin = t_quantile(fit.samples - fit.curve.parameters()) * error;
+        let low = fit.curve.crossing(threshold - margin)?;
+        let high = fit.curve.crossing(threshold + margin);
+        Some((low + origin, high.map(|high| high + origin)))
+    });
+
+    Some(Forecast {
+        fit,
+        threshold,
+        latest_depth: points.last()?.1,
+        rate: fit.curve.rate(last_t),
+        year,
+        interval,
+    })
+}
+
+/// Forecast for one river from its full inspection history.
+pub fn forecast_river(conn: &Connection, river: &SecurityModel) -> Result<Option<Forecast>> {
+    Ok(forecast(&river.inspections(conn)?, river.threshold))
+}
@@ -0,0 +1,118 @@
+use chrono::Local;
+
+use crate::{
+    db::{Field, Model},
+    dredging::{self, Assessment},
+    security_model::SecurityModel,
+    template::template_fields,
+};
+
+/// Text entry of one river, field by field, for front ends without the
+/// Windows form. The id and the derived columns are not entered, and the
+/// entry time is the time of saving.
+pub struct RiverForm {
+    /// Record being edited, with id 0 for a new river.
+    pub original: SecurityModel,
+    pub fields: Vec<Field<SecurityModel>>,
+    /// Entered text of each field, in the order of `fields`.
+    pub texts: Vec<String>,
+}
+
+impl RiverForm {
+    /// Blank form for a new river, at the first level like the Windows form.
+    pub fn new() -> Self {
+        let fields: Vec<Field<SecurityModel>> = template_fields()
+            .into_iter()
+            .filter(|field| field.column != "time")
+            .collect();
+        let texts = fields
+            .iter()
+            .map(|field| match field.column {
+                "level" => String::from("第一级"),
+                _ => String::new(),
+            })
+            .collect();
+        RiverForm {
+            original: SecurityModel::default(),
+            fields,
+            texts,
+        }
+    }
+
+    /// Form filled in with a stored river.
+    pub fn edit(model: SecurityModel) -> Self {
+        let mut form = RiverForm::new();
+        form.texts = form.fields.iter().map(|field| field.text(&model)).collect();
+        form.original = model;
+        form
+    }
+
+    pub fn text(&self, column: &str) -> &str {
+        self.fields
+            .iter()
+            .position(|field| field.column == column)
+            .map(|index| self.texts[index].as_str())
+            .unwrap_or_default()
+    }
+
+    pub fn set_text(&mut self, column: &str, text: &str) {
+        if let Some(index) = self.fields.iter().position(|field| field.column == column) {
+            self.texts[index] = String::from(text);
+        }
+    }
+
+    /// River as entered, with the derived columns recomputed, and the
+    /// problems found by column. Blank optional fields take their default.
+    pub fn model(&self) -> (SecurityModel, Vec<(&'static str, String)>) {
+        let blank = SecurityModel::default();
+        let mut model = self.original.clone();
+        let mut errors = Vec::new();
+        for (field, text) in self.fields.iter().zip(self.texts.iter()) {
+            let result = match text.trim() {
+                "" if field.required => field.check(""),
+                "" => (field.parser)(&mut model, &(field.formatter)(&blank)),
+                text => (field.parser)(&mut model, text),
+            };
+            if let Err(message) = result {
+                errors.push((field.column, message));
+            }
+        }
+        model.recompute();
+        for (column, message) in SecurityModel::validate(&model) {
+            if errors.iter().all(|(known, _)| *known != column) {
+                errors.push((column, message));
+            }
+        }
+        (model, errors)
+    }
+
+    /// Calculation as the form stands, once the fields it depends on are
+    /// valid; otherwise the first problem among them.
+    pub fn assessment(&self) -> Result<Assessment, String> {
+        let (model, errors) = self.model();
+        match errors
+            .into_iter()
+            .find(|(column, _)| dredging::INPUT_COLUMNS.contains(column))
+        {
+            Some((_, message)) => Err(message),
+            None => Ok(dredging::assess(&model)),
+        }
+    }
+
+    /// River to store: the entered values at the current time, or every
+    /// problem found.
+    pub fn save(&self) -> Result<SecurityModel, Vec<(&'static str, String)>> {
+        let (mut model, errors) = self.model();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        model.time = Local::now();
+        Ok(model)
+    }
+}
+
+impl Default for RiverForm {
+    fn default() -> Self {
+        RiverForm::new()
+    }
+}
@@ -0,0 +1,1056 @@
+use std::{fs, path::Path};
+
+use calamine::{DataType, Range, Reader, Sheets};
+use encoding_rs::GBK;
+use rusqlite::Connection;
+use serde_json::Value;
+use simple_excel_writer::{Column, Row, Workbook};
+
+use crate::{
+    audit,
+    db::{excel_time, DbConn, Field, Model, DATE_TIME_FORMAT},
+    dredging::DERIVED_COLUMNS,
+    export::FORECAST_HEADERS,
+    mapping::MappingProfile,
+    recycle,
+    security_model::{conflict_message, SecurityModel},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    /// The row cannot be imported.
+    Error,
+    /// The row is imported, but part of it was ignored or looks suspicious.
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "错误",
+            Severity::Warning => "警告",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    /// Row number as shown by the spreadsheet, starting at 1.
+    pub row: usize,
+    /// Header of the offending column, empty for whole-row problems.
+    pub column: String,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl Issue {
+    pub fn to_line(&self) -> String {
+        if self.column.is_empty() {
+            format!(
+                "第{}行 {}：{}",
+                self.row,
+                self.severity.name(),
+                self.message
+            )
+        } else {
+            format!(
+                "第{}行【{}】{}：{}",
+                self.row,
+                self.column,
+                self.severity.name(),
+                self.message
+            )
+        }
+    }
+}
+
+/// Cells of a sheet as text, with the row holding the headers.
+pub struct Table {
+    /// Name of the worksheet the table was read from, or the file format
+    /// when it has no sheets.
+    pub sheet: String,
+    pub headers: Vec<String>,
+    /// Spreadsheet row number of the headers, starting at 1, or 0 when the
+    /// headers are keys and records are numbered from 1.
+    pub header_row: usize,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Where the table was found, e.g. the worksheet and its header row.
+    pub fn describe(&self) -> String {
+        if self.header_row == 0 {
+            format!("文件格式：{}", self.sheet)
+        } else {
+            format!("工作表：{}，表头位于第{}行", self.sheet, self.header_row)
+        }
+    }
+}
+
+/// Number of rows searched for the header row, to skip titles and notes
+/// written above the table.
+const HEADER_SCAN_ROWS: usize = 20;
+
+/// How well a worksheet looks like a table of `T`.
+pub struct SheetCandidate {
+    pub name: String,
+    /// Spreadsheet row number of the best header row, starting at 1.
+    pub header_row: usize,
+    /// Number of headers on that row recognised as fields of `T`.
+    pub matched: usize,
+}
+
+/// Text of a cell as the field parsers read it. Date cells become times in
+/// `DATE_TIME_FORMAT`.
+fn cell_text(cell: &DataType) -> String {
+    match cell {
+        DataType::DateTime(serial) => match excel_time(*serial) {
+            Some(time) => time.format(DATE_TIME_FORMAT).to_string(),
+            None => cell.to_string(),
+        },
+        _ => cell.to_string(),
+    }
+}
+
+fn cell_rows(range: &Range<DataType>) -> Vec<Vec<String>> {
+    range
+        .rows()
+        .map(|row| row.iter().map(cell_text).collect())
+        .collect()
+}
+
+enum HeaderMapping {
+    Field(usize),
+    /// The mapping profile assigns the header to no column.
+    Ignored,
+    Unknown,
+}
+
+/// Field of `header`, taken from the profile when it has an entry and from
+/// the field names and aliases otherwise.
+fn map_header<T>(
+    fields: &[Field<T>],
+    header: &str,
+    profile: Option<&MappingProfile>,
+) -> HeaderMapping {
+    if FORECAST_HEADERS.contains(&header.trim()) {
+        return HeaderMapping::Ignored;
+    }
+    let found = match profile.and_then(|profile| profile.column_for(header)) {
+        Some("") => return HeaderMapping::Ignored,
+        Some(column) => fields.iter().position(|field| field.column == column),
+        None => fields.iter().position(|field| field.matches(header)),
+    };
+    match found {
+        Some(index) => HeaderMapping::Field(index),
+        None => HeaderMapping::Unknown,
+    }
+}
+
+/// Index of the row among the first few with the most headers matching a
+/// field of `T`, with the number matched.
+fn find_header<T: Model>(rows: &[Vec<String>], profile: Option<&MappingProfile>) -> (usize, usize) {
+    let fields = T::fields();
+    rows.iter()
+        .take(HEADER_SCAN_ROWS)
+        .map(|row| {
+            row.iter()
+                .filter(|cell| {
+                    matches!(map_header(&fields, cell, profile), HeaderMapping::Field(_))
+                })
+                .count()
+        })
+        .enumerate()
+        .fold((0, 0), |best, (index, matched)| {
+            if matched > best.1 {
+                (index, matched)
+            } else {
+                best
+            }
+        })
+}
+
+fn open(path: &Path) -> Result<Sheets, String> {
+    calamine::open_workbook_auto(path).map_err(|error| format!("无法打开文件：{}", error))
+}
+
+fn read_range(workbook: &mut Sheets, name: &str) -> Result<Range<DataType>, String> {
+    workbook
+        .worksheet_range(name)
+        .ok_or_else(|| format!("找不到工作表{}", name))?
+        .map_err(|error| format!("无法读取工作表{}：{}", name, error))
+}
+
+/// Spreadsheet row number of the first row of `range`.
+fn first_row(range: &Range<DataType>) -> usize {
+    range.start().map(|(row, _)| row as usize).unwrap_or(0) + 1
+}
+
+fn scan<T: Model>(
+    workbook: &mut Sheets,
+    profile: Option<&MappingProfile>,
+) -> Result<Vec<SheetCandidate>, String> {
+    let mut candidates = Vec::new();
+    for name in workbook.sheet_names().to_owned() {
+        let range = read_range(workbook, &name)?;
+        let (index, matched) = find_header::<T>(&cell_rows(&range), profile);
+        candidates.push(SheetCandidate {
+            header_row: first_row(&range) + index,
+            name,
+            matched,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Every worksheet of a .xlsx, .xlsm, .xlsb, .xls or .ods file with its best
+/// header row for `T`.
+pub fn scan_workbook<T: Model, P: AsRef<Path>>(
+    path: P,
+    profile: Option<&MappingProfile>,
+) -> Result<Vec<SheetCandidate>, String> {
+    scan::<T>(&mut open(path.as_ref())?, profile)
+}
+
+/// Reads the table of `T` from worksheet `sheet`, or from the worksheet whose
+/// headers best match the fields of `T` when no sheet is named. The header
+/// row need not be the first row.
+pub fn read_workbook<T: Model, P: AsRef<Path>>(
+    path: P,
+    sheet: Option<&str>,
+    profile: Option<&MappingProfile>,
+) -> Result<Table, String> {
+    let mut workbook = open(path.as_ref())?;
+    let name = match sheet {
+        Some(sheet) => String::from(sheet),
+        None => scan::<T>(&mut workbook, profile)?
+            .into_iter()
+            .filter(|candidate| candidate.matched > 0)
+            .fold(None, |best: Option<SheetCandidate>, candidate| match best {
+                Some(best) if best.matched >= candidate.matched => Some(best),
+                _ => Some(candidate),
+            })
+            .map(|candidate| candidate.name)
+            .ok_or_else(|| String::from("没有找到包含可识别表头的工作表"))?,
+    };
+    let range = read_range(&mut workbook, &name)?;
+    Ok(split_table::<T>(
+        name,
+        first_row(&range),
+        cell_rows(&range),
+        profile,
+    ))
+}
+
+/// Finds the header row among `rows`, numbered from `first_row`, and keeps
+/// the rows below it as data.
+fn split_table<T: Model>(
+    sheet: String,
+    first_row: usize,
+    mut rows: Vec<Vec<String>>,
+    profile: Option<&MappingProfile>,
+) -> Table {
+    let (index, _) = find_header::<T>(&rows, profile);
+    let mut rows = rows.split_off(index);
+    let headers = if rows.is_empty() {
+        Vec::new()
+    } else {
+        rows.remove(0)
+    };
+    Table {
+        sheet,
+        headers,
+        header_row: first_row + index,
+        rows,
+    }
+}
+
+/// Text of a file in UTF-8, with or without a byte order mark, or else in
+/// GBK as saved by Excel on Chinese Windows.
+fn read_text(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|error| format!("无法打开文件：{}", error))?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(String::from(text)),
+        Err(_) => {
+            let (text, _, malformed) = GBK.decode(bytes);
+            if malformed {
+                Err(String::from("无法识别文件编码，请保存为UTF-8或GBK"))
+            } else {
+                Ok(text.into_owned())
+            }
+        }
+    }
+}
+
+/// Reads a comma-separated file in UTF-8 or GBK. The header row need not be
+/// the first line.
+pub fn read_csv<T: Model, P: AsRef<Path>>(
+    path: P,
+    profile: Option<&MappingProfile>,
+) -> Result<Table, String> {
+    let text = read_text(path.as_ref())?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|error| format!("无法读取CSV：{}", error))?;
+        rows.push(record.iter().map(String::from).collect());
+    }
+    Ok(split_table::<T>(String::from("CSV"), 1, rows, profile))
+}
+
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Reads a JSON array of objects, or one object per line (NDJSON). Keys are
+/// headers, so column names and Chinese headers both work; records are
+/// numbered from 1.
+pub fn read_json<P: AsRef<Path>>(path: P) -> Result<Table, String> {
+    parse_json(&read_text(path.as_ref())?)
+}
+
+/// Table of JSON text as read by `read_json`.
+pub fn parse_json(text: &str) -> Result<Table, String> {
+    let records = match serde_json::from_str::<Value>(text) {
+        Ok(Value::Array(records)) => records,
+        Ok(record @ Value::Object(_)) => vec![record],
+        Ok(_) => return Err(String::from("JSON内容应为对象数组")),
+        Err(_) => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .map_err(|error| format!("第{}行不是有效的JSON：{}", index + 1, error))
+            })
+            .collect::<Result<Vec<Value>, String>>()?,
+    };
+
+    let mut headers: Vec<String> = Vec::new();
+    for record in records.iter() {
+        let object = record
+            .as_object()
+            .ok_or_else(|| String::from("JSON记录应为对象"))?;
+        for key in object.keys() {
+            if !headers.contains(key) {
+                headers.push(key.clone());
+            }
+        }
+    }
+    let rows = records
+        .iter()
+        .map(|record| {
+            headers
+                .iter()
+                .map(|header| record.get(header).map(json_text).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    Ok(Table {
+        sheet: String::from("JSON"),
+        headers,
+        header_row: 0,
+        rows,
+    })
+}
+
+/// Reads a CSV, JSON or NDJSON file by its extension, or a workbook
+/// otherwise.
+pub fn read_table<T: Model, P: AsRef<Path>>(
+    path: P,
+    sheet: Option<&str>,
+    profile: Option<&MappingProfile>,
+) -> Result<Table, String> {
+    match extension(path.as_ref()).as_str() {
+        "csv" | "txt" => read_csv::<T, _>(path, profile),
+        "json" | "ndjson" | "jsonl" => read_json(path),
+        _ => read_workbook::<T, _>(path, sheet, profile),
+    }
+}
+
+/// Lower-case extension of `path`, empty when it has none.
+pub fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Reads the table with the profile called `profile`, or, when none is named,
+/// with the stored profile that covers the most headers of the detected
+/// table. Returns the profile used.
+pub fn read_with_profile<T: Model, P: AsRef<Path>>(
+    conn: &Connection,
+    path: P,
+    sheet: Option<&str>,
+    profile: Option<&str>,
+) -> Result<(Table, Option<MappingProfile>), String> {
+    let profile = match profile {
+        Some(name) => Some(
+            MappingProfile::load(conn, name)
+                .map_err(|error| error.to_string())?
+                .ok_or_else(|| format!("找不到映射方案{}", name))?,
+        ),
+        None => {
+            let table = read_table::<T, _>(path.as_ref(), sheet, None)?;
+            match MappingProfile::best_for(conn, &table.headers)
+                .map_err(|error| error.to_string())?
+            {
+                Some(profile) => Some(profile),
+                None => return Ok((table, None)),
+            }
+        }
+    };
+    let table = read_table::<T, _>(path, sheet, profile.as_ref())?;
+    Ok((table, profile))
+}
+
+pub struct ImportRow<T> {
+    /// Spreadsheet row number, starting at 1.
+    pub row: usize,
+    pub cells: Vec<String>,
+    pub model: T,
+    /// Columns given a value by the row. Other fields of `model` are defaults
+    /// and never replace stored values.
+    pub provided: Vec<&'static str>,
+    pub issues: Vec<Issue>,
+}
+
+impl<T> ImportRow<T> {
+    pub fn is_valid(&self) -> bool {
+        self.issues
+            .iter()
+            .all(|issue| issue.severity != Severity::Error)
+    }
+}
+
+/// Result of parsing and validating a table without touching the database.
+pub struct ImportPreview<T> {
+    pub headers: Vec<String>,
+    /// Problems with the table as a whole, such as unknown columns.
+    pub issues: Vec<Issue>,
+    pub rows: Vec<ImportRow<T>>,
+}
+
+impl<T> ImportPreview<T> {
+    pub fn valid_rows(&self) -> impl Iterator<Item = &ImportRow<T>> {
+        self.rows.iter().filter(|row| row.is_valid())
+    }
+
+    pub fn rejected_rows(&self) -> impl Iterator<Item = &ImportRow<T>> {
+        self.rows.iter().filter(|row| !row.is_valid())
+    }
+
+    /// Every issue, table-level first, then in row order.
+    pub fn all_issues(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .chain(self.rows.iter().flat_map(|row| row.issues.iter()))
+    }
+
+    pub fn summary(&self) -> String {
+        let rejected = self.rejected_rows().count();
+        let warnings = self
+            .all_issues()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .count();
+        format!(
+            "共{}行，可导入{}行，错误{}行，警告{}条",
+            self.rows.len(),
+            self.rows.len() - rejected,
+            rejected,
+            warnings
+        )
+    }
+
+    /// Writes the rejected rows with their original cells and an extra column
+    /// explaining why each was rejected.
+    pub fn write_rejects<P: AsRef<Path>>(&self, path: P) -> Result<usize, String> {
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| String::from("文件路径无效"))?;
+        let mut workbook = Workbook::create(path);
+        let mut sheet = workbook.create_sheet("Sheet1");
+        sheet.add_column(Column { width: 10.0 });
+        for _ in self.headers.iter() {
+            sheet.add_column(Column { width: 20.0 });
+        }
+        sheet.add_column(Column { width: 60.0 });
+
+        let mut count = 0usize;
+        workbook
+            .write_sheet(&mut sheet, |sheet_writer| {
+                let mut header_row = Row::new();
+                header_row.add_cell("原行号");
+                for header in self.headers.iter() {
+                    header_row.add_cell(header.as_str());
+                }
+                header_row.add_cell("错误说明");
+                sheet_writer.append_row(header_row)?;
+                for row in self.rejected_rows() {
+                    let mut data_row = Row::new();
+                    data_row.add_cell(row.row as f64);
+                    for index in 0..self.headers.len() {
+                        data_row.add_cell(row.cells.get(index).cloned().unwrap_or_default());
+                    }
+                    data_row.add_cell(
+                        row.issues
+                            .iter()
+                            .map(|issue| issue.to_line())
+                            .collect::<Vec<_>>()
+                            .join("；"),
+                    );
+                    sheet_writer.append_row(data_row)?;
+                    count += 1;
+                }
+                Ok(())
+            })
+            .map_err(|error| error.to_string())?;
+        workbook.close().map_err(|error| error.to_string())?;
+        Ok(count)
+    }
+}
+
+/// Parses every row of `table` into a model and validates it, collecting
+/// every problem instead of stopping at the first. Headers are assigned to
+/// fields by `profile` before falling back to the field names and aliases.
+pub fn preview<T: Model + Default>(
+    table: &Table,
+    profile: Option<&MappingProfile>,
+) -> ImportPreview<T> {
+    let fields = T::fields();
+    let mut issues = Vec::new();
+    let mut columns: Vec<Option<usize>> = Vec::new();
+    for header in table.headers.iter() {
+        let column = match map_header(&fields, header, profile) {
+            HeaderMapping::Field(index) => {
+                if let Some(earlier) = columns.iter().position(|column| *column == Some(index)) {
+                    issues.push(Issue {
+                        row: table.header_row,
+                        column: header.clone(),
+                        message: format!(
+                            "与【{}】列对应同一字段【{}】，已忽略",
+                            table.headers[earlier],
+                            fields[index].header()
+                        ),
+                        severity: Severity::Warning,
+                    });
+                    None
+                } else {
+                    Some(index)
+                }
+            }
+            HeaderMapping::Ignored => None,
+            HeaderMapping::Unknown => {
+                if !header.trim().is_empty() {
+                    issues.push(Issue {
+                        row: table.header_row,
+                        column: header.clone(),
+                        message: String::from("无法识别的列，已忽略，可在映射方案中指定对应字段"),
+                        severity: Severity::Warning,
+                    });
+                }
+                None
+            }
+        };
+        columns.push(column);
+    }
+    for field in fields.iter().filter(|field| field.required) {
+        if !columns
+            .iter()
+            .any(|column| column.map(|index| fields[index].column) == Some(field.column))
+        {
+            issues.push(Issue {
+                row: table.header_row,
+                column: field.header(),
+                message: String::from("缺少必填列"),
+                severity: Severity::Warning,
+            });
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (index, cells) in table.rows.iter().enumerate() {
+        if cells.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let row = table.header_row + index + 1;
+        let mut model = T::default();
+        let mut row_issues = Vec::new();
+        let mut provided = Vec::new();
+        for (cell, column) in cells.iter().zip(columns.iter()) {
+            if let Some(index) = column {
+                let field = &fields[*index];
+                if cell.trim().is_empty() {
+                    continue;
+                }
+                match (field.parser)(&mut model, cell) {
+                    Ok(()) => provided.push(field.column),
+                    Err(message) => row_issues.push(Issue {
+                        row,
+                        column: field.header(),
+                        message,
+                        // A filled cell that cannot be read would otherwise
+                        // be stored as 0 or, for a time, the time of the
+                        // import; blank cells are left out above.
+                        severity: Severity::Error,
+                    }),
+                }
+            }
+        }
+        for (column, message) in T::validate(&model) {
+            // Columns absent from the table are reported once above and
+            // checked again against the stored record in `resolve`.
+            if !column.is_empty()
+                && !columns
+                    .iter()
+                    .any(|index| index.map(|index| fields[index].column) == Some(column))
+            {
+                continue;
+            }
+            let header = fields
+                .iter()
+                .find(|field| field.column == column)
+                .map(|field| field.header())
+                .unwrap_or_default();
+            if row_issues
+                .iter()
+                .all(|issue: &Issue| issue.column != header || header.is_empty())
+            {
+                row_issues.push(Issue {
+                    row,
+                    column: header,
+                    message,
+                    severity: Severity::Error,
+                });
+            }
+        }
+        rows.push(ImportRow {
+            row,
+            cells: cells.clone(),
+            model,
+            provided,
+            issues: row_issues,
+        });
+    }
+
+    ImportPreview {
+        headers: table.headers.clone(),
+        issues,
+        rows,
+    }
+}
+
+/// Flags rows describing the same river as an earlier row of the file, i.e.
+/// with the same name, jurisdiction and ends. `commit` stores the first and
+/// resolves the later ones against it under the conflict policy.
+pub fn check_duplicates(preview: &mut ImportPreview<SecurityModel>) {
+    for index in 0..preview.rows.len() {
+        let (earlier, rest) = preview.rows.split_at_mut(index);
+        let current = &mut rest[0];
+        if let Some(first) = earlier
+            .iter()
+            .find(|row| row.model.is_same_river(&current.model))
+        {
+            current.issues.push(Issue {
+                row: current.row,
+                column: String::new(),
+                message: format!(
+                    "与第{}行河道名称、辖区、起点和终点都相同，为同一河道，将按冲突策略处理",
+                    first.row
+                ),
+                severity: Severity::Warning,
+            });
+        }
+    }
+}
+
+/// What to do with an imported row describing a river already stored.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    /// Keep the stored record untouched.
+    Skip,
+    /// Replace stored values with every value given in the row.
+    Overwrite,
+    /// Overwrite only when the row's 录入时间 is later than the stored one.
+    NewerWins,
+    /// Only fill stored fields that are blank.
+    FillEmpty,
+}
+
+impl ConflictPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "跳过已有记录",
+            ConflictPolicy::Overwrite => "覆盖已有记录",
+            ConflictPolicy::NewerWins => "录入时间较新者为准",
+            ConflictPolicy::FillEmpty => "仅补充空白字段",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "skip" | "跳过" => Some(ConflictPolicy::Skip),
+            "overwrite" | "覆盖" => Some(ConflictPolicy::Overwrite),
+            "newer" | "较新" => Some(ConflictPolicy::NewerWins),
+            "fill" | "补充" => Some(ConflictPolicy::FillEmpty),
+            _ => None,
+        }
+    }
+}
+
+pub struct FieldChange {
+    pub header: String,
+    pub old: String,
+    pub new: String,
+}
+
+pub enum Action {
+    Insert,
+    Update,
+    /// The river is taken out of the recycle bin, with the row's values
+    /// merged in.
+    Restore,
+    /// The stored record is kept, for the given reason.
+    Skip(String),
+    /// The record would be invalid, e.g. a new river missing columns the
+    /// table lacks.
+    Reject(String),
+}
+
+/// How one valid row will be stored, with the fields it changes.
+pub struct Resolution {
+    pub row: usize,
+    pub action: Action,
+    /// Record to store: the row itself for an insert, the stored record with
+    /// the row's values merged in for an update or a restore.
+    pub model: SecurityModel,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Copies `columns` of `incoming` onto `existing`, or only those blank in
+/// `existing` when `blank_only` is set.
+pub(crate) fn merge<T: Model + Clone>(
+    existing: &T,
+    incoming: &T,
+    columns: &[&str],
+    blank_only: bool,
+) -> T {
+    let mut merged = existing.clone();
+    for field in T::fields() {
+        if field.column == "id" || !columns.contains(&field.column) {
+            continue;
+        }
+        if blank_only && !field.is_blank(existing) {
+            continue;
+        }
+        // The formatter writes what the parser reads, so this cannot fail.
+        let _ = (field.parser)(&mut merged, &(field.formatter)(incoming));
+    }
+    merged
+}
+
+/// Fields whose stored text differs between `old` and `new`.
+pub fn diff<T: Model>(old: &T, new: &T) -> Vec<FieldChange> {
+    T::fields()
+        .iter()
+        .filter(|field| field.column != "id")
+        .filter_map(|field| {
+            let (before, after) = ((field.formatter)(old), (field.formatter)(new));
+            if before == after {
+                None
+            } else {
+                Some(FieldChange {
+                    header: field.header(),
+                    old: before,
+                    new: after,
+                })
+            }
+        })
+        .collect()
+}
+
+/// `river`, found by the id of `row`, if the row gives it no other name,
+/// jurisdiction or ends; a reused or stale id must not rename another river.
+fn check_id(
+    row: &ImportRow<SecurityModel>,
+    river: Option<SecurityModel>,
+) -> Result<Option<SecurityModel>, String> {
+    match river {
+        Some(river) if !merge(&river, &row.model, &row.provided, false).is_same_river(&river) => {
+            Err(format!(
+                "编号{}的河道为{}，与该行的名称、辖区、起点或终点不同，不按编号更新",
+                river.id, river.name
+            ))
+        }
+        river => Ok(river),
+    }
+}
+
+/// Stored river the row describes: the one with the same name,
+/// jurisdiction and ends, or else the one with the row's id, see `check_id`.
+fn find_existing(
+    conn: &DbConn<SecurityModel>,
+    row: &ImportRow<SecurityModel>,
+) -> Result<Option<SecurityModel>, String> {
+    if let Some(id) = row.model.find_same(&conn.instance, 0).ok().flatten() {
+        return Ok(conn.find_by_id(id).ok());
+    }
+    if row.model.id == 0 {
+        return Ok(None);
+    }
+    check_id(row, conn.find_by_id(row.model.id).ok())
+}
+
+/// Deleted river the row matches when no stored one does, by name,
+/// jurisdiction and ends or else by id, see `check_id`.
+fn find_deleted(
+    conn: &DbConn<SecurityModel>,
+    row: &ImportRow<SecurityModel>,
+) -> Result<Option<SecurityModel>, String> {
+    if let Some(river) = recycle::find_same(&conn.instance, &row.model)
+        .ok()
+        .flatten()
+    {
+        return Ok(Some(river.model));
+    }
+    if row.model.id == 0 {
+        return Ok(None);
+    }
+    let river = recycle::find(&conn.instance, row.model.id).ok().flatten();
+    check_id(row, river.map(|river| river.model))
+}
+
+/// Decides how `row` is stored under `policy`, against the database as it is
+/// now. A row matching only a river in the recycle bin restores it unless
+/// the policy keeps the stored record.
+pub fn resolve(
+    conn: &DbConn<SecurityModel>,
+    row: &ImportRow<SecurityModel>,
+    policy: ConflictPolicy,
+) -> Resolution {
+    let found = find_existing(conn, row).and_then(|existing| match existing {
+        Some(existing) => Ok(Some((existing, false))),
+        None => find_deleted(conn, row).map(|deleted| deleted.map(|river| (river, true))),
+    });
+    let (existing, deleted) = match found {
+        Ok(Some(found)) => found,
+        Err(message) => {
+            return Resolution {
+                row: row.row,
+                action: Action::Reject(message),
+                model: row.model.clone(),
+                changes: Vec::new(),
+            }
+        }
+        Ok(None) => {
+            let mut model = row.model.clone();
+            model.id = 0;
+            return Resolution {
+                row: row.row,
+                action: checked(&model, Action::Insert),
+                model,
+                changes: Vec::new(),
+            };
+        }
+    };
+    let skip = |reason: String| Resolution {
+        row: row.row,
+        action: Action::Skip(if deleted {
+            format!("{}，河道仍在回收站中", reason)
+        } else {
+            reason
+        }),
+        model: existing.clone(),
+        changes: Vec::new(),
+    };
+    let has_time = row.provided.contains(&"time");
+    let mut merged = match policy {
+        ConflictPolicy::Skip => return skip(String::from("记录已存在")),
+        ConflictPolicy::NewerWins if !has_time => {
+            return skip(String::from("缺少录入时间，无法比较新旧"))
+        }
+        ConflictPolicy::NewerWins if row.model.time <= existing.time => {
+            return skip(format!(
+                "已有记录较新（{}）",
+                existing.time.format(DATE_TIME_FORMAT)
+            ))
+        }
+        ConflictPolicy::FillEmpty => merge(&existing, &row.model, &row.provided, true),
+        ConflictPolicy::Overwrite | ConflictPolicy::NewerWins => {
+            merge(&existing, &row.model, &row.provided, false)
+        }
+    };
+    merged.recompute();
+    let changes = diff(&existing, &merged);
+    if changes.is_empty() && !deleted {
+        return skip(String::from("数据相同"));
+    }
+    // Without a time in the row, new values count as measured at import.
+    if !has_time && policy != ConflictPolicy::FillEmpty {
+        merged.time = row.model.time;
+    }
+    let action = match merged.find_same(&conn.instance, existing.id) {
+        Ok(Some(other)) => Action::Reject(conflict_message(other)),
+        _ if deleted => checked(&merged, Action::Restore),
+        _ => checked(&merged, Action::Update),
+    };
+    Resolution {
+        row: row.row,
+        action,
+        model: merged,
+        changes,
+    }
+}
+
+/// `action`, or a rejection listing what makes `model` invalid.
+fn checked(model: &SecurityModel, action: Action) -> Action {
+    let errors = SecurityModel::validate(model);
+    if errors.is_empty() {
+        return action;
+    }
+    let fields = SecurityModel::fields();
+    Action::Reject(
+        errors
+            .into_iter()
+            .map(
+                |(column, message)| match fields.iter().find(|field| field.column == column) {
+                    Some(field) => format!("【{}】{}", field.header(), message),
+                    None => message,
+                },
+            )
+            .collect::<Vec<_>>()
+            .join("；"),
+    )
+}
+
+/// Replaces the derived columns of every row with values computed from its
+/// inputs. With `flag` set, rows whose sheet gave different values get a
+/// warning.
+pub fn recompute_derived(preview: &mut ImportPreview<SecurityModel>, flag: bool) {
+    let fields = SecurityModel::fields();
+    for row in preview.rows.iter_mut() {
+        let given = row.model.clone();
+        row.model.recompute();
+        if !flag {
+            continue;
+        }
+        let mut issues = Vec::new();
+        for field in fields.iter().filter(|field| {
+            DERIVED_COLUMNS.contains(&field.column) && row.provided.contains(&field.column)
+        }) {
+            let (sheet, computed) = ((field.formatter)(&given), (field.formatter)(&row.model));
+            let same = match (sheet.parse::<f64>(), computed.parse::<f64>()) {
+                (Ok(sheet), Ok(computed)) => (sheet - computed).abs() < 0.005,
+                _ => sheet.trim() == computed.trim(),
+            };
+            if !same {
+                issues.push(Issue {
+                    row: row.row,
+                    column: field.header(),
+                    message: format!(
+                        "表中为{}，按输入数据计算应为{}，已改用计算值",
+                        sheet, computed
+                    ),
+                    severity: Severity::Warning,
+                });
+            }
+        }
+        row.issues.append(&mut issues);
+    }
+}
+
+#[derive(Default)]
+pub struct ImportSummary {
+    pub inserted: u32,
+    pub updated: u32,
+    /// Rivers taken out of the recycle bin.
+    pub restored: u32,
+    pub skipped: u32,
+    pub failed: u32,
+}
+
+impl ImportSummary {
+    pub fn to_message(&self) -> String {
+        format!(
+            "导入完成，新增{}条，更新{}条，恢复{}条，跳过{}条，失败{}条",
+            self.inserted, self.updated, self.restored, self.skipped, self.failed
+        )
+    }
+}
+
+/// Source of the changes logged by `commit`, followed by the file imported
+/// when a front end knows it.
+pub const IMPORT_SOURCE: &str = "导入";
+
+/// Stores rows under `policy`, resolving each against the database as left
+/// by the rows before it, so repeated rows update the river the first one
+/// inserted. New rivers are upserted, so a river stored meanwhile under the
+/// same name, jurisdiction and ends is updated rather than duplicated. The
+/// changes are logged as made by an import unless the source set for `conn`
+/// already names one, e.g. "导入 河道.xlsx".
+pub fn commit<'a, I>(
+    conn: &mut DbConn<SecurityModel>,
+    rows: I,
+    policy: ConflictPolicy,
+) -> ImportSummary
+where
+    I: IntoIterator<Item = &'a ImportRow<SecurityModel>>,
+{
+    let context = audit::context(&conn.instance).ok();
+    if let Some((user, source)) = context.as_ref() {
+        if !source.starts_with(IMPORT_SOURCE) {
+            let _ = audit::set_context(&conn.instance, user, IMPORT_SOURCE);
+        }
+    }
+    let mut summary = ImportSummary::default();
+    for row in rows {
+        let resolution = resolve(conn, row, policy);
+        let result = match resolution.action {
+            Action::Skip(_) => {
+                summary.skipped += 1;
+                continue;
+            }
+            Action::Reject(_) => {
+                summary.failed += 1;
+                continue;
+            }
+            Action::Insert => {
+                conn.set(resolution.model);
+                conn.upsert()
+            }
+            Action::Update => {
+                conn.set(resolution.model);
+                conn.update()
+            }
+            Action::Restore => match recycle::restore(conn, resolution.model.id) {
+                Ok(_) if resolution.changes.is_empty() => Ok(1),
+                Ok(_) => {
+                    conn.set(resolution.model);
+                    conn.update()
+                }
+                Err(_) => Ok(0),
+            },
+        };
+        match (result, &resolution.action) {
+            (Ok(1), Action::Insert) => summary.inserted += 1,
+            (Ok(1), Action::Restore) => summary.restored += 1,
+            (Ok(1), _) => summary.updated += 1,
+            _ => summary.failed += 1,
+        }
+    }
+    *conn.model = None;
+    if let Some((user, source)) = context {
+        let _ = audit::set_context(&conn.instance, &user, &source);
+    }
+    summary
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Local};
+use rusqlite::{named_params, Result, Row, Statement};
+
+use crate::db::{parse_date_time, Bound, DbOpt, Field, FieldType, Model, DATE_TIME_FORMAT};
+
+/// One siltation measurement of a river reach. A river keeps every
+/// inspection so the development of `depth` can be followed over the years.
+#[derive(Clone)]
+pub struct InspectionModel {
+    pub id: u32,
+    pub river_id: u32,
+    pub time: DateTime<Local>,
+    pub depth: f32,
+    pub threshold: f32,
+    pub dredging: String,
+}
+
+impl Default for InspectionModel {
+    fn default() -> Self {
+        Self {
+            id: Default::default(),
+            river_id: Default::default(),
+            time: Local::now(),
+            depth: Default::default(),
+            threshold: Default::default(),
+            dredging: Default::default(),
+        }
+    }
+}
+
+impl Model for InspectionModel {
+    fn get_sql(opt: DbOpt) -> String {
+        match opt {
+            DbOpt::Create => r#"CREATE TABLE inspections
+                (
+                    id        INTEGER PRIMARY KEY AUTOINCREMENT,
+                    river_id  INTEGER NOT NULL REFERENCES rivers (id),
+                    time      TEXT,
+                    depth     REAL,
+                    threshold REAL,
+                    dredging  TEXT
+                )"#
+            .to_string(),
+            DbOpt::Insert => r#"INSERT INTO inspections(river_id, time, depth, threshold, dredging)
+                VALUES(:river_id, :time, :depth, :threshold, :dredging)"#
+                .to_string(),
+            DbOpt::Update => r#"UPDATE inspections SET
+                    river_id=:river_id, time=:time, depth=:depth, threshold=:threshold,
+                    dredging=:dredging
+                WHERE id=:id"#
+                .to_string(),
+            DbOpt::Upsert => {
+                r#"INSERT INTO inspections(id, river_id, time, depth, threshold, dredging)
+                VALUES(NULLIF(:id, 0), :river_id, :time, :depth, :threshold, :dredging)
+                ON CONFLICT(id) DO UPDATE SET
+                    river_id=excluded.river_id, time=excluded.time, depth=excluded.depth,
+                    threshold=excluded.threshold, dredging=excluded.dredging"#
+                    .to_string()
+            }
+            DbOpt::Delete => r#"DELETE FROM inspections WHERE id=?"#.to_string(),
+            DbOpt::Select => r#"SELECT
+                id, river_id, time, depth, threshold, dredging
+                FROM inspections"#
+                .to_string(),
+        }
+    }
+
+    fn fields() -> Vec<Field<Self>> {
+        vec![
+            Field {
+                column: "id",
+                name: "编号",
+                aliases: &[],
+                field_type: FieldType::Integer,
+                unit: None,
+                required: false,
+                min: None,
+                max: None,
+                width: 10.0,
+                formatter: |model| model.id.to_string(),
+                display: |model| model.id.to_string(),
+                parser: |model, text| {
+                    model.id = text
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("无效的编号：{}", text))?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "river_id",
+                name: "河道编号",
+                aliases: &[],
+                field_type: FieldType::Integer,
+                unit: None,
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 10.0,
+                formatter: |model| model.river_id.to_string(),
+                display: |model| model.river_id.to_string(),
+                parser: |model, text| {
+                    model.river_id = text
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("无效的河道编号：{}", text))?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "time",
+                name: "录入时间",
+                aliases: &["巡查时间"],
+                field_type: FieldType::DateTime,
+                unit: None,
+                required: true,
+                min: None,
+                max: None,
+                width: 25.0,
+                formatter: |model| format!("{}", model.time.format(DATE_TIME_FORMAT)),
+                display: |model| format!("{}", model.time.format(DATE_TIME_FORMAT)),
+                parser: |model, text| {
+                    model.time = parse_date_time(text)
+                        .map_err(|_| format!("无法识别的录入时间：{}", text))?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "depth",
+                name: "淤积深度",
+                aliases: &[],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 15.0,
+                formatter: |model| model.depth.to_string(),
+                display: |model| model.depth.to_string(),
+                parser: |model, text| {
+                    model.depth = text
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("{}不是有效的数字", text))?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "threshold",
+                name: "淤积阈值",
+                aliases: &[],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: false,
+                min: None,
+                max: None,
+                width: 15.0,
+                formatter: |model| model.threshold.to_string(),
+                display: |model| model.threshold.to_string(),
+                parser: |model, text| {
+                    model.threshold = text
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("{}不是有效的数字", text))?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "dredging",
+                name: "清淤判断",
+                aliases: &[],
+                field_type: FieldType::Text,
+                unit: None,
+                required: false,
+                min: None,
+                max: None,
+                width: 30.0,
+                formatter: |model| model.dredging.clone(),
+                display: |model| model.dredging.clone(),
+                parser: |model, text| {
+                    model.dredging = String::from(text.trim());
+                    Ok(())
+                },
+            },
+        ]
+    }
+
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            river_id: row.get("river_id")?,
+            time: row.get("time")?,
+            depth: row.get("depth")?,
+            threshold: row.get("threshold")?,
+            dredging: row.get("dredging")?,
+        })
+    }
+
+    fn execute(&self, stmt: &mut Statement, opt: DbOpt) -> Result<usize> {
+        match opt {
+            DbOpt::Create => unimplemented!(),
+            DbOpt::Insert => stmt.execute(named_params! {
+                ":river_id": self.river_id,
+                ":time": self.time,
+                ":depth": self.depth,
+                ":threshold": self.threshold,
+                ":dredging": self.dredging,
+            }),
+            DbOpt::Update | DbOpt::Upsert => stmt.execute(named_params! {
+                ":river_id": self.river_id,
+                ":time": self.time,
+                ":depth": self.depth,
+                ":threshold": self.threshold,
+                ":dredging": self.dredging,
+                ":id": self.id,
+            }),
+            DbOpt::Delete => stmt.execute([self.id]),
+            DbOpt::Select => unimplemented!(),
+        }
+    }
+}
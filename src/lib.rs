@@ -0,0 +1,22 @@
+pub mod api;
+pub mod assessment;
+pub mod audit;
+pub mod chainage;
+pub mod db;
+pub mod dredging;
+pub mod duplicate;
+pub mod estimate;
+pub mod export;
+pub mod forecast;
+pub mod form;
+pub mod import;
+pub mod inspection_model;
+pub mod mapping;
+mod migration;
+pub mod recycle;
+pub mod report;
+pub mod search;
+pub mod section;
+pub mod security_model;
+pub mod statistics;
+pub mod template;
@@ -1,14 +1,17 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(windows, windows_subsystem = "windows")]
 
+#[cfg(windows)]
 use std::{cell::RefCell, thread};
 
+#[cfg(windows)]
 use nwd::NwgUi;
+#[cfg(windows)]
 use nwg::NativeUi;
 
-pub mod db;
+#[cfg(windows)]
 mod security_app;
-pub mod security_model;
 
+#[cfg(windows)]
 #[derive(Default, NwgUi)]
 pub struct BasicApp {
     security_handle: RefCell<Option<thread::JoinHandle<()>>>,
@@ -30,6 +33,7 @@ pub struct BasicApp {
     quit_button: nwg::Button,
 }
 
+#[cfg(windows)]
 impl BasicApp {
     fn window_close(&self) {
         nwg::stop_thread_dispatch();
@@ -72,6 +76,7 @@ impl BasicApp {
 /// cargo rustc -- -Clink-args="/SUBSYSTEM:WINDOWS /ENTRY:mainCRTStartup"
 /// ```
 /// set entry point named WinMain
+#[cfg(windows)]
 fn main() {
     nwg::init().expect("Failed to init Native Windows GUI");
 
@@ -92,3 +97,9 @@ fn main() {
 
     nwg::dispatch_thread_events();
 }
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("图形界面仅支持Windows，请使用wrs-cli");
+    std::process::exit(1);
+}
@@ -0,0 +1,119 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::db::{normalize_header, Model};
+
+/// Header-to-column assignments saved under a name for spreadsheets that
+/// keep arriving from the same source, kept in the `header_mappings` table.
+#[derive(Clone, Default)]
+pub struct MappingProfile {
+    pub name: String,
+    /// Spreadsheet header and model column. An empty column ignores the
+    /// header without reporting it.
+    pub entries: Vec<(String, String)>,
+}
+
+impl MappingProfile {
+    /// Column assigned to `header`, compared the way field headers are.
+    pub fn column_for(&self, header: &str) -> Option<&str> {
+        let header = normalize_header(header);
+        self.entries
+            .iter()
+            .find(|(entry, _)| normalize_header(entry) == header)
+            .map(|(_, column)| column.as_str())
+    }
+
+    /// Sets the column of `header`, which may be given as a column or a field
+    /// header of `T`, or left empty to ignore the header.
+    pub fn assign<T: Model>(
+        &mut self,
+        header: &str,
+        column: &str,
+    ) -> std::result::Result<(), String> {
+        let column = if column.trim().is_empty() {
+            String::new()
+        } else {
+            let field = T::fields()
+                .into_iter()
+                .find(|field| field.column == column.trim() || field.matches(column))
+                .ok_or_else(|| format!("未知的字段：{}", column))?;
+            String::from(field.column)
+        };
+        let header = String::from(header.trim());
+        match self.entries.iter_mut().find(|(entry, _)| *entry == header) {
+            Some(entry) => entry.1 = column,
+            None => self.entries.push((header, column)),
+        }
+        Ok(())
+    }
+
+    pub fn names(conn: &Connection) -> Result<Vec<String>> {
+        let mut stmt =
+            conn.prepare("SELECT DISTINCT profile FROM header_mappings ORDER BY profile")?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        names.collect()
+    }
+
+    pub fn load(conn: &Connection, name: &str) -> Result<Option<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT header, column_name FROM header_mappings WHERE profile=? ORDER BY rowid",
+        )?;
+        let entries = stmt
+            .query_map([name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(String, String)>>>()?;
+        if entries.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Self {
+                name: String::from(name),
+                entries,
+            }))
+        }
+    }
+
+    pub fn load_all(conn: &Connection) -> Result<Vec<Self>> {
+        let mut profiles = Vec::new();
+        for name in Self::names(conn)? {
+            if let Some(profile) = Self::load(conn, &name)? {
+                profiles.push(profile);
+            }
+        }
+        Ok(profiles)
+    }
+
+    /// Replaces the stored profile of the same name.
+    pub fn save(&self, conn: &Connection) -> Result<()> {
+        let tx = conn.unchecked_transaction()?;
+        Self::delete(conn, &self.name)?;
+        for (header, column) in self.entries.iter() {
+            conn.execute(
+                "INSERT INTO header_mappings(profile, header, column_name) VALUES(?1, ?2, ?3)",
+                params![self.name, header, column],
+            )?;
+        }
+        tx.commit()
+    }
+
+    pub fn delete(conn: &Connection, name: &str) -> Result<usize> {
+        conn.execute("DELETE FROM header_mappings WHERE profile=?", [name])
+    }
+
+    /// Number of `headers` the profile has an entry for.
+    pub fn coverage(&self, headers: &[String]) -> usize {
+        headers
+            .iter()
+            .filter(|header| self.column_for(header).is_some())
+            .count()
+    }
+
+    /// The stored profile covering the most of `headers`, if any covers one.
+    pub fn best_for(conn: &Connection, headers: &[String]) -> Result<Option<Self>> {
+        let mut best: Option<(usize, Self)> = None;
+        for profile in Self::load_all(conn)? {
+            let coverage = profile.coverage(headers);
+            if coverage > best.as_ref().map(|(coverage, _)| *coverage).unwrap_or(0) {
+                best = Some((coverage, profile));
+            }
+        }
+        Ok(best.map(|(_, profile)| profile))
+    }
+}
@@ -0,0 +1,203 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::{audit, chainage::Chainage, duplicate, recycle, search};
+
+/// Schema changes in the order they were introduced. The index of the last
+/// applied entry plus one is kept in `PRAGMA user_version`.
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    split_rivers_and_inspections,
+    create_unit_prices,
+    add_river_chainage,
+    create_header_mappings,
+    create_river_search,
+    create_audit_log,
+    add_river_deleted,
+    add_river_identity,
+];
+
+pub fn migrate(conn: &Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.unchecked_transaction()?;
+        migration(conn)?;
+        conn.execute_batch(format!("PRAGMA user_version = {}", index + 1).as_str())?;
+        tx.commit()?;
+    }
+    // Left out by `add_river_identity` until rivers stored twice are merged
+    // or deleted, possibly by another program.
+    duplicate::create_index(conn)?;
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?1",
+        [name],
+        |row| row.get::<_, u32>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Moves the single-row-per-river `water_security` table into `rivers` and
+/// `inspections`, keeping the old table as `water_security_backup` and
+/// replacing it with a view of each river's latest inspection.
+fn split_rivers_and_inspections(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"CREATE TABLE rivers
+        (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            level         INTEGER,
+            name          TEXT,
+            area          TEXT,
+            start         TEXT,
+            end           TEXT,
+            river_width   REAL,
+            elevation     REAL,
+            ratio         REAL,
+            line          REAL,
+            allow         REAL,
+            safe          REAL,
+            channel_width REAL
+        );
+        CREATE TABLE inspections
+        (
+            id        INTEGER PRIMARY KEY AUTOINCREMENT,
+            river_id  INTEGER NOT NULL REFERENCES rivers (id),
+            time      TEXT,
+            depth     REAL,
+            threshold REAL,
+            dredging  TEXT
+        );
+        CREATE INDEX inspections_river_time ON inspections (river_id, time);"#,
+    )?;
+
+    if table_exists(conn, "water_security")? {
+        conn.execute_batch(
+            r#"INSERT INTO rivers(
+                id, level, name, area, start, end, river_width, elevation, ratio,
+                line, allow, safe, channel_width
+            )
+            SELECT
+                id, level, name, area, start, end, river_width, elevation, ratio,
+                line, allow, safe, channel_width
+            FROM water_security;
+            INSERT INTO inspections(river_id, time, depth, threshold, dredging)
+            SELECT id, time, depth, threshold, dredging FROM water_security;
+            ALTER TABLE water_security RENAME TO water_security_backup;"#,
+        )?;
+    }
+
+    conn.execute_batch(
+        r#"CREATE VIEW water_security(
+            id, level, name, area, start, end, river_width, elevation, ratio, line, allow,
+            safe, depth, channel_width, threshold, dredging, time
+        ) AS
+        SELECT
+            rivers.id, rivers.level, rivers.name, rivers.area, rivers.start, rivers.end,
+            rivers.river_width, rivers.elevation, rivers.ratio, rivers.line, rivers.allow,
+            rivers.safe, inspections.depth, rivers.channel_width, inspections.threshold,
+            inspections.dredging, inspections.time
+        FROM rivers
+        JOIN inspections ON inspections.id = (
+            SELECT id FROM inspections
+            WHERE river_id = rivers.id
+            ORDER BY time DESC, id DESC
+            LIMIT 1
+        )"#,
+    )
+}
+
+fn create_unit_prices(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"CREATE TABLE unit_prices
+        (
+            item  TEXT PRIMARY KEY,
+            price REAL
+        )"#,
+    )
+}
+
+/// Stores the stake numbers of `start` and `end` in metres so reach lengths
+/// can be used in SQL. Blank ends are left without one.
+fn add_river_chainage(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"ALTER TABLE rivers ADD COLUMN start_chainage REAL;
+        ALTER TABLE rivers ADD COLUMN end_chainage REAL;"#,
+    )?;
+    let mut stmt = conn.prepare("SELECT id, start, end FROM rivers")?;
+    let rivers = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    for (id, start, end) in rivers {
+        conn.execute(
+            "UPDATE rivers SET start_chainage=?1, end_chainage=?2 WHERE id=?3",
+            params![
+                start.as_deref().and_then(Chainage::metres_of),
+                end.as_deref().and_then(Chainage::metres_of),
+                id
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn create_header_mappings(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"CREATE TABLE header_mappings
+        (
+            profile     TEXT NOT NULL,
+            header      TEXT NOT NULL,
+            column_name TEXT NOT NULL,
+            PRIMARY KEY (profile, header)
+        )"#,
+    )
+}
+
+/// Indexes the text of every river for keyword and pinyin search.
+fn create_river_search(conn: &Connection) -> Result<()> {
+    search::create(conn)
+}
+
+/// Logs every later change to a river, see `audit`.
+fn create_audit_log(conn: &Connection) -> Result<()> {
+    audit::create(conn)
+}
+
+/// Marks deleted rivers with the time of deletion instead of removing them,
+/// hiding them from `water_security` and listing them in the recycle bin.
+fn add_river_deleted(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"ALTER TABLE rivers ADD COLUMN deleted TEXT;
+        DROP VIEW water_security;
+        CREATE VIEW water_security(
+            id, level, name, area, start, end, river_width, elevation, ratio, line, allow,
+            safe, depth, channel_width, threshold, dredging, time
+        ) AS
+        SELECT
+            rivers.id, rivers.level, rivers.name, rivers.area, rivers.start, rivers.end,
+            rivers.river_width, rivers.elevation, rivers.ratio, rivers.line, rivers.allow,
+            rivers.safe, inspections.depth, rivers.channel_width, inspections.threshold,
+            inspections.dredging, inspections.time
+        FROM rivers
+        JOIN inspections ON inspections.id = (
+            SELECT id FROM inspections
+            WHERE river_id = rivers.id
+            ORDER BY time DESC, id DESC
+            LIMIT 1
+        )
+        WHERE rivers.deleted IS NULL;"#,
+    )?;
+    recycle::create(conn)
+}
+
+/// Makes the name, jurisdiction and ends of a river unique outside the
+/// recycle bin, once no river is stored twice; see `duplicate::create_index`.
+fn add_river_identity(conn: &Connection) -> Result<()> {
+    duplicate::create_index(conn).map(|_| ())
+}
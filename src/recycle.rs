@@ -0,0 +1,159 @@
+use chrono::{DateTime, Local};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
+
+use crate::{
+    audit::{self, AuditAction},
+    db::{DbConn, Model},
+    search,
+    security_model::{self, SecurityModel},
+};
+
+/// View of the deleted rivers, with the columns of `water_security` and the
+/// time each was deleted.
+pub const RECYCLE_VIEW: &str = "recycle_bin";
+
+/// A river in the recycle bin.
+#[derive(Clone)]
+pub struct DeletedRiver {
+    pub model: SecurityModel,
+    pub deleted: DateTime<Local>,
+}
+
+impl DeletedRiver {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(DeletedRiver {
+            model: SecurityModel::from_row(row)?,
+            deleted: row.get("deleted")?,
+        })
+    }
+}
+
+pub(crate) fn create(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        r#"CREATE VIEW {}(
+            id, level, name, area, start, end, river_width, elevation, ratio, line, allow,
+            safe, depth, channel_width, threshold, dredging, time, deleted
+        ) AS
+        SELECT
+            rivers.id, rivers.level, rivers.name, rivers.area, rivers.start, rivers.end,
+            rivers.river_width, rivers.elevation, rivers.ratio, rivers.line, rivers.allow,
+            rivers.safe, inspections.depth, rivers.channel_width, inspections.threshold,
+            inspections.dredging, inspections.time, rivers.deleted
+        FROM rivers
+        JOIN inspections ON inspections.id = (
+            SELECT id FROM inspections
+            WHERE river_id = rivers.id
+            ORDER BY time DESC, id DESC
+            LIMIT 1
+        )
+        WHERE rivers.deleted IS NOT NULL"#,
+        RECYCLE_VIEW
+    ))
+}
+
+/// Deleted rivers, the latest deleted first.
+pub fn deleted_rivers(conn: &Connection) -> Result<Vec<DeletedRiver>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM {} ORDER BY deleted DESC, id DESC",
+        RECYCLE_VIEW
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut rivers = Vec::new();
+    while let Some(row) = rows.next()? {
+        rivers.push(DeletedRiver::from_row(row)?);
+    }
+    Ok(rivers)
+}
+
+/// Deleted river `id`, `None` when it is not in the recycle bin.
+pub fn find(conn: &Connection, id: u32) -> Result<Option<DeletedRiver>> {
+    conn.query_row(
+        &format!("SELECT * FROM {} WHERE id=?1", RECYCLE_VIEW),
+        [id],
+        DeletedRiver::from_row,
+    )
+    .optional()
+}
+
+/// Deleted river with the name, jurisdiction and ends of `model`, the latest
+/// deleted if there are several.
+pub fn find_same(conn: &Connection, model: &SecurityModel) -> Result<Option<DeletedRiver>> {
+    conn.query_row(
+        &format!(
+            "SELECT * FROM {} WHERE name=?1 AND area=?2 AND start=?3 AND end=?4 \
+             ORDER BY deleted DESC, id DESC LIMIT 1",
+            RECYCLE_VIEW
+        ),
+        [&model.name, &model.area, &model.start, &model.end],
+        DeletedRiver::from_row,
+    )
+    .optional()
+}
+
+/// Puts deleted river `id` back in the list with its inspections, unless
+/// another river of the same name, jurisdiction and ends has been entered
+/// since.
+pub fn restore(conn: &DbConn<SecurityModel>, id: u32) -> Result<SecurityModel, String> {
+    let river = find(&conn.instance, id)
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| format!("回收站中没有编号为{}的河道", id))?;
+    if let Some(other) = river
+        .model
+        .find_same(&conn.instance, id)
+        .map_err(|error| error.to_string())?
+    {
+        return Err(format!(
+            "{}，无法恢复",
+            security_model::conflict_message(other)
+        ));
+    }
+    let restore = || -> Result<()> {
+        let tx = conn.instance.unchecked_transaction()?;
+        conn.instance
+            .execute("UPDATE rivers SET deleted=NULL WHERE id=?1", [id])?;
+        search::index(&conn.instance, id)?;
+        audit::record_as(
+            &conn.instance,
+            AuditAction::Restore,
+            id,
+            None,
+            audit::snapshot(&conn.instance, id)?,
+        )?;
+        tx.commit()
+    };
+    restore().map_err(|error| error.to_string())?;
+    Ok(river.model)
+}
+
+/// Removes deleted river `id` and its inspections for good. Only the change
+/// log keeps its last values. Returns the number of rivers removed.
+pub fn purge(conn: &Connection, id: u32) -> Result<usize> {
+    let river = match find(conn, id)? {
+        Some(river) => river,
+        None => return Ok(0),
+    };
+    let tx = conn.unchecked_transaction()?;
+    conn.execute("DELETE FROM inspections WHERE river_id=?1", [id])?;
+    let num = conn.execute(
+        "DELETE FROM rivers WHERE id=?1 AND deleted IS NOT NULL",
+        [id],
+    )?;
+    audit::record_as(
+        conn,
+        AuditAction::Purge,
+        id,
+        Some(river.model.to_json()),
+        None,
+    )?;
+    tx.commit()?;
+    Ok(num)
+}
+
+/// Purges every river in the recycle bin, see `purge`.
+pub fn purge_all(conn: &Connection) -> Result<usize> {
+    let mut num = 0;
+    for river in deleted_rivers(conn)? {
+        num += purge(conn, river.model.id)?;
+    }
+    Ok(num)
+}
@@ -0,0 +1,343 @@
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use rust_xlsxwriter::{
+    utility::row_col_to_cell, Color, ConditionalFormatFormula, ExcelDateTime, Format, FormatAlign,
+    FormatBorder, Workbook, Worksheet, XlsxError,
+};
+
+use crate::{
+    db::{DbConn, Field, FieldType, FieldValue, Model},
+    dredging,
+    export::{self, ExportColumn, ExportOptions},
+    security_model::SecurityModel,
+    statistics::{self, GroupBy, Statistics, STATISTICS_HEADERS},
+};
+
+pub const DETAIL_SHEET: &str = "河道明细";
+pub const SUMMARY_SHEET: &str = "汇总";
+pub const STATISTICS_SHEET: &str = "统计";
+
+const SUMMARY_HEADERS: [&str; 7] = [
+    "河道所属辖区",
+    "河道防洪排涝等级",
+    "河道数",
+    "需要清淤",
+    "建议清淤",
+    "总长度(km)",
+    "长度未知",
+];
+
+/// Statistics per jurisdiction and level, ordered by jurisdiction then
+/// level, with a subtotal after each jurisdiction keyed by the jurisdiction
+/// alone, then the grand total with an empty key.
+pub fn summary_rows(models: &[SecurityModel]) -> Vec<Statistics> {
+    let (levels, _) = statistics::tally(models, &[GroupBy::Area, GroupBy::Level]);
+    let (areas, total) = statistics::tally(models, &[GroupBy::Area]);
+    let mut rows = Vec::new();
+    let mut levels = levels.into_iter().peekable();
+    for area in areas {
+        while let Some(level) = levels.next_if(|level| level.key[0] == area.key[0]) {
+            rows.push(level);
+        }
+        rows.push(area);
+    }
+    rows.push(total);
+    rows
+}
+
+/// Fills of the detail rows: red when dredging is needed, amber when it is
+/// suggested.
+const VERDICT_FILLS: [(&str, Color); 2] = [
+    (dredging::NEEDED, Color::RGB(0xFFC7CE)),
+    (dredging::SUGGESTED, Color::RGB(0xFFEB9C)),
+];
+
+fn verdict_color(dredging: &str) -> Option<Color> {
+    VERDICT_FILLS
+        .iter()
+        .find(|(verdict, _)| *verdict == dredging)
+        .map(|(_, color)| *color)
+}
+
+pub(crate) fn header_format() -> Format {
+    Format::new()
+        .set_bold()
+        .set_font_color(Color::White)
+        .set_background_color(Color::RGB(0x1F4E78))
+        .set_align(FormatAlign::Center)
+        .set_align(FormatAlign::VerticalCenter)
+        .set_text_wrap()
+        .set_border(FormatBorder::Thin)
+}
+
+/// Cell format of a column: whole numbers, two decimals followed by the unit,
+/// or a full date and time.
+fn column_format(column: ExportColumn, fields: &[Field<SecurityModel>]) -> Format {
+    let format = Format::new().set_border(FormatBorder::Thin);
+    let field = match column {
+        ExportColumn::Field(index) => &fields[index],
+        ExportColumn::Forecast(_) => return format.set_align(FormatAlign::Center),
+    };
+    match (field.field_type, field.unit) {
+        (FieldType::Integer, _) => format.set_num_format("0"),
+        (FieldType::Real, Some(unit)) => format.set_num_format(format!("0.00\" {}\"", unit)),
+        (FieldType::Real, None) => format.set_num_format("0.00"),
+        (FieldType::DateTime, _) => format.set_num_format("yyyy-mm-dd hh:mm:ss"),
+        (FieldType::Text, _) => format,
+    }
+}
+
+/// Width in characters of `text` as Excel shows it, counting CJK characters
+/// twice.
+pub(crate) fn text_width(text: &str) -> f64 {
+    text.chars()
+        .map(|c| if (c as u32) >= 0x2E80 { 2.0 } else { 1.0 })
+        .sum()
+}
+
+fn excel_date_time(time: &NaiveDateTime) -> Result<ExcelDateTime, XlsxError> {
+    ExcelDateTime::from_ymd(time.year() as u16, time.month() as u8, time.day() as u8)?.and_hms(
+        time.hour() as u16,
+        time.minute() as u8,
+        time.second(),
+    )
+}
+
+pub(crate) fn write_value(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: FieldValue,
+    format: &Format,
+) -> Result<(), XlsxError> {
+    match value {
+        FieldValue::Empty => sheet.write_blank(row, col, format)?,
+        FieldValue::Number(number) => sheet.write_number_with_format(row, col, number, format)?,
+        FieldValue::Text(text) => sheet.write_string_with_format(row, col, text, format)?,
+        FieldValue::DateTime(time) => {
+            sheet.write_datetime_with_format(row, col, excel_date_time(&time)?, format)?
+        }
+    };
+    Ok(())
+}
+
+fn write_details(
+    conn: &DbConn<SecurityModel>,
+    sheet: &mut Worksheet,
+    options: &ExportOptions,
+    models: &[SecurityModel],
+) -> Result<(), XlsxError> {
+    let fields = SecurityModel::fields();
+    let columns = options.columns();
+    let formats: Vec<Format> = columns
+        .iter()
+        .map(|column| column_format(*column, &fields))
+        .collect();
+    let mut widths: Vec<f64> = Vec::new();
+
+    sheet.set_name(DETAIL_SHEET)?;
+    sheet.set_row_height(0, 30)?;
+    for (col, column) in columns.iter().enumerate() {
+        let header = column.header(&fields);
+        widths.push(text_width(&header));
+        sheet.write_string_with_format(0, col as u16, header, &header_format())?;
+    }
+    // Rows follow later edits of the verdict through conditional formats;
+    // without the verdict column they get fixed fills instead.
+    let verdict_column = columns.iter().position(
+        |column| matches!(column, ExportColumn::Field(index) if fields[*index].column == "dredging"),
+    );
+    if let Some(verdict_col) = verdict_column.filter(|_| !models.is_empty()) {
+        let verdict_cell = format!("${}", row_col_to_cell(1, verdict_col as u16));
+        for (verdict, color) in VERDICT_FILLS.iter() {
+            let rule = ConditionalFormatFormula::new()
+                .set_rule(format!("={}=\"{}\"", verdict_cell, verdict).as_str())
+                .set_format(Format::new().set_background_color(*color));
+            sheet.add_conditional_format(
+                1,
+                0,
+                models.len() as u32,
+                columns.len() as u16 - 1,
+                &rule,
+            )?;
+        }
+    }
+    for (index, model) in models.iter().enumerate() {
+        let row = index as u32 + 1;
+        let color = match verdict_column {
+            Some(_) => None,
+            None => verdict_color(&model.dredging),
+        };
+        let values = export::values(conn, &fields, &columns, model);
+        for (col, value) in values.into_iter().enumerate() {
+            // Units and date parts shown by the number formats take room too.
+            let shown = text_width(&export::text(value.clone())) + 2.0;
+            widths[col] = widths[col].max(shown);
+            let format = match color {
+                Some(color) => formats[col].clone().set_background_color(color),
+                None => formats[col].clone(),
+            };
+            write_value(sheet, row, col as u16, value, &format)?;
+        }
+    }
+    for (col, width) in widths.iter().enumerate() {
+        sheet.set_column_width(col as u16, width.clamp(8.0, 50.0))?;
+    }
+
+    // Keep the headers and, when exported, the names in view while scrolling.
+    let name_column = columns.iter().position(
+        |column| matches!(column, ExportColumn::Field(index) if fields[*index].column == "name"),
+    );
+    sheet.set_freeze_panes(1, name_column.map_or(0, |col| col as u16 + 1))?;
+    if !columns.is_empty() {
+        sheet.autofilter(0, 0, models.len() as u32, columns.len() as u16 - 1)?;
+    }
+    Ok(())
+}
+
+fn write_summary(sheet: &mut Worksheet, models: &[SecurityModel]) -> Result<(), XlsxError> {
+    let body = Format::new().set_border(FormatBorder::Thin);
+    let subtotal_fill = Color::RGB(0xDDEBF7);
+
+    sheet.set_name(SUMMARY_SHEET)?;
+    sheet.set_row_height(0, 30)?;
+    for (col, header) in SUMMARY_HEADERS.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &header_format())?;
+        sheet.set_column_width(col as u16, if col < 2 { 24 } else { 12 })?;
+    }
+    for (index, summary) in summary_rows(models).iter().enumerate() {
+        let row = index as u32 + 1;
+        let format = match summary.key.len() {
+            2 => body.clone(),
+            _ => body.clone().set_bold().set_background_color(subtotal_fill),
+        };
+        let (area, level) = match summary.key.as_slice() {
+            [area, level] => (area.as_str(), level.as_str()),
+            [area] => (area.as_str(), "小计"),
+            _ => ("合计", ""),
+        };
+        sheet.write_string_with_format(row, 0, area, &format)?;
+        sheet.write_string_with_format(row, 1, level, &format)?;
+        for (col, count) in [summary.rivers, summary.needed, summary.suggested]
+            .iter()
+            .enumerate()
+        {
+            sheet.write_number_with_format(
+                row,
+                col as u16 + 2,
+                *count as f64,
+                &format.clone().set_num_format("0"),
+            )?;
+        }
+        sheet.write_number_with_format(
+            row,
+            5,
+            summary.length,
+            &format.clone().set_num_format("0.000"),
+        )?;
+        sheet.write_number_with_format(
+            row,
+            6,
+            summary.missing as f64,
+            &format.clone().set_num_format("0"),
+        )?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+fn write_statistics(
+    sheet: &mut Worksheet,
+    groups: &[GroupBy],
+    rows: &[Statistics],
+    total: &Statistics,
+) -> Result<(), XlsxError> {
+    let body = Format::new().set_border(FormatBorder::Thin);
+    let total_format = body
+        .clone()
+        .set_bold()
+        .set_background_color(Color::RGB(0xDDEBF7));
+    let key_columns = groups.len() as u16;
+
+    sheet.set_name(STATISTICS_SHEET)?;
+    sheet.set_row_height(0, 30)?;
+    let headers = groups
+        .iter()
+        .map(|group| group.header())
+        .chain(STATISTICS_HEADERS.iter().copied());
+    for (col, header) in headers.enumerate() {
+        let col = col as u16;
+        sheet.write_string_with_format(0, col, header, &header_format())?;
+        sheet.set_column_width(col, if col < key_columns { 24 } else { 12 })?;
+    }
+    for (index, statistics) in rows.iter().chain(Some(total)).enumerate() {
+        let row = index as u32 + 1;
+        let format = if index == rows.len() {
+            &total_format
+        } else {
+            &body
+        };
+        for col in 0..key_columns {
+            let text = match statistics.key.get(col as usize) {
+                Some(text) => text.as_str(),
+                None if col == 0 => "合计",
+                None => "",
+            };
+            sheet.write_string_with_format(row, col, text, format)?;
+        }
+        let numbers = [
+            (statistics.rivers as f64, "0"),
+            (statistics.needed as f64, "0"),
+            (statistics.suggested as f64, "0"),
+            (statistics.length, "0.000"),
+            (statistics.missing as f64, "0"),
+            (statistics.average_depth(), "0.00"),
+            (statistics.max_depth, "0.00"),
+            (statistics.exceeding as f64, "0"),
+            (statistics.max_exceedance, "0.00"),
+            (statistics.exceeding_ratio(), "0.0%"),
+        ];
+        for (offset, (number, number_format)) in numbers.iter().enumerate() {
+            sheet.write_number_with_format(
+                row,
+                key_columns + offset as u16,
+                *number,
+                &format.clone().set_num_format(*number_format),
+            )?;
+        }
+    }
+    sheet.set_freeze_panes(1, key_columns)?;
+    Ok(())
+}
+
+/// Writes the rivers chosen by `options` as a report for reading rather than
+/// re-import: a styled detail sheet with rows coloured by dredging verdict,
+/// following edits of the verdict cells when that column is exported and
+/// fixed otherwise, a summary sheet per jurisdiction and level and, when
+/// `options` groups
+/// statistics, a statistics sheet. Returns the number of rivers written.
+pub fn write_report<P: AsRef<Path>>(
+    conn: &DbConn<SecurityModel>,
+    path: P,
+    options: &ExportOptions,
+) -> Result<usize, String> {
+    let models = options.rivers(conn)?;
+    let statistics = if options.group_by.is_empty() {
+        None
+    } else {
+        Some(statistics::statistics(conn, &options.group_by, options)?)
+    };
+    let mut workbook = Workbook::new();
+    write_details(conn, workbook.add_worksheet(), options, &models)
+        .and_then(|_| write_summary(workbook.add_worksheet(), &models))
+        .and_then(|_| match statistics.as_ref() {
+            Some((rows, total)) => {
+                write_statistics(workbook.add_worksheet(), &options.group_by, rows, total)
+            }
+            None => Ok(()),
+        })
+        .and_then(|_| workbook.save(path.as_ref()))
+        .map_err(|error| error.to_string())?;
+    Ok(models.len())
+}
@@ -0,0 +1,175 @@
+use pinyin::ToPinyin;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// FTS5 table of the searchable text of each river, by river id. Chinese
+/// text is stored one character per token, so a phrase query finds any run
+/// of characters; the pinyin columns hold every suffix of the spelling of the
+/// name and jurisdiction, so a prefix query finds a run of syllables.
+pub const SEARCH_TABLE: &str = "river_search";
+
+/// Columns searched for the keyword as written.
+const TEXT_COLUMNS: &str = "{name area start end dredging}";
+/// Columns searched for a keyword of Latin letters.
+const PINYIN_COLUMNS: &str = "{spelling initials}";
+
+pub(crate) fn create(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE {} USING fts5(name, area, start, end, dredging, spelling, initials)",
+        SEARCH_TABLE
+    ))?;
+    let ids = conn
+        .prepare("SELECT id FROM rivers")?
+        .query_map([], |row| row.get::<_, u32>(0))?
+        .collect::<Result<Vec<u32>>>()?;
+    for id in ids {
+        index(conn, id)?;
+    }
+    Ok(())
+}
+
+/// Replaces the indexed text of river `id` with its stored name,
+/// jurisdiction, ends and latest verdict.
+pub(crate) fn index(conn: &Connection, id: u32) -> Result<()> {
+    unindex(conn, id)?;
+    let river = conn
+        .query_row(
+            "SELECT name, area, start, end, dredging FROM water_security WHERE id=?1",
+            [id],
+            |row| {
+                Ok([
+                    row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                ])
+            },
+        )
+        .optional()?;
+    let [name, area, start, end, dredging] = match river {
+        Some(river) => river,
+        None => return Ok(()),
+    };
+    let (mut spelling, mut initials) = (Vec::new(), Vec::new());
+    for text in [&name, &area].iter() {
+        let syllables = syllables(text);
+        spelling.push(suffixes(&syllables, ""));
+        initials.push(suffixes(
+            &syllables
+                .iter()
+                .map(|syllable| &syllable[..1])
+                .collect::<Vec<&str>>(),
+            "",
+        ));
+    }
+    conn.execute(
+        &format!(
+            "INSERT INTO {}(rowid, name, area, start, end, dredging, spelling, initials) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            SEARCH_TABLE
+        ),
+        params![
+            id,
+            segment(&name),
+            segment(&area),
+            segment(&start),
+            segment(&end),
+            segment(&dredging),
+            spelling.join(" "),
+            initials.join(" "),
+        ],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn unindex(conn: &Connection, id: u32) -> Result<()> {
+    conn.execute(
+        &format!("DELETE FROM {} WHERE rowid=?1", SEARCH_TABLE),
+        [id],
+    )?;
+    Ok(())
+}
+
+/// Puts spaces around every character that is not an ASCII letter or digit,
+/// so that the tokenizer makes each Chinese character a token.
+fn segment(text: &str) -> String {
+    let mut segmented = String::with_capacity(text.len() * 2);
+    for c in text.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            segmented.push(c);
+        } else {
+            segmented.push(' ');
+            segmented.push(c);
+            segmented.push(' ');
+        }
+    }
+    segmented
+}
+
+/// Pinyin of each Chinese character of `text`, without tones, and each run
+/// of ASCII letters and digits as is. Characters with several readings take
+/// the most common one.
+fn syllables(text: &str) -> Vec<String> {
+    let mut syllables = Vec::new();
+    let mut word = String::new();
+    for c in text.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            syllables.push(word.clone());
+            word.clear();
+        }
+        if let Some(pinyin) = c.to_pinyin() {
+            syllables.push(String::from(pinyin.plain()));
+        }
+    }
+    if !word.is_empty() {
+        syllables.push(word);
+    }
+    syllables
+}
+
+/// Every suffix of `parts` joined by `separator`, as space-separated tokens,
+/// e.g. "qinghehu hehu hu".
+fn suffixes<S: AsRef<str>>(parts: &[S], separator: &str) -> String {
+    (0..parts.len())
+        .map(|start| {
+            parts[start..]
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<&str>>()
+                .join(separator)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// FTS5 query finding rivers whose text contains every word of `keyword`;
+/// words of Latin letters also match the pinyin spelling or initials of the
+/// name or jurisdiction from any syllable on. `None` when there are no
+/// words.
+pub fn expression(keyword: &str) -> Option<String> {
+    let terms: Vec<String> = keyword
+        .split_whitespace()
+        .filter_map(|word| {
+            let tokens = segment(word);
+            let tokens: Vec<&str> = tokens.split_whitespace().collect();
+            if tokens.is_empty() {
+                return None;
+            }
+            let phrase = format!("\"{}\"*", tokens.join(" ").replace('"', "\"\""));
+            let mut any = vec![format!("{} : {}", TEXT_COLUMNS, phrase)];
+            if word.chars().all(|c| c.is_ascii_alphabetic()) {
+                any.push(format!("{} : {}*", PINYIN_COLUMNS, word.to_lowercase()));
+            }
+            Some(format!("({})", any.join(" OR ")))
+        })
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" AND "))
+    }
+}
@@ -0,0 +1,367 @@
+use std::{fs, path::Path};
+
+use crate::{dredging::number_text, security_model::SecurityModel};
+
+/// Size of a sketch in drawing units, which SVG output uses as pixels.
+pub const SKETCH_WIDTH: f32 = 560.0;
+pub const SKETCH_HEIGHT: f32 = 280.0;
+
+/// Left and right ends of the bank tops, with room for labels outside.
+const PLOT_LEFT: f32 = 120.0;
+const PLOT_RIGHT: f32 = 360.0;
+/// Bed and top of the drawn height range.
+const PLOT_BOTTOM: f32 = 240.0;
+const PLOT_TOP: f32 = 30.0;
+/// Length of ground drawn beyond each bank.
+const GROUND: f32 = 24.0;
+/// Height range drawn, relative to the bank height, so that levels a little
+/// above the banks stay in view.
+const HEADROOM: f32 = 1.25;
+
+/// Trapezoidal cross-section of a reach, with heights in metres above the
+/// design bed.
+pub struct Section {
+    /// Width between the bank tops, `river_width`.
+    pub top_width: f32,
+    /// Width of the bed, the computed channel width, at least zero.
+    pub bottom_width: f32,
+    /// Design bed elevation, `elevation`.
+    pub bed: f32,
+    /// Bank height: the design flood level plus the freeboard.
+    pub height: f32,
+    /// Design flood level, `line`.
+    pub flood: f32,
+    /// Siltation depth, `depth`.
+    pub silt: f32,
+    /// Siltation threshold, `threshold`.
+    pub threshold: f32,
+}
+
+impl Section {
+    /// Section of `model` as stored; recompute the model first to draw the
+    /// current channel width and threshold.
+    pub fn of(model: &SecurityModel) -> Self {
+        Section {
+            top_width: model.river_width.max(0.0),
+            bottom_width: model.channel_width.max(0.0).min(model.river_width.max(0.0)),
+            bed: model.elevation,
+            height: (model.line + model.safe).max(0.0),
+            flood: model.line.max(0.0),
+            silt: model.depth.max(0.0),
+            threshold: model.threshold.max(0.0),
+        }
+    }
+
+    /// Width of the section at `height` above the bed, which is the top
+    /// width above the banks.
+    pub fn width_at(&self, height: f32) -> f32 {
+        if self.height <= 0.0 || height >= self.height {
+            return self.top_width;
+        }
+        self.bottom_width + (self.top_width - self.bottom_width) * height / self.height
+    }
+
+    /// Shapes of a sketch that is not to scale: the horizontal and vertical
+    /// scales are chosen separately so that wide, shallow sections stay
+    /// readable. Levels beyond the drawn range are only labelled.
+    pub fn sketch(&self) -> Vec<Shape> {
+        let bank = self.height.max(f32::EPSILON);
+        let range = bank * HEADROOM;
+        let half = self.top_width.max(f32::EPSILON) / 2.0;
+        let x = |offset: f32| PLOT_LEFT + (offset + half) / (2.0 * half) * (PLOT_RIGHT - PLOT_LEFT);
+        let y = |height: f32| PLOT_BOTTOM - height.min(range) / range * (PLOT_BOTTOM - PLOT_TOP);
+        let point = |side: f32, height: f32| (x(side * self.width_at(height) / 2.0), y(height));
+        let level = |height: f32| format!("▽{}", number_text(self.bed + height));
+        let label = |at: (f32, f32), text: String, anchor: Anchor| Shape::Text {
+            at,
+            text,
+            size: 12.0,
+            anchor,
+        };
+
+        let mut shapes = Vec::new();
+        let silt = self.silt.min(bank);
+        let flood = self.flood.min(bank);
+        if flood > silt {
+            shapes.push(Shape::Polygon {
+                points: vec![
+                    point(-1.0, silt),
+                    point(-1.0, flood),
+                    point(1.0, flood),
+                    point(1.0, silt),
+                ],
+                fill: WATER_FILL,
+                stroke: "none",
+            });
+        }
+        if silt > 0.0 {
+            shapes.push(Shape::Polygon {
+                points: vec![
+                    point(-1.0, 0.0),
+                    point(-1.0, silt),
+                    point(1.0, silt),
+                    point(1.0, 0.0),
+                ],
+                fill: SILT_COLOR,
+                stroke: SILT_COLOR,
+            });
+        }
+        shapes.push(Shape::Line {
+            from: point(-1.0, flood),
+            to: point(1.0, flood),
+            stroke: WATER_COLOR,
+            dashed: false,
+        });
+        if self.threshold <= range {
+            shapes.push(Shape::Line {
+                from: point(-1.0, self.threshold),
+                to: point(1.0, self.threshold),
+                stroke: THRESHOLD_COLOR,
+                dashed: true,
+            });
+        }
+
+        // Ground beyond the banks, then the banks and bed.
+        shapes.push(Shape::Line {
+            from: (PLOT_LEFT - GROUND, y(bank)),
+            to: point(-1.0, bank),
+            stroke: OUTLINE_COLOR,
+            dashed: false,
+        });
+        shapes.push(Shape::Line {
+            from: point(1.0, bank),
+            to: (PLOT_RIGHT + GROUND, y(bank)),
+            stroke: OUTLINE_COLOR,
+            dashed: false,
+        });
+        shapes.push(Shape::Polygon {
+            points: vec![
+                point(-1.0, bank),
+                point(-1.0, 0.0),
+                point(1.0, 0.0),
+                point(1.0, bank),
+            ],
+            fill: "none",
+            stroke: OUTLINE_COLOR,
+        });
+
+        // Freeboard between the flood level and the bank top.
+        let dimension = PLOT_LEFT - GROUND / 2.0;
+        if self.height > self.flood {
+            shapes.push(Shape::Line {
+                from: (dimension, y(flood)),
+                to: (dimension, y(bank)),
+                stroke: OUTLINE_COLOR,
+                dashed: false,
+            });
+            for height in [flood, bank].iter() {
+                shapes.push(Shape::Line {
+                    from: (dimension - 4.0, y(*height)),
+                    to: (dimension + 4.0, y(*height)),
+                    stroke: OUTLINE_COLOR,
+                    dashed: false,
+                });
+            }
+            shapes.push(label(
+                (dimension - 6.0, (y(flood) + y(bank)) / 2.0 + 4.0),
+                format!("安全超高 {} m", number_text(self.height - self.flood)),
+                Anchor::End,
+            ));
+        }
+
+        let left = PLOT_LEFT - GROUND - 4.0;
+        let right = PLOT_RIGHT + GROUND + 4.0;
+        shapes.push(label(
+            (left, y(bank) - 6.0),
+            format!("堤顶 {}", level(self.height)),
+            Anchor::End,
+        ));
+        shapes.push(label(
+            (left, y(0.0) + 4.0),
+            format!("设计河底 {}", level(0.0)),
+            Anchor::End,
+        ));
+        shapes.push(label(
+            (right, y(flood) + 4.0),
+            format!("设计洪水位 {}", level(self.flood)),
+            Anchor::Start,
+        ));
+        let threshold = format!("淤积阈值 T = {} m", number_text(self.threshold));
+        if self.threshold <= range {
+            shapes.push(label(
+                (right, y(self.threshold) + 4.0),
+                threshold,
+                Anchor::Start,
+            ));
+        } else {
+            shapes.push(label((right, PLOT_TOP - 12.0), threshold, Anchor::Start));
+            shapes.push(label(
+                (right, PLOT_TOP + 2.0),
+                String::from("（超出图幅）"),
+                Anchor::Start,
+            ));
+        }
+        shapes.push(label(
+            ((PLOT_LEFT + PLOT_RIGHT) / 2.0, y(bank) - 8.0),
+            format!("B = {} m", number_text(self.top_width)),
+            Anchor::Middle,
+        ));
+        shapes.push(label(
+            ((PLOT_LEFT + PLOT_RIGHT) / 2.0, y(0.0) + 18.0),
+            format!("b = {} m", number_text(self.bottom_width)),
+            Anchor::Middle,
+        ));
+        if silt > 0.0 {
+            shapes.push(label(
+                ((PLOT_LEFT + PLOT_RIGHT) / 2.0, y(silt / 2.0) + 4.0),
+                format!(
+                    "淤积深度 d = {} m{}",
+                    number_text(self.silt),
+                    if self.silt > bank {
+                        "（高于堤顶）"
+                    } else {
+                        ""
+                    }
+                ),
+                Anchor::Middle,
+            ));
+        }
+        shapes.push(Shape::Text {
+            at: (SKETCH_WIDTH - 4.0, SKETCH_HEIGHT - 4.0),
+            text: String::from("示意图，不按比例"),
+            size: 10.0,
+            anchor: Anchor::End,
+        });
+        shapes
+    }
+}
+
+/// Writes the sketch of each river to `dir` as `<id>-<name>.svg`, creating
+/// the directory if needed. Rivers are drawn as stored. Returns the number
+/// of files written.
+pub fn write_sections<P: AsRef<Path>>(models: &[SecurityModel], dir: P) -> Result<usize, String> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).map_err(|error| format!("{}：{}", dir.display(), error))?;
+    for model in models {
+        let name: String = model
+            .name
+            .chars()
+            .map(|c| if "\\/:*?\"<>|".contains(c) { '_' } else { c })
+            .collect();
+        let path = dir.join(format!("{}-{}.svg", model.id, name));
+        fs::write(&path, svg(&Section::of(model).sketch()))
+            .map_err(|error| format!("{}：{}", path.display(), error))?;
+    }
+    Ok(models.len())
+}
+
+pub const OUTLINE_COLOR: &str = "#333333";
+pub const WATER_COLOR: &str = "#1F77B4";
+pub const WATER_FILL: &str = "#D6EAF8";
+pub const SILT_COLOR: &str = "#C8A165";
+pub const THRESHOLD_COLOR: &str = "#C00000";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// A drawing primitive in sketch units, with y growing downwards. Colours
+/// are `#RRGGBB` or `none`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+    Polygon {
+        points: Vec<(f32, f32)>,
+        fill: &'static str,
+        stroke: &'static str,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        stroke: &'static str,
+        dashed: bool,
+    },
+    Text {
+        at: (f32, f32),
+        text: String,
+        size: f32,
+        anchor: Anchor,
+    },
+}
+
+/// Escapes text for XML and HTML content and attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders shapes as a standalone SVG element.
+pub fn svg(shapes: &[Shape]) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"SimSun, sans-serif\">\n",
+        SKETCH_WIDTH, SKETCH_HEIGHT
+    );
+    for shape in shapes {
+        match shape {
+            Shape::Polygon {
+                points,
+                fill,
+                stroke,
+            } => svg.push_str(&format!(
+                "  <polygon points=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"1.5\"/>\n",
+                points
+                    .iter()
+                    .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                fill,
+                stroke
+            )),
+            Shape::Line {
+                from,
+                to,
+                stroke,
+                dashed,
+            } => svg.push_str(&format!(
+                "  <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"1.5\"{}/>\n",
+                from.0,
+                from.1,
+                to.0,
+                to.1,
+                stroke,
+                if *dashed { " stroke-dasharray=\"6 4\"" } else { "" }
+            )),
+            Shape::Text {
+                at,
+                text,
+                size,
+                anchor,
+            } => svg.push_str(&format!(
+                "  <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{}\" text-anchor=\"{}\">{}</text>\n",
+                at.0,
+                at.1,
+                size,
+                match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                },
+                escape(text)
+            )),
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -1,20 +1,24 @@
 use std::{
     cell::RefCell,
-    ffi::OsString,
     mem::size_of,
     thread::{self, JoinHandle},
 };
 
-use calamine::{Reader, Xlsx};
-use chrono::{Local, TimeZone};
+use chrono::Local;
 use nwd::{NwgPartial, NwgUi};
 use nwg::NativeUi;
-use rusqlite::Result;
-use simple_excel_writer::{row, Column, Row, Workbook};
 
-use crate::{
-    db::{DbConn, Model, ModelNameType},
-    security_model::SecurityModel,
+use wrs_nwg::{
+    audit::{self, AuditAction, AuditEntry},
+    chainage::{check_reach, Chainage},
+    db::{DbConn, Model, ModelNameType, DATE_TIME_FORMAT},
+    dredging, duplicate,
+    export::{self, ExportOptions, TextEncoding},
+    import::{self, ConflictPolicy, ImportPreview},
+    inspection_model::InspectionModel,
+    recycle, report,
+    security_model::{check_area, level_text, SecurityModel},
+    template,
 };
 
 enum SecurityFormError {
@@ -283,15 +287,9 @@ impl SecurityFormWindow {
             self.security_form_ui
                 .level_input
                 .set_pos(model.level as usize);
-            let level_text = match model.level {
-                1 => "第一级",
-                2 => "第二级",
-                3 => "第三级",
-                4 => "第四级",
-                5 => "第五级",
-                _ => "第一级",
-            };
-            self.security_form_ui.level_text.set_text(level_text);
+            self.security_form_ui
+                .level_text
+                .set_text(level_text(model.level));
 
             self.security_form_ui
                 .name_input
@@ -408,15 +406,9 @@ impl SecurityFormWindow {
     }
 
     fn level_scroll(&self) {
-        let level_text = match self.security_form_ui.level_input.pos() {
-            1 => "第一级",
-            2 => "第二级",
-            3 => "第三级",
-            4 => "第四级",
-            5 => "第五级",
-            _ => "第一级",
-        };
-        self.security_form_ui.level_text.set_text(level_text);
+        self.security_form_ui
+            .level_text
+            .set_text(level_text(self.security_form_ui.level_input.pos() as u32));
 
         if self.security_form_ui.allow_radio_true.check_state() == nwg::RadioButtonState::Checked {
             self.allow_true_checked();
@@ -470,72 +462,47 @@ impl SecurityFormWindow {
         self.security_form_ui.safe_input.set_readonly(false);
     }
 
-    fn check_input(&self) -> Result<(), SecurityFormError> {
-        let name = self.security_form_ui.name_input.text();
-        if name.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写河道名称"),
-                String::from("name"),
-            ));
+    fn check_field(&self, column: &str, text: &str) -> Result<(), SecurityFormError> {
+        if let Some(field) = SecurityModel::fields()
+            .into_iter()
+            .find(|field| field.column == column)
+        {
+            if let Err(message) = field.check(text) {
+                return Err(SecurityFormError::InvalidInput(
+                    message,
+                    String::from(column),
+                ));
+            }
         }
+        Ok(())
+    }
+
+    fn check_input(&self) -> Result<(), SecurityFormError> {
+        self.check_field("name", &self.security_form_ui.name_input.text())?;
 
         let area = self.security_form_ui.area_input.text();
-        if area.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写河道所属辖区"),
-                String::from("area"),
-            ));
-        }
-        if !area.contains("一般")
-            && !area.contains("市")
-            && !area.contains("县")
-            && !area.contains("区")
-            && !area.contains("乡")
-        {
+        self.check_field("area", &area)?;
+        if let Err(message) = check_area(&area) {
             return Err(SecurityFormError::InvalidInput(
-                String::from(
-                    "河道所属辖区至少要含有【市】|【县】|【区】|【乡】|【一般】关键字中的一个",
-                ),
+                message,
                 String::from("area"),
             ));
         }
 
         let start = self.security_form_ui.start_input.text();
-        if start.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写河道起点"),
-                String::from("start"),
-            ));
-        }
-
+        self.check_field("start", &start)?;
         let end = self.security_form_ui.end_input.text();
-        if end.is_empty() {
+        self.check_field("end", &end)?;
+        if let Err(message) = check_reach(&start, &end) {
             return Err(SecurityFormError::InvalidInput(
-                String::from("请填写河道终点"),
+                message,
                 String::from("end"),
             ));
         }
-
-        let river_width = self.security_form_ui.river_width_input.text();
-        if river_width.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写河道宽度"),
-                String::from("river_width"),
-            ));
-        }
-        if let Ok(river_width) = river_width.parse::<f32>() {
-            if river_width <= 0.0 {
-                return Err(SecurityFormError::InvalidInput(
-                    String::from("河道宽度必须大于0"),
-                    String::from("river_width"),
-                ));
-            }
-        } else {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("河道宽度必须为数字"),
-                String::from("river_width"),
-            ));
-        }
+        self.check_field(
+            "river_width",
+            &self.security_form_ui.river_width_input.text(),
+        )?;
 
         let ratio = self.security_form_ui.ratio_input.text();
         if self.security_form_ui.ratio_radio_true.check_state() == nwg::RadioButtonState::Unchecked
@@ -572,47 +539,8 @@ impl SecurityFormWindow {
             }
         }
 
-        let elevation = self.security_form_ui.elevation_input.text();
-        if elevation.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写河底高程"),
-                String::from("elevation"),
-            ));
-        }
-        if let Ok(elevation) = elevation.parse::<f32>() {
-            if elevation <= 0.0 {
-                return Err(SecurityFormError::InvalidInput(
-                    String::from("河底高程必须大于0"),
-                    String::from("elevation"),
-                ));
-            }
-        } else {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("河底高程必须为数字"),
-                String::from("elevation"),
-            ));
-        }
-
-        let line = self.security_form_ui.line_input.text();
-        if line.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写洪水水位"),
-                String::from("line"),
-            ));
-        }
-        if let Ok(line) = line.parse::<f32>() {
-            if line <= 0.0 {
-                return Err(SecurityFormError::InvalidInput(
-                    String::from("洪水水位必须大于0"),
-                    String::from("line"),
-                ));
-            }
-        } else {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("洪水水位必须为数字"),
-                String::from("line"),
-            ));
-        }
+        self.check_field("elevation", &self.security_form_ui.elevation_input.text())?;
+        self.check_field("line", &self.security_form_ui.line_input.text())?;
 
         if self.security_form_ui.allow_radio_true.check_state() == nwg::RadioButtonState::Unchecked
             && self.security_form_ui.allow_radio_false.check_state()
@@ -626,47 +554,8 @@ impl SecurityFormWindow {
             ));
         }
 
-        let safe = self.security_form_ui.safe_input.text();
-        if safe.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写安全超高"),
-                String::from("safe"),
-            ));
-        }
-        if let Ok(safe) = safe.parse::<f32>() {
-            if safe <= 0.0 {
-                return Err(SecurityFormError::InvalidInput(
-                    String::from("安全超高必须大于0"),
-                    String::from("safe"),
-                ));
-            }
-        } else {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("安全超高必须为数字"),
-                String::from("safe"),
-            ));
-        }
-
-        let depth = self.security_form_ui.depth_input.text();
-        if depth.is_empty() {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("请填写淤积深度"),
-                String::from("depth"),
-            ));
-        }
-        if let Ok(depth) = depth.parse::<f32>() {
-            if depth <= 0.0 {
-                return Err(SecurityFormError::InvalidInput(
-                    String::from("淤积深度必须大于0"),
-                    String::from("depth"),
-                ));
-            }
-        } else {
-            return Err(SecurityFormError::InvalidInput(
-                String::from("淤积深度必须为数字"),
-                String::from("depth"),
-            ));
-        }
+        self.check_field("safe", &self.security_form_ui.safe_input.text())?;
+        self.check_field("depth", &self.security_form_ui.depth_input.text())?;
         Ok(())
     }
 
@@ -675,16 +564,6 @@ impl SecurityFormWindow {
             return Err(error);
         }
 
-        let area = self.security_form_ui.area_input.text();
-        let area = if area.contains("一般") || area.contains("乡") {
-            3
-        } else if area.contains("县") || area.contains("区") {
-            2
-        } else if area.contains("市") {
-            1
-        } else {
-            3
-        };
         let ratio = if self.security_form_ui.ratio_radio_true.check_state()
             == nwg::RadioButtonState::Checked
         {
@@ -696,122 +575,46 @@ impl SecurityFormWindow {
         } else {
             0.0
         };
-        let depth = self
-            .security_form_ui
-            .depth_input
-            .text()
-            .parse::<f32>()
-            .unwrap();
-        let river_width = self
-            .security_form_ui
-            .river_width_input
-            .text()
-            .parse::<f32>()
-            .unwrap();
-
-        self.security_form_ui
-            .channel_width_input
-            .set_text(river_width.to_string().as_str());
-
-        if ratio == 0.0 {
-            if area == 3 {
-                if depth <= 47.0 {
-                    self.security_form_ui.threshold_input.set_text("47");
-                    self.security_form_ui.dredging_input.set_text("不需要清淤.");
-                } else if depth <= 61.0 {
-                    self.security_form_ui.threshold_input.set_text("61");
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("建议对该河道进行清淤.");
-                } else {
-                    self.security_form_ui.threshold_input.set_text("61");
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("需要对该河道进行清淤.");
-                }
-            } else if area == 2 {
-                self.security_form_ui.threshold_input.set_text("19");
-                if depth <= 33.0 {
-                    self.security_form_ui.dredging_input.set_text("不需要清淤.");
-                } else {
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("需要对该河道进行清淤.");
-                }
-            } else if area == 1 {
-                self.security_form_ui.threshold_input.set_text("19");
-                if depth <= 19.0 {
-                    self.security_form_ui.dredging_input.set_text("不需要清淤.");
-                } else {
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("需要对该河道进行清淤.");
-                }
-            } else {
-                if depth <= 47.0 {
-                    self.security_form_ui.threshold_input.set_text("47");
-                    self.security_form_ui.dredging_input.set_text("不需要清淤.");
-                } else if depth <= 61.0 {
-                    self.security_form_ui.threshold_input.set_text("61");
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("建议对该河道进行清淤.");
-                } else {
-                    self.security_form_ui.threshold_input.set_text("61");
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("需要对该河道进行清淤.");
-                }
-            }
-        } else {
-            let line = self
+        let model = SecurityModel {
+            area: self.security_form_ui.area_input.text(),
+            ratio,
+            depth: self
+                .security_form_ui
+                .depth_input
+                .text()
+                .parse::<f32>()
+                .unwrap(),
+            river_width: self
+                .security_form_ui
+                .river_width_input
+                .text()
+                .parse::<f32>()
+                .unwrap(),
+            line: self
                 .security_form_ui
                 .line_input
                 .text()
                 .parse::<f32>()
-                .unwrap();
-            let safe = self
+                .unwrap_or_default(),
+            safe: self
                 .security_form_ui
                 .safe_input
                 .text()
                 .parse::<f32>()
-                .unwrap();
-
-            let slope_ratio = 1.0 / ratio;
+                .unwrap_or_default(),
+            ..Default::default()
+        };
 
-            let channel_width = river_width - 2.0 * (line + safe) * ratio;
-            self.security_form_ui
-                .channel_width_input
-                .set_text(channel_width.to_string().as_str());
-
-            let threshold = ((((0.04 / ratio)
-                * ((line + safe).powi(2) * slope_ratio + channel_width * (line + safe))
-                + channel_width.powi(2) / 4.0 * slope_ratio.powi(2))
-                - channel_width / 2.0 * slope_ratio)
-                * 100.0)
-                .round()
-                / 100.0;
-            if area == 3 || area == 2 || area == 1 {
-                if depth <= threshold {
-                    self.security_form_ui
-                        .threshold_input
-                        .set_text(threshold.to_string().as_str());
-                    self.security_form_ui.dredging_input.set_text("不需要清淤.");
-                } else {
-                    self.security_form_ui
-                        .threshold_input
-                        .set_text(threshold.to_string().as_str());
-                    self.security_form_ui
-                        .dredging_input
-                        .set_text("需要对该河道进行清淤.");
-                }
-            } else {
-                self.security_form_ui.threshold_input.set_text("0");
-                self.security_form_ui
-                    .dredging_input
-                    .set_text("计算错误，请检查输入数据.");
-            }
-        }
+        let assessment = dredging::assess(&model);
+        self.security_form_ui
+            .channel_width_input
+            .set_text(assessment.channel_width.to_string().as_str());
+        self.security_form_ui
+            .threshold_input
+            .set_text(assessment.threshold.to_string().as_str());
+        self.security_form_ui
+            .dredging_input
+            .set_text(assessment.dredging);
         Ok(())
     }
 
@@ -881,8 +684,8 @@ impl SecurityFormWindow {
                 model.level = self.security_form_ui.level_input.pos() as u32;
                 model.name = self.security_form_ui.name_input.text();
                 model.area = self.security_form_ui.area_input.text();
-                model.start = self.security_form_ui.start_input.text();
-                model.end = self.security_form_ui.end_input.text();
+                model.start = Chainage::normalize(&self.security_form_ui.start_input.text());
+                model.end = Chainage::normalize(&self.security_form_ui.end_input.text());
                 model.river_width = self
                     .security_form_ui
                     .river_width_input
@@ -917,21 +720,30 @@ impl SecurityFormWindow {
 
                 let id = model.id;
                 conn.set(model);
-                match if id > 0 { conn.update() } else { conn.insert() } {
-                    Ok(num) => nwg::simple_message(
-                        "提示",
-                        if num == 1 {
-                            "保存成功"
-                        } else {
-                            "保存失败"
-                        },
-                    ),
-                    Err(error) => nwg::simple_message("错误", error.to_string().as_str()),
+                let saved = match if id > 0 { conn.update() } else { conn.insert() } {
+                    Ok(num) => {
+                        nwg::simple_message(
+                            "提示",
+                            if num == 1 {
+                                "保存成功"
+                            } else {
+                                "保存失败"
+                            },
+                        );
+                        true
+                    }
+                    Err(error) => {
+                        nwg::simple_message("错误", error.to_string().as_str());
+                        false
+                    }
                 };
 
                 *self.db_conn.borrow_mut() = Some(conn);
 
-                self.window.close();
+                // Left open to correct the river, e.g. when it duplicates one.
+                if saved {
+                    self.window.close();
+                }
             }
         }
     }
@@ -945,10 +757,197 @@ impl SecurityFormWindow {
     }
 }
 
+#[derive(Default, NwgUi)]
+pub struct ExportFormWindow {
+    db_conn: RefCell<Option<DbConn<SecurityModel>>>,
+
+    #[nwg_control(size: (520, 440), center: true, title: "导出", flags: "WINDOW | VISIBLE")]
+    #[nwg_events(OnWindowClose: [Self::window_close])]
+    window: nwg::Window,
+
+    #[nwg_layout(parent: window, max_column: Some(4), max_row: Some(10))]
+    layout: nwg::GridLayout,
+
+    #[nwg_control(text: "河道所属辖区", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 0)]
+    area_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("全部，多个辖区用逗号分隔"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 0, col_span: 3)]
+    area_input: nwg::TextInput,
+
+    #[nwg_control(text: "防洪排涝等级", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 1)]
+    level_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("全部，如：1,2"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 1, col_span: 3)]
+    level_input: nwg::TextInput,
+
+    #[nwg_control(text: "清淤判断", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 2)]
+    dredging_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("全部，如：需要,建议"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 2, col_span: 3)]
+    dredging_input: nwg::TextInput,
+
+    #[nwg_control(text: "录入时间", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 3)]
+    time_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("起始日期"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 3)]
+    since_input: nwg::TextInput,
+
+    #[nwg_control(text: "至", h_align: nwg::HTextAlign::Center)]
+    #[nwg_layout_item(layout: layout, col: 2, row: 3)]
+    until_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("截止日期"))]
+    #[nwg_layout_item(layout: layout, col: 3, row: 3)]
+    until_input: nwg::TextInput,
+
+    #[nwg_control(text: "导出列", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 4)]
+    columns_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("全部，如：河道名称,淤积深度"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 4, col_span: 3)]
+    columns_input: nwg::TextInput,
+
+    #[nwg_control(text: "排序", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 5)]
+    sort_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("按编号，如：河道所属辖区,淤积深度:降序"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 5, col_span: 3)]
+    sort_input: nwg::TextInput,
+
+    #[nwg_control(text: "统计分组", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 6)]
+    group_by_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("生成报表时另加统计表，如：辖区,等级,清淤判断,年份"))]
+    #[nwg_layout_item(layout: layout, col: 1, row: 6, col_span: 3)]
+    group_by_input: nwg::TextInput,
+
+    #[nwg_control(text: "生成报表（带格式和汇总表，仅用于查看）")]
+    #[nwg_layout_item(layout: layout, col: 1, row: 7, col_span: 3)]
+    report_check: nwg::CheckBox,
+
+    #[nwg_control(size: (80, 30), text: "导出")]
+    #[nwg_layout_item(layout: layout, col: 2, row: 8)]
+    #[nwg_events(OnButtonClick: [Self::export_button_click])]
+    export_button: nwg::Button,
+
+    #[nwg_control(size: (80, 30), text: "取消")]
+    #[nwg_layout_item(layout: layout, col: 3, row: 8)]
+    #[nwg_events(OnButtonClick: [Self::cancel_button_click])]
+    cancel_button: nwg::Button,
+}
+
+impl ExportFormWindow {
+    pub fn window_open(
+        conn: Option<DbConn<SecurityModel>>,
+        sender: nwg::NoticeSender,
+    ) -> thread::JoinHandle<DbConn<SecurityModel>> {
+        thread::spawn(move || {
+            let app =
+                Self::build_ui(Default::default()).expect("Build ExportFormWindow UI failed.");
+
+            *app.db_conn.borrow_mut() = conn;
+
+            nwg::dispatch_thread_events();
+
+            sender.notice();
+
+            app.db_conn.take().unwrap()
+        })
+    }
+
+    fn window_close(&self) {
+        nwg::stop_thread_dispatch();
+    }
+
+    fn options(&self) -> Result<ExportOptions, String> {
+        let mut options = ExportOptions::default();
+        options.set_areas(&self.area_input.text());
+        options.set_levels(&self.level_input.text())?;
+        options.set_dredging(&self.dredging_input.text())?;
+        options.set_since(&self.since_input.text())?;
+        options.set_until(&self.until_input.text())?;
+        options.set_columns(&self.columns_input.text())?;
+        options.set_sort(&self.sort_input.text())?;
+        options.set_group_by(&self.group_by_input.text())?;
+        Ok(options)
+    }
+
+    fn export_button_click(&self) {
+        let options = match self.options() {
+            Ok(options) => options,
+            Err(error) => {
+                nwg::simple_message("错误", error.as_str());
+                return;
+            }
+        };
+
+        let report = self.report_check.check_state() == nwg::CheckBoxState::Checked;
+
+        let mut export_file_dialog = nwg::FileDialog::default();
+
+        if let Ok(_) = nwg::FileDialog::builder()
+            .title("请选择导出位置")
+            .action(nwg::FileDialogAction::Save)
+            .filters(if report {
+                "Excel文件(*.xlsx)"
+            } else {
+                "Excel文件(*.xlsx)|CSV文件(*.csv)|JSON文件(*.json)|NDJSON文件(*.ndjson)"
+            })
+            .build(&mut export_file_dialog)
+        {
+            if export_file_dialog.run(Some(&self.window)) {
+                if let Ok(export_file) = export_file_dialog.get_selected_item() {
+                    let conn = self.db_conn.borrow();
+                    let result = if report {
+                        report::write_report(conn.as_ref().unwrap(), export_file, &options)
+                    } else {
+                        // CSV is written as GBK so that Excel opens it correctly.
+                        export::write_file(
+                            conn.as_ref().unwrap(),
+                            export_file,
+                            TextEncoding::Gbk,
+                            &options,
+                        )
+                    };
+                    match result {
+                        Ok(num) => {
+                            nwg::simple_message(
+                                "导出",
+                                format!("导出完成，共{}条数据", num).as_str(),
+                            );
+                            self.window.close();
+                        }
+                        Err(error) => {
+                            nwg::simple_message("导出失败", error.as_str());
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    fn cancel_button_click(&self) {
+        self.window.close();
+    }
+}
+
 #[derive(Default, NwgUi)]
 pub struct SecurityApp {
     db_conn: RefCell<Option<DbConn<SecurityModel>>>,
     security_window_handle: RefCell<Option<JoinHandle<DbConn<SecurityModel>>>>,
+    /// Whether the list shows the recycle bin instead of the rivers.
+    recycle_bin: RefCell<bool>,
 
     #[nwg_control(size: (900, 600), center: true, title: "水安全", flags: "MAIN_WINDOW | VISIBLE")]
     #[nwg_events(OnWindowClose: [Self::window_close], OnInit: [Self::init_data_view])]
@@ -966,10 +965,22 @@ pub struct SecurityApp {
     #[nwg_events(OnMenuOpen: [Self::export_menu_open])]
     export_menu: nwg::Menu,
 
+    #[nwg_control(text: "模板")]
+    #[nwg_events(OnMenuOpen: [Self::template_menu_open])]
+    template_menu: nwg::Menu,
+
     #[nwg_control(text: "新增")]
     #[nwg_events(OnMenuOpen: [Self::create_menu_open])]
     create_menu: nwg::Menu,
 
+    #[nwg_control(text: "回收站")]
+    #[nwg_events(OnMenuOpen: [Self::recycle_menu_open])]
+    recycle_menu: nwg::Menu,
+
+    #[nwg_control(text: "查重")]
+    #[nwg_events(OnMenuOpen: [Self::duplicates_menu_open])]
+    duplicates_menu: nwg::Menu,
+
     #[nwg_control(popup: true)]
     right_click_menu: nwg::Menu,
 
@@ -985,13 +996,42 @@ pub struct SecurityApp {
     #[nwg_events(OnMenuItemSelected: [Self::delete_menu_selected])]
     delete_menu: nwg::MenuItem,
 
-    #[nwg_layout(parent: window)]
+    #[nwg_control(text: "历史记录", parent: right_click_menu)]
+    #[nwg_events(OnMenuItemSelected: [Self::history_menu_selected])]
+    history_menu: nwg::MenuItem,
+
+    #[nwg_control(text: "修改记录", parent: right_click_menu)]
+    #[nwg_events(OnMenuItemSelected: [Self::audit_menu_selected])]
+    audit_menu: nwg::MenuItem,
+
+    #[nwg_control(text: "恢复修改前版本", parent: right_click_menu)]
+    #[nwg_events(OnMenuItemSelected: [Self::restore_menu_selected])]
+    restore_menu: nwg::MenuItem,
+
+    #[nwg_control(text: "从回收站恢复", parent: right_click_menu, disabled: true)]
+    #[nwg_events(OnMenuItemSelected: [Self::undelete_menu_selected])]
+    undelete_menu: nwg::MenuItem,
+
+    #[nwg_control(text: "永久删除", parent: right_click_menu, disabled: true)]
+    #[nwg_events(OnMenuItemSelected: [Self::purge_menu_selected])]
+    purge_menu: nwg::MenuItem,
+
+    #[nwg_layout(parent: window, max_column: Some(12), max_row: Some(16))]
     layout: nwg::GridLayout,
 
+    #[nwg_control(text: "搜索", h_align: nwg::HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, col: 0, row: 0)]
+    search_label: nwg::Label,
+
+    #[nwg_control(placeholder_text: Some("河道名称、辖区、起点、终点或清淤判断，可输入拼音或首字母，如qhh"))]
+    #[nwg_layout_item(layout: layout, col: 1, col_span: 11, row: 0)]
+    #[nwg_events(OnTextInput: [Self::reload_menu_selected])]
+    search_input: nwg::TextInput,
+
     #[nwg_control(size: (850, 550), list_style: nwg::ListViewStyle::Detailed, focus: true,
         ex_flags: nwg::ListViewExFlags::GRID | nwg::ListViewExFlags::FULL_ROW_SELECT,
     )]
-    #[nwg_layout_item(layout: layout, col: 0, col_span: 1, row: 0, row_span: 1)]
+    #[nwg_layout_item(layout: layout, col: 0, col_span: 12, row: 1, row_span: 15)]
     #[nwg_events(OnListViewRightClick: [Self::right_click_menu_popup], OnListViewDoubleClick: [Self::update_menu_selected])]
     data_view: nwg::ListView,
 }
@@ -1001,7 +1041,9 @@ impl SecurityApp {
         thread::spawn(move || {
             let app = Self::build_ui(Default::default()).expect("Build SecurityApp UI failed.");
 
-            *app.db_conn.borrow_mut() = Some(DbConn::new());
+            let conn = DbConn::new();
+            let _ = audit::set_context(&conn.instance, &audit::system_user(), "界面");
+            *app.db_conn.borrow_mut() = Some(conn);
 
             nwg::dispatch_thread_events();
         })
@@ -1024,44 +1066,35 @@ impl SecurityApp {
     }
 
     fn load_data_view(&self) {
-        let mut conn = self.db_conn.take().unwrap();
-        if let Ok(models) = conn.select() {
+        let conn = self.db_conn.take().unwrap();
+        if *self.recycle_bin.borrow() {
+            if let Ok(rivers) = recycle::deleted_rivers(&conn.instance) {
+                let fields = SecurityModel::fields();
+                for river in rivers {
+                    self.data_view.insert_items_row(
+                        None,
+                        &fields
+                            .iter()
+                            .map(|field| (field.display)(&river.model))
+                            .collect::<Vec<String>>(),
+                    );
+                }
+            }
+            *self.db_conn.borrow_mut() = Some(conn);
+            return;
+        }
+        let mut options = ExportOptions::default();
+        options.set_keyword(&self.search_input.text());
+        if let Ok(models) = options.rivers(&conn) {
+            let fields = SecurityModel::fields();
             for model in models {
                 let data_view = &self.data_view;
                 data_view.insert_items_row(
                     None,
-                    &[
-                        model.id.to_string(),
-                        String::from(match model.level {
-                            1 => "第一级",
-                            2 => "第二级",
-                            3 => "第三级",
-                            4 => "第四级",
-                            5 => "第五级",
-                            _ => "第一级",
-                        }),
-                        model.name,
-                        model.area,
-                        model.start,
-                        model.end,
-                        model.river_width.to_string(),
-                        model.elevation.to_string(),
-                        model.ratio.to_string(),
-                        model.line.to_string(),
-                        String::from(if model.allow == 0.8 {
-                            "是"
-                        } else if model.allow == 0.4 {
-                            "否"
-                        } else {
-                            "自定义"
-                        }),
-                        model.safe.to_string(),
-                        model.depth.to_string(),
-                        model.channel_width.to_string(),
-                        model.threshold.to_string(),
-                        model.dredging,
-                        format!("{}", model.time.format("%Y-%m-%d %H:%M:%S")),
-                    ],
+                    &fields
+                        .iter()
+                        .map(|field| (field.display)(&model))
+                        .collect::<Vec<String>>(),
                 );
             }
         }
@@ -1083,305 +1116,217 @@ impl SecurityApp {
         }
     }
 
-    // TODO 分文件类型导入
-    // TODO 自动识别工作表
     fn import_menu_open(&self) {
         let mut import_file_dialog = nwg::FileDialog::default();
 
         if let Ok(_) = nwg::FileDialog::builder()
             .title("请选择导入文件")
             .action(nwg::FileDialogAction::Open)
-            .filters("Excel文件(*.xls;*.xlsx;*.xlsm;*.xlsb;*.xla;*.xlam)")
+            .filters(
+                "全部支持的文件(*.xlsx;*.xlsm;*.xlsb;*.xls;*.ods;*.csv;*.json;*.ndjson;*.jsonl)|电子表格(*.xlsx;*.xlsm;*.xlsb;*.xls;*.ods)|CSV文件(*.csv)|JSON文件(*.json;*.ndjson;*.jsonl)",
+            )
             .build(&mut import_file_dialog)
         {
-            let mut models = vec![];
             if import_file_dialog.run(Some(&self.window)) {
                 if let Ok(import_file) = import_file_dialog.get_selected_item() {
-                    if let Ok(mut workbook) =
-                        calamine::open_workbook::<Xlsx<_>, OsString>(import_file)
-                    {
-                        if let Some(Ok(range)) = workbook.worksheet_range("Sheet1") {
-                            let mut current_row = 0usize;
-                            let mut columns = vec![];
-                            for row in range.rows() {
-                                if current_row == 0 {
-                                    for cell in row {
-                                        let column = match cell.get_string() {
-                                            Some("编号") => "id",
-                                            Some("河道防洪排涝等级") => "level",
-                                            Some("河道名称") => "name",
-                                            Some("河道所属辖区") => "area",
-                                            Some("河道起点") => "start",
-                                            Some("河道终点") => "end",
-                                            Some("河道宽度(m)") => "river_width",
-                                            Some("边坡比") => "ratio",
-                                            Some("设计河底高程(m)") => "elevation",
-                                            Some("设计洪水水位(m)") => "line",
-                                            Some("是否允许浪爬高") => "allow",
-                                            Some("安全超高(m)") => "safe",
-                                            Some("淤积深度(m)") => "depth",
-                                            Some("河槽宽度(m)") => "channel_width",
-                                            Some("淤积阈值(m)") => "threshold",
-                                            Some("清淤判断") => "dredging",
-                                            Some("录入时间") => "time",
-                                            _ => "",
-                                        };
-                                        columns.push(column);
-                                    }
-                                } else {
-                                    let mut current_cell = 0usize;
-                                    let mut model = SecurityModel::default();
-                                    for cell in row {
-                                        if current_cell >= columns.len() {
-                                            continue;
-                                        }
-                                        match columns[current_cell] {
-                                            "id" => {
-                                                if let Some(id) = cell.get_int() {
-                                                    model.id = id as u32;
-                                                }
-                                            }
-                                            "level" => {
-                                                if let Some(level) = cell.get_int() {
-                                                    model.level = level as u32;
-                                                }
-                                            }
-                                            "name" => {
-                                                if let Some(name) = cell.get_string() {
-                                                    model.name = String::from(name);
-                                                }
-                                            }
-                                            "area" => {
-                                                if let Some(area) = cell.get_string() {
-                                                    model.area = String::from(area);
-                                                }
-                                            }
-                                            "start" => {
-                                                if let Some(start) = cell.get_string() {
-                                                    model.start = String::from(start);
-                                                }
-                                            }
-                                            "end" => {
-                                                if let Some(end) = cell.get_string() {
-                                                    model.end = String::from(end);
-                                                }
-                                            }
-                                            "river_width" => {
-                                                if let Some(river_width) = cell.get_float() {
-                                                    model.river_width = river_width as f32;
-                                                }
-                                            }
-                                            "ratio" => {
-                                                if let Some(ratio) = cell.get_float() {
-                                                    model.ratio = ratio as f32;
-                                                }
-                                            }
-                                            "elevation" => {
-                                                if let Some(elevation) = cell.get_float() {
-                                                    model.elevation = elevation as f32;
-                                                }
-                                            }
-                                            "line" => {
-                                                if let Some(line) = cell.get_float() {
-                                                    model.line = line as f32;
-                                                }
-                                            }
-                                            "allow" => {
-                                                if let Some(allow) = cell.get_float() {
-                                                    model.allow = allow as f32;
-                                                }
-                                            }
-                                            "safe" => {
-                                                if let Some(safe) = cell.get_float() {
-                                                    model.safe = safe as f32;
-                                                }
-                                            }
-                                            "depth" => {
-                                                if let Some(depth) = cell.get_float() {
-                                                    model.depth = depth as f32;
-                                                }
-                                            }
-                                            "channel_width" => {
-                                                if let Some(channel_width) = cell.get_float() {
-                                                    model.channel_width = channel_width as f32;
-                                                }
-                                            }
-                                            "threshold" => {
-                                                if let Some(threshold) = cell.get_float() {
-                                                    model.threshold = threshold as f32;
-                                                }
-                                            }
-                                            "dredging" => {
-                                                if let Some(dredging) = cell.get_string() {
-                                                    model.dredging = String::from(dredging);
-                                                }
-                                            }
-                                            "time" => {
-                                                if let Some(time) = cell.get_string() {
-                                                    model.time = match Local.datetime_from_str(
-                                                        time,
-                                                        "%Y-%m-%d %H:%M:%S",
-                                                    ) {
-                                                        Ok(time) => time,
-                                                        _ => Local::now(),
-                                                    };
-                                                }
-                                            }
-                                            _ => {}
-                                        }
-                                        current_cell += 1;
-                                    }
-                                    models.push(model);
-                                }
-                                current_row += 1;
-                            }
-                        }
-                    }
-                    let mut conn = self.db_conn.take().unwrap();
-                    let (mut insert_num, mut update_num, mut failed_num) = (0u32, 0u32, 0u32);
-                    for mut model in models {
-                        if let Ok(id) = conn.instance.query_row(
-                            "SELECT id FROM water_security WHERE name=?1 and area=?2",
-                            [model.name.as_str(), model.area.as_str()],
-                            |row| row.get(0),
-                        ) {
-                            model.id = id;
-                        } else {
-                            if model.id > 0 {
-                                match conn.instance.query_row(
-                                    "SELECT id FROM water_security WHERE id=?1",
-                                    [model.id],
-                                    |row| -> Result<u32> { row.get(0) },
-                                ) {
-                                    Err(_) => model.id = 0,
-                                    _ => {}
-                                }
-                            }
+                    let source = format!(
+                        "{} {}",
+                        import::IMPORT_SOURCE,
+                        import_file.to_string_lossy()
+                    );
+                    let result = import::read_with_profile::<SecurityModel, _>(
+                        &self.db_conn.borrow().as_ref().unwrap().instance,
+                        import_file,
+                        None,
+                        None,
+                    );
+                    let (table, profile) = match result {
+                        Ok(result) => result,
+                        Err(error) => {
+                            nwg::simple_message("导入失败", error.as_str());
+                            return;
                         }
-                        let id = model.id;
-                        conn.set(model);
-                        if let Ok(num) = if id > 0 { conn.update() } else { conn.insert() } {
-                            if num == 1 {
-                                if id > 0 {
-                                    update_num += 1;
-                                } else {
-                                    insert_num += 1;
-                                }
-                            } else {
-                                failed_num += 1;
-                            }
-                        } else {
-                            failed_num += 1;
+                    };
+                    let mut preview = import::preview::<SecurityModel>(&table, profile.as_ref());
+                    import::recompute_derived(&mut preview, true);
+                    import::check_duplicates(&mut preview);
+
+                    let mut content = format!(
+                        "{}\r\n{}{}",
+                        table.describe(),
+                        profile
+                            .as_ref()
+                            .map(|profile| format!("映射方案：{}\r\n", profile.name))
+                            .unwrap_or_default(),
+                        preview.summary()
+                    );
+                    let lines = preview
+                        .all_issues()
+                        .take(20)
+                        .map(|issue| issue.to_line())
+                        .collect::<Vec<String>>();
+                    if !lines.is_empty() {
+                        content = format!("{}\r\n\r\n{}", content, lines.join("\r\n"));
+                        let more = preview.all_issues().count() - lines.len();
+                        if more > 0 {
+                            content = format!("{}\r\n……另有{}条", content, more);
                         }
                     }
+                    content = format!("{}\r\n\r\n确定导入可导入的数据？", content);
+
+                    if preview.rejected_rows().next().is_some()
+                        && nwg::modal_message(
+                            &self.window,
+                            &nwg::MessageParams {
+                                title: "导入预览",
+                                content: format!(
+                                    "{}\r\n\r\n是否先将无法导入的行导出为错误工作簿？",
+                                    preview.summary()
+                                )
+                                .as_str(),
+                                buttons: nwg::MessageButtons::YesNo,
+                                icons: nwg::MessageIcons::Warning,
+                            },
+                        ) == nwg::MessageChoice::Yes
+                    {
+                        self.save_rejects(&preview);
+                    }
 
-                    *self.db_conn.borrow_mut() = Some(conn);
+                    if nwg::modal_message(
+                        &self.window,
+                        &nwg::MessageParams {
+                            title: "导入预览",
+                            content: content.as_str(),
+                            buttons: nwg::MessageButtons::OkCancel,
+                            icons: nwg::MessageIcons::Question,
+                        },
+                    ) == nwg::MessageChoice::Ok
+                    {
+                        let policy = match self.choose_policy(&preview) {
+                            Some(policy) => policy,
+                            None => return,
+                        };
+                        let mut conn = self.db_conn.take().unwrap();
+                        let _ = audit::set_source(&conn.instance, &source);
+                        let summary = import::commit(&mut conn, preview.valid_rows(), policy);
+                        *self.db_conn.borrow_mut() = Some(conn);
 
-                    nwg::simple_message(
-                        "导入完成",
-                        format!(
-                            "导入完成，新增{}条，更新{}条，失败{}条",
-                            insert_num, update_num, failed_num
-                        )
-                        .as_str(),
-                    );
+                        nwg::simple_message("导入完成", summary.to_message().as_str());
 
-                    self.reload_menu_selected();
+                        self.reload_menu_selected();
+                    }
                 }
             }
         }
     }
 
-    fn export_menu_open(&self) {
-        let mut export_file_dialog = nwg::FileDialog::default();
+    /// Shows what the import would change in rivers already stored and asks
+    /// how to treat them. `None` cancels the import.
+    fn choose_policy(&self, preview: &ImportPreview<SecurityModel>) -> Option<ConflictPolicy> {
+        let mut existing = 0;
+        let mut lines = Vec::new();
+        if let Some(conn) = self.db_conn.borrow().as_ref() {
+            for row in preview.valid_rows() {
+                let resolution = import::resolve(conn, row, ConflictPolicy::Overwrite);
+                if resolution.model.id == 0 {
+                    continue;
+                }
+                existing += 1;
+                for change in resolution.changes.iter() {
+                    lines.push(format!(
+                        "第{}行 {}【{}】{} → {}",
+                        resolution.row,
+                        resolution.model.name,
+                        change.header,
+                        change.old,
+                        change.new
+                    ));
+                }
+            }
+        }
+        if existing == 0 {
+            return Some(ConflictPolicy::Overwrite);
+        }
 
-        if let Ok(_) = nwg::FileDialog::builder()
-            .title("请选择导出位置")
+        let mut content = format!("有{}行与已有河道相同", existing);
+        if !lines.is_empty() {
+            content = format!(
+                "{}，将修改以下字段：\r\n{}",
+                content,
+                lines[..lines.len().min(20)].join("\r\n")
+            );
+            if lines.len() > 20 {
+                content = format!("{}\r\n……另有{}处", content, lines.len() - 20);
+            }
+        }
+        content = format!(
+            "{}\r\n\r\n是：{}\r\n否：{}\r\n取消：放弃导入",
+            content,
+            ConflictPolicy::NewerWins.name(),
+            ConflictPolicy::FillEmpty.name()
+        );
+        match nwg::modal_message(
+            &self.window,
+            &nwg::MessageParams {
+                title: "已有记录",
+                content: content.as_str(),
+                buttons: nwg::MessageButtons::YesNoCancel,
+                icons: nwg::MessageIcons::Question,
+            },
+        ) {
+            nwg::MessageChoice::Yes => Some(ConflictPolicy::NewerWins),
+            nwg::MessageChoice::No => Some(ConflictPolicy::FillEmpty),
+            _ => None,
+        }
+    }
+
+    fn save_rejects(&self, preview: &ImportPreview<SecurityModel>) {
+        let mut error_file_dialog = nwg::FileDialog::default();
+
+        if nwg::FileDialog::builder()
+            .title("请选择错误工作簿保存位置")
             .action(nwg::FileDialogAction::Save)
-            .filters("Excel文件(*.xls;*.xlsx;*.xlsm;*.xlsb;*.xla;*.xlam)")
-            .build(&mut export_file_dialog)
+            .filters("Excel文件(*.xlsx)")
+            .build(&mut error_file_dialog)
+            .is_ok()
+            && error_file_dialog.run(Some(&self.window))
         {
-            if export_file_dialog.run(Some(&self.window)) {
-                if let Ok(export_file) = export_file_dialog.get_selected_item() {
-                    let mut workbook = Workbook::create(export_file.to_str().unwrap());
-                    let mut sheet = workbook.create_sheet("Sheet1");
-
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-                    sheet.add_column(Column { width: 30.0 });
-
-                    if let Ok(_) = workbook.write_sheet(&mut sheet, |sheet_writer| {
-                        sheet_writer.append_row(row![
-                            "编号",
-                            "河道防洪排涝等级",
-                            "河道名称",
-                            "河道所属辖区",
-                            "河道起点",
-                            "河道终点",
-                            "河道宽度(m)",
-                            "边坡比",
-                            "设计河底高程(m)",
-                            "设计洪水水位(m)",
-                            "是否允许浪爬高",
-                            "安全超高(m)",
-                            "淤积深度(m)",
-                            "河槽宽度(m)",
-                            "淤积阈值(m)",
-                            "清淤判断",
-                            "录入时间"
-                        ])?;
-                        let mut conn = self.db_conn.take().unwrap();
-                        if let Ok(security_models) = conn.select() {
-                            let mut row_num = 0usize;
-                            for model in security_models {
-                                sheet_writer.append_row(row![
-                                    model.id.to_string(),
-                                    model.level.to_string(),
-                                    model.name,
-                                    model.area,
-                                    model.start,
-                                    model.end,
-                                    model.river_width.to_string(),
-                                    model.elevation.to_string(),
-                                    model.ratio.to_string(),
-                                    model.line.to_string(),
-                                    model.allow.to_string(),
-                                    model.safe.to_string(),
-                                    model.depth.to_string(),
-                                    model.channel_width.to_string(),
-                                    model.threshold.to_string(),
-                                    model.dredging,
-                                    format!("{}", model.time.format("%Y-%m-%d %H:%M:%S"))
-                                ])?;
-                                row_num += 1;
-                            }
-                            nwg::simple_message(
-                                "导出",
-                                format!("导出完成，共{}条数据", row_num).as_str(),
-                            );
-                        }
+            if let Ok(error_file) = error_file_dialog.get_selected_item() {
+                match preview.write_rejects(error_file) {
+                    Ok(num) => nwg::simple_message(
+                        "导出",
+                        format!("已导出{}行无法导入的数据", num).as_str(),
+                    ),
+                    Err(error) => nwg::simple_message("错误", error.as_str()),
+                };
+            }
+        }
+    }
 
-                        *self.db_conn.borrow_mut() = Some(conn);
+    fn export_menu_open(&self) {
+        let conn = self.db_conn.take();
+        *self.security_window_handle.borrow_mut() = Some(ExportFormWindow::window_open(
+            conn,
+            self.security_form_notice.sender(),
+        ));
+    }
 
-                        Ok(())
-                    }) {}
+    fn template_menu_open(&self) {
+        let mut template_file_dialog = nwg::FileDialog::default();
 
-                    if let Ok(_) = workbook.close() {}
-                }
+        if nwg::FileDialog::builder()
+            .title("请选择模板保存位置")
+            .action(nwg::FileDialogAction::Save)
+            .filters("Excel文件(*.xlsx)")
+            .build(&mut template_file_dialog)
+            .is_ok()
+            && template_file_dialog.run(Some(&self.window))
+        {
+            if let Ok(template_file) = template_file_dialog.get_selected_item() {
+                match template::write_template(template_file) {
+                    Ok(()) => nwg::simple_message("模板", "模板已生成，填写后可直接导入"),
+                    Err(error) => nwg::simple_message("错误", error.as_str()),
+                };
             }
         }
     }
@@ -1404,7 +1349,62 @@ impl SecurityApp {
         self.load_data_view();
     }
 
+    fn recycle_menu_open(&self) {
+        let recycle_bin = !*self.recycle_bin.borrow();
+        *self.recycle_bin.borrow_mut() = recycle_bin;
+        self.window.set_text(if recycle_bin {
+            "水安全 - 回收站"
+        } else {
+            "水安全"
+        });
+        for item in [&self.update_menu, &self.delete_menu, &self.restore_menu].iter() {
+            item.set_enabled(!recycle_bin);
+        }
+        self.undelete_menu.set_enabled(recycle_bin);
+        self.purge_menu.set_enabled(recycle_bin);
+        self.search_input.set_enabled(!recycle_bin);
+        self.reload_menu_selected();
+    }
+
+    fn duplicates_menu_open(&self) {
+        let conn = self.db_conn.take().unwrap();
+        let result = duplicate::groups(&conn.instance)
+            .and_then(|groups| Ok((groups, duplicate::is_enforced(&conn.instance)?)));
+        *self.db_conn.borrow_mut() = Some(conn);
+        match result {
+            Ok((groups, _)) if groups.is_empty() => {
+                nwg::simple_message("查重", "没有疑似重复的河道")
+            }
+            Ok((groups, enforced)) => {
+                let mut lines = groups
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, group)| {
+                        std::iter::once(format!("第{}组：{}", index + 1, group.similarity.reason()))
+                            .chain(group.rivers.iter().map(|model| {
+                                format!(
+                                    "    编号{}  {}  {}  {}～{}",
+                                    model.id, model.name, model.area, model.start, model.end
+                                )
+                            }))
+                    })
+                    .collect::<Vec<String>>();
+                if !enforced {
+                    lines.push(String::new());
+                    lines.push(String::from(
+                        "完全相同的河道合并或删除前不禁止重复录入，可用wrs-cli duplicates merge合并",
+                    ));
+                }
+                nwg::simple_message("疑似重复的河道", lines.join("\r\n").as_str())
+            }
+            Err(error) => nwg::simple_message("错误", error.to_string().as_str()),
+        };
+    }
+
     fn update_menu_selected(&self) {
+        if *self.recycle_bin.borrow() {
+            return;
+        }
         if let Some(index) = self.data_view.selected_item() {
             if let Some(item) = self.data_view.item(index, 0, size_of::<u32>()) {
                 let mut conn = self.db_conn.take().unwrap();
@@ -1427,7 +1427,7 @@ impl SecurityApp {
                         &self.window,
                         &nwg::MessageParams {
                             title: "确认",
-                            content: "删除后将无法恢复，确定删除？",
+                            content: "删除的河道将移入回收站，可随时恢复。确定删除？",
                             buttons: nwg::MessageButtons::OkCancel,
                             icons: nwg::MessageIcons::Question,
                         },
@@ -1459,4 +1459,171 @@ impl SecurityApp {
             }
         }
     }
+
+    fn history_menu_selected(&self) {
+        if let Some(index) = self.data_view.selected_item() {
+            if let Some(item) = self.data_view.item(index, 0, size_of::<u32>()) {
+                if let Ok(river_id) = item.text.parse::<u32>() {
+                    let conn = DbConn::<InspectionModel>::new();
+                    match conn.find(
+                        "river_id=:river_id",
+                        ("time", "ASC"),
+                        (0, 0),
+                        &[(":river_id", &river_id)],
+                    ) {
+                        Ok(inspections) => {
+                            let lines = inspections
+                                .iter()
+                                .map(|inspection| {
+                                    format!(
+                                        "{}  淤积深度{}m  淤积阈值{}m  {}",
+                                        inspection.time.format("%Y-%m-%d"),
+                                        inspection.depth,
+                                        inspection.threshold,
+                                        inspection.dredging
+                                    )
+                                })
+                                .collect::<Vec<String>>();
+                            nwg::simple_message("历史记录", lines.join("\r\n").as_str())
+                        }
+                        Err(error) => nwg::simple_message("错误", error.to_string().as_str()),
+                    };
+                }
+            }
+        }
+    }
+
+    fn selected_river_id(&self) -> Option<u32> {
+        self.data_view
+            .selected_item()
+            .and_then(|index| self.data_view.item(index, 0, size_of::<u32>()))
+            .and_then(|item| item.text.parse().ok())
+    }
+
+    fn audit_menu_selected(&self) {
+        if let Some(river_id) = self.selected_river_id() {
+            let conn = self.db_conn.take().unwrap();
+            let result = audit::entries(&conn.instance, Some(river_id), None, None);
+            *self.db_conn.borrow_mut() = Some(conn);
+            match result {
+                Ok(entries) if entries.is_empty() => {
+                    nwg::simple_message("修改记录", "没有修改记录")
+                }
+                Ok(entries) => {
+                    let mut lines = Vec::new();
+                    let skipped = entries.len().saturating_sub(20);
+                    if skipped > 0 {
+                        lines.push(format!("……之前另有{}条", skipped));
+                    }
+                    for entry in entries.iter().skip(skipped) {
+                        lines.push(format!(
+                            "{}  {}  {}  {}",
+                            entry.time.format(DATE_TIME_FORMAT),
+                            entry.user,
+                            entry.source,
+                            entry.action.label()
+                        ));
+                        if entry.action == AuditAction::Update {
+                            for (header, old, new) in entry.changes() {
+                                lines.push(format!("    {}：{} → {}", header, old, new));
+                            }
+                        }
+                    }
+                    nwg::simple_message("修改记录", lines.join("\r\n").as_str())
+                }
+                Err(error) => nwg::simple_message("错误", error.to_string().as_str()),
+            };
+        }
+    }
+
+    /// Asks to restore the river of `entry` as it was before that change.
+    fn confirm_restore(&self, entry: &AuditEntry) {
+        let content = format!(
+            "将{}恢复为{}{}之前的版本？",
+            entry.name(),
+            entry.time.format(DATE_TIME_FORMAT),
+            entry.action.label()
+        );
+        if nwg::modal_message(
+            &self.window,
+            &nwg::MessageParams {
+                title: "确认",
+                content: content.as_str(),
+                buttons: nwg::MessageButtons::OkCancel,
+                icons: nwg::MessageIcons::Question,
+            },
+        ) != nwg::MessageChoice::Ok
+        {
+            return;
+        }
+        let mut conn = self.db_conn.take().unwrap();
+        match audit::restore(&mut conn, entry.id) {
+            Ok(model) => nwg::simple_message("提示", format!("已恢复{}", model.name).as_str()),
+            Err(error) => nwg::simple_message("错误", error.as_str()),
+        };
+        *self.db_conn.borrow_mut() = Some(conn);
+        self.reload_menu_selected();
+    }
+
+    fn restore_menu_selected(&self) {
+        if let Some(river_id) = self.selected_river_id() {
+            let conn = self.db_conn.take().unwrap();
+            let result = audit::entries(&conn.instance, Some(river_id), None, None);
+            *self.db_conn.borrow_mut() = Some(conn);
+            match result {
+                Ok(entries) => match entries.iter().rev().find(|entry| entry.old.is_some()) {
+                    Some(entry) => self.confirm_restore(entry),
+                    None => {
+                        nwg::simple_message("提示", "该河道没有可恢复的版本");
+                    }
+                },
+                Err(error) => {
+                    nwg::simple_message("错误", error.to_string().as_str());
+                }
+            }
+        }
+    }
+
+    fn undelete_menu_selected(&self) {
+        if let Some(river_id) = self.selected_river_id() {
+            let conn = self.db_conn.take().unwrap();
+            let result = recycle::restore(&conn, river_id);
+            *self.db_conn.borrow_mut() = Some(conn);
+            match result {
+                Ok(model) => nwg::simple_message("提示", &format!("已恢复{}", model.name)),
+                Err(error) => nwg::simple_message("错误", &error),
+            };
+            self.reload_menu_selected();
+        }
+    }
+
+    fn purge_menu_selected(&self) {
+        if let Some(river_id) = self.selected_river_id() {
+            if nwg::modal_message(
+                &self.window,
+                &nwg::MessageParams {
+                    title: "确认",
+                    content: "永久删除的河道及其历史记录无法恢复。确定永久删除？",
+                    buttons: nwg::MessageButtons::OkCancel,
+                    icons: nwg::MessageIcons::Warning,
+                },
+            ) == nwg::MessageChoice::Ok
+            {
+                let conn = self.db_conn.take().unwrap();
+                match recycle::purge(&conn.instance, river_id) {
+                    Ok(num) => nwg::simple_message(
+                        "提示",
+                        if num == 1 {
+                            "永久删除成功"
+                        } else {
+                            "永久删除失败"
+                        },
+                    ),
+                    Err(error) => nwg::simple_message("错误", error.to_string().as_str()),
+                };
+                *self.db_conn.borrow_mut() = Some(conn);
+                self.reload_menu_selected();
+            }
+        }
+    }
 }
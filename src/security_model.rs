@@ -1,7 +1,95 @@
+use std::str::FromStr;
+
 use chrono::{DateTime, Local};
-use rusqlite::{named_params, Result, Row, Statement};
+use rusqlite::{
+    ffi, named_params, params, Connection, Error, OptionalExtension, Result, Row, Statement,
+};
+use serde_json::Value;
+
+use crate::{
+    audit,
+    chainage::{check_reach, Chainage},
+    db::{check_fields, parse_date_time, Bound, DbOpt, Field, FieldType, Model, DATE_TIME_FORMAT},
+    duplicate, export,
+    inspection_model::InspectionModel,
+    search,
+};
+
+pub const LEVEL_TEXTS: [&str; 5] = ["第一级", "第二级", "第三级", "第四级", "第五级"];
+
+/// Message for a river that would share its name, jurisdiction and ends
+/// with river `id`.
+pub fn conflict_message(id: u32) -> String {
+    format!("已有名称、辖区、起点和终点都相同的河道，编号为{}", id)
+}
+
+/// Error of a write refused because it would duplicate river `id`.
+fn conflict_error(id: u32) -> Error {
+    Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_CONSTRAINT_UNIQUE),
+        Some(conflict_message(id)),
+    )
+}
+
+/// Text of a level outside 1-5, such as the 0 of a river stored without one.
+pub const UNKNOWN_LEVEL: &str = "未填写";
+
+pub fn level_text(level: u32) -> &'static str {
+    match level {
+        1..=5 => LEVEL_TEXTS[level as usize - 1],
+        _ => UNKNOWN_LEVEL,
+    }
+}
+
+pub fn parse_level(text: &str) -> std::result::Result<u32, String> {
+    let text = text.trim();
+    if let Some(index) = LEVEL_TEXTS.iter().position(|level| *level == text) {
+        return Ok(index as u32 + 1);
+    }
+    match text.parse::<f64>() {
+        Ok(level) if level.fract() == 0.0 && (1.0..=5.0).contains(&level) => Ok(level as u32),
+        _ => Err(format!("无法识别的防洪排涝等级：{}", text)),
+    }
+}
+
+pub fn allow_text(allow: f32) -> &'static str {
+    if allow == 0.8 {
+        "是"
+    } else if allow == 0.4 {
+        "否"
+    } else {
+        "自定义"
+    }
+}
+
+/// The jurisdiction decides which thresholds apply, so it has to name its
+/// administrative level.
+pub fn check_area(area: &str) -> std::result::Result<(), String> {
+    if ["一般", "市", "县", "区", "乡"]
+        .iter()
+        .any(|keyword| area.contains(keyword))
+    {
+        Ok(())
+    } else {
+        Err(String::from(
+            "河道所属辖区至少要含有【市】|【县】|【区】|【乡】|【一般】关键字中的一个",
+        ))
+    }
+}
+
+fn parse_allow(text: &str) -> std::result::Result<f32, String> {
+    match text.trim() {
+        "是" => Ok(0.8),
+        "否" => Ok(0.4),
+        text => parse_number(text),
+    }
+}
 
-use crate::db::{DbOpt, Model, ModelNameType};
+fn parse_number<N: FromStr>(text: &str) -> std::result::Result<N, String> {
+    text.trim()
+        .parse::<N>()
+        .map_err(|_| format!("{}不是有效的数字", text))
+}
 
 #[derive(Clone)]
 pub struct SecurityModel {
@@ -51,44 +139,57 @@ impl Default for SecurityModel {
 impl Model for SecurityModel {
     fn get_sql(opt: DbOpt) -> String {
         match opt {
-            DbOpt::Create => r#"CREATE TABLE water_security
-                (
-                    id            INTEGER PRIMARY KEY AUTOINCREMENT,
-                    level         INTEGER,
-                    name          TEXT,
-                    area          TEXT,
-                    start         TEXT,
-                    end           TEXT,
-                    river_width   REAL,
-                    elevation     REAL,
-                    ratio         REAL,
-                    line          REAL,
-                    allow         REAL,
-                    safe          REAL,
-                    depth         REAL,
-                    channel_width REAL,
-                    threshold     REAL,
-                    dredging      TEXT,
-                    time          TEXT
-                )"#
+            DbOpt::Create => r#"CREATE VIEW water_security(
+                    id, level, name, area, start, end, river_width, elevation, ratio, line, allow,
+                    safe, depth, channel_width, threshold, dredging, time
+                ) AS
+                SELECT
+                    rivers.id, rivers.level, rivers.name, rivers.area, rivers.start, rivers.end,
+                    rivers.river_width, rivers.elevation, rivers.ratio, rivers.line, rivers.allow,
+                    rivers.safe, inspections.depth, rivers.channel_width, inspections.threshold,
+                    inspections.dredging, inspections.time
+                FROM rivers
+                JOIN inspections ON inspections.id = (
+                    SELECT id FROM inspections
+                    WHERE river_id = rivers.id
+                    ORDER BY time DESC, id DESC
+                    LIMIT 1
+                )
+                WHERE rivers.deleted IS NULL"#
             .to_string(),
-            DbOpt::Insert => r#"INSERT INTO water_security(
-                    level, name, area, start, end, river_width, elevation, ratio,
-                    line, allow, safe, depth, channel_width, threshold, dredging, time
+            DbOpt::Insert => r#"INSERT INTO rivers(
+                    level, name, area, start, end, start_chainage, end_chainage, river_width,
+                    elevation, ratio, line, allow, safe, channel_width
                 )
                 VALUES(
-                    :level, :name, :area, :start, :end, :river_width, :elevation, :ratio,
-                    :line, :allow, :safe, :depth, :channel_width, :threshold, :dredging, :time
+                    :level, :name, :area, :start, :end, :start_chainage, :end_chainage, :river_width,
+                    :elevation, :ratio, :line, :allow, :safe, :channel_width
                 )"#
             .to_string(),
-            DbOpt::Update => r#"UPDATE water_security SET
+            DbOpt::Upsert => r#"INSERT INTO rivers(
+                    level, name, area, start, end, start_chainage, end_chainage, river_width,
+                    elevation, ratio, line, allow, safe, channel_width
+                )
+                VALUES(
+                    :level, :name, :area, :start, :end, :start_chainage, :end_chainage, :river_width,
+                    :elevation, :ratio, :line, :allow, :safe, :channel_width
+                )
+                ON CONFLICT(name, area, start, end) WHERE deleted IS NULL DO UPDATE SET
+                    level=excluded.level, start_chainage=excluded.start_chainage,
+                    end_chainage=excluded.end_chainage, river_width=excluded.river_width,
+                    elevation=excluded.elevation, ratio=excluded.ratio, line=excluded.line,
+                    allow=excluded.allow, safe=excluded.safe, channel_width=excluded.channel_width"#
+                .to_string(),
+            DbOpt::Update => r#"UPDATE rivers SET
                     level=:level, name=:name, area=:area, start=:start, end=:end,
+                    start_chainage=:start_chainage, end_chainage=:end_chainage,
                     river_width=:river_width, elevation=:elevation, ratio=:ratio,
-                    line=:line, allow=:allow, safe=:safe, depth=:depth, channel_width=:channel_width,
-                    threshold=:threshold, dredging=:dredging, time=:time
+                    line=:line, allow=:allow, safe=:safe, channel_width=:channel_width
                 WHERE id=:id"#
                 .to_string(),
-            DbOpt::Delete => r#"DELETE FROM water_security WHERE id=?"#.to_string(),
+            DbOpt::Delete => {
+                r#"UPDATE rivers SET deleted=?2 WHERE id=?1 AND deleted IS NULL"#.to_string()
+            }
             DbOpt::Select => r#"SELECT
                 id, level, name, area, start, end, river_width, elevation, ratio,
                 line, allow, safe, depth, channel_width, threshold, dredging, time
@@ -97,47 +198,305 @@ impl Model for SecurityModel {
         }
     }
 
-    fn get_names(name_type: ModelNameType) -> Vec<String> {
-        match name_type {
-            ModelNameType::Column => vec![
-                String::from("id"),
-                String::from("level"),
-                String::from("name"),
-                String::from("area"),
-                String::from("start"),
-                String::from("end"),
-                String::from("river_width"),
-                String::from("elevation"),
-                String::from("ratio"),
-                String::from("line"),
-                String::from("allow"),
-                String::from("safe"),
-                String::from("depth"),
-                String::from("channel_width"),
-                String::from("threshold"),
-                String::from("dredging"),
-                String::from("time"),
-            ],
-            ModelNameType::Header => vec![
-                String::from("编号"),
-                String::from("河道防洪排涝等级"),
-                String::from("河道名称"),
-                String::from("河道所属辖区"),
-                String::from("河道起点"),
-                String::from("河道终点"),
-                String::from("河道宽度(m)"),
-                String::from("边坡比"),
-                String::from("设计河底高程(m)"),
-                String::from("设计洪水水位(m)"),
-                String::from("是否允许浪爬高"),
-                String::from("安全超高(m)"),
-                String::from("淤积深度(m)"),
-                String::from("河槽宽度(m)"),
-                String::from("淤积阈值(m)"),
-                String::from("清淤判断"),
-                String::from("录入时间"),
-            ],
-        }
+    fn fields() -> Vec<Field<Self>> {
+        vec![
+            Field {
+                column: "id",
+                name: "编号",
+                aliases: &["ID"],
+                field_type: FieldType::Integer,
+                unit: None,
+                required: false,
+                min: None,
+                max: None,
+                width: 10.0,
+                formatter: |model| model.id.to_string(),
+                display: |model| model.id.to_string(),
+                parser: |model, text| {
+                    model.id = parse_number::<f64>(text)? as u32;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "level",
+                name: "河道防洪排涝等级",
+                aliases: &["防洪排涝等级", "等级", "Flood Control Level"],
+                field_type: FieldType::Integer,
+                unit: None,
+                required: true,
+                min: Some(Bound::Inclusive(1.0)),
+                max: Some(Bound::Inclusive(5.0)),
+                width: 20.0,
+                formatter: |model| model.level.to_string(),
+                display: |model| String::from(level_text(model.level)),
+                parser: |model, text| {
+                    model.level = parse_level(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "name",
+                name: "河道名称",
+                aliases: &["名称", "河道", "河流名称", "River Name", "River"],
+                field_type: FieldType::Text,
+                unit: None,
+                required: true,
+                min: None,
+                max: None,
+                width: 30.0,
+                formatter: |model| model.name.clone(),
+                display: |model| model.name.clone(),
+                parser: |model, text| {
+                    model.name = String::from(text.trim());
+                    Ok(())
+                },
+            },
+            Field {
+                column: "area",
+                name: "河道所属辖区",
+                aliases: &["所属辖区", "辖区", "所在辖区", "行政区", "Area", "District"],
+                field_type: FieldType::Text,
+                unit: None,
+                required: true,
+                min: None,
+                max: None,
+                width: 30.0,
+                formatter: |model| model.area.clone(),
+                display: |model| model.area.clone(),
+                parser: |model, text| {
+                    model.area = String::from(text.trim());
+                    Ok(())
+                },
+            },
+            Field {
+                column: "start",
+                name: "河道起点",
+                aliases: &["起点", "起点桩号", "起始桩号", "Start"],
+                field_type: FieldType::Text,
+                unit: None,
+                required: true,
+                min: None,
+                max: None,
+                width: 30.0,
+                formatter: |model| model.start.clone(),
+                display: |model| model.start.clone(),
+                parser: |model, text| {
+                    model.start = Chainage::normalize(text);
+                    Ok(())
+                },
+            },
+            Field {
+                column: "end",
+                name: "河道终点",
+                aliases: &["终点", "终点桩号", "终止桩号", "End"],
+                field_type: FieldType::Text,
+                unit: None,
+                required: true,
+                min: None,
+                max: None,
+                width: 30.0,
+                formatter: |model| model.end.clone(),
+                display: |model| model.end.clone(),
+                parser: |model, text| {
+                    model.end = Chainage::normalize(text);
+                    Ok(())
+                },
+            },
+            Field {
+                column: "river_width",
+                name: "河道宽度",
+                aliases: &["河宽", "河道宽", "宽度", "River Width", "Width"],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 15.0,
+                formatter: |model| model.river_width.to_string(),
+                display: |model| model.river_width.to_string(),
+                parser: |model, text| {
+                    model.river_width = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "elevation",
+                name: "设计河底高程",
+                aliases: &["河底高程", "设计河底标高", "Bed Elevation", "Elevation"],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 20.0,
+                formatter: |model| model.elevation.to_string(),
+                display: |model| model.elevation.to_string(),
+                parser: |model, text| {
+                    model.elevation = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "ratio",
+                name: "边坡比",
+                aliases: &["坡比", "边坡系数", "Slope Ratio", "Slope"],
+                field_type: FieldType::Real,
+                unit: None,
+                required: false,
+                min: Some(Bound::Inclusive(0.0)),
+                max: None,
+                width: 10.0,
+                formatter: |model| model.ratio.to_string(),
+                display: |model| model.ratio.to_string(),
+                parser: |model, text| {
+                    model.ratio = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "line",
+                name: "设计洪水水位",
+                aliases: &["洪水水位", "设计水位", "Flood Level"],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 20.0,
+                formatter: |model| model.line.to_string(),
+                display: |model| model.line.to_string(),
+                parser: |model, text| {
+                    model.line = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "allow",
+                name: "是否允许浪爬高",
+                aliases: &["允许浪爬高", "Wave Runup"],
+                field_type: FieldType::Real,
+                unit: None,
+                required: false,
+                min: None,
+                max: None,
+                width: 20.0,
+                formatter: |model| model.allow.to_string(),
+                display: |model| String::from(allow_text(model.allow)),
+                parser: |model, text| {
+                    model.allow = parse_allow(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "safe",
+                name: "安全超高",
+                aliases: &["超高", "Freeboard"],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 15.0,
+                formatter: |model| model.safe.to_string(),
+                display: |model| model.safe.to_string(),
+                parser: |model, text| {
+                    model.safe = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "depth",
+                name: "淤积深度",
+                aliases: &[
+                    "淤积深",
+                    "淤深",
+                    "淤泥深度",
+                    "Silt Depth",
+                    "Siltation Depth",
+                ],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: true,
+                min: Some(Bound::Exclusive(0.0)),
+                max: None,
+                width: 15.0,
+                formatter: |model| model.depth.to_string(),
+                display: |model| model.depth.to_string(),
+                parser: |model, text| {
+                    model.depth = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "channel_width",
+                name: "河槽宽度",
+                aliases: &["河槽宽", "河底宽度", "底宽", "Channel Width"],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: false,
+                min: None,
+                max: None,
+                width: 15.0,
+                formatter: |model| model.channel_width.to_string(),
+                display: |model| model.channel_width.to_string(),
+                parser: |model, text| {
+                    model.channel_width = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "threshold",
+                name: "淤积阈值",
+                aliases: &["阈值", "Threshold"],
+                field_type: FieldType::Real,
+                unit: Some("m"),
+                required: false,
+                min: None,
+                max: None,
+                width: 15.0,
+                formatter: |model| model.threshold.to_string(),
+                display: |model| model.threshold.to_string(),
+                parser: |model, text| {
+                    model.threshold = parse_number(text)?;
+                    Ok(())
+                },
+            },
+            Field {
+                column: "dredging",
+                name: "清淤判断",
+                aliases: &["是否清淤", "Dredging"],
+                field_type: FieldType::Text,
+                unit: None,
+                required: false,
+                min: None,
+                max: None,
+                width: 30.0,
+                formatter: |model| model.dredging.clone(),
+                display: |model| model.dredging.clone(),
+                parser: |model, text| {
+                    model.dredging = String::from(text.trim());
+                    Ok(())
+                },
+            },
+            Field {
+                column: "time",
+                name: "录入时间",
+                aliases: &["时间", "巡查时间", "测量时间", "Time", "Date"],
+                field_type: FieldType::DateTime,
+                unit: None,
+                required: false,
+                min: None,
+                max: None,
+                width: 25.0,
+                formatter: |model| format!("{}", model.time.format(DATE_TIME_FORMAT)),
+                display: |model| format!("{}", model.time.format(DATE_TIME_FORMAT)),
+                parser: |model, text| {
+                    model.time = parse_date_time(text)
+                        .map_err(|_| format!("无法识别的录入时间：{}", text))?;
+                    Ok(())
+                },
+            },
+        ]
     }
 
     fn from_row(row: &Row) -> Result<Self> {
@@ -162,26 +521,37 @@ impl Model for SecurityModel {
         })
     }
 
+    fn validate(model: &Self) -> Vec<(&'static str, String)> {
+        let mut errors = check_fields(&Self::fields(), model);
+        if errors.iter().all(|(column, _)| *column != "area") {
+            if let Err(message) = check_area(&model.area) {
+                errors.push(("area", message));
+            }
+        }
+        if let Err(message) = check_reach(&model.start, &model.end) {
+            errors.push(("end", message));
+        }
+        errors
+    }
+
     fn execute(&self, stmt: &mut Statement, opt: DbOpt) -> Result<usize> {
         match opt {
             DbOpt::Create => unimplemented!(),
-            DbOpt::Insert => stmt.execute(named_params! {
+            DbOpt::Insert | DbOpt::Upsert => stmt.execute(named_params! {
                 ":level": self.level,
                 ":name": self.name,
                 ":area": self.area,
                 ":start": self.start,
                 ":end": self.end,
+                ":start_chainage": Chainage::metres_of(&self.start),
+                ":end_chainage": Chainage::metres_of(&self.end),
                 ":river_width": self.river_width,
                 ":ratio": self.ratio,
                 ":elevation": self.elevation,
                 ":line": self.line,
                 ":allow": self.allow,
                 ":safe": self.safe,
-                ":depth": self.depth,
                 ":channel_width": self.channel_width,
-                ":threshold": self.threshold,
-                ":dredging": self.dredging,
-                ":time": self.time,
             }),
             DbOpt::Update => stmt.execute(named_params! {
                 ":level": self.level,
@@ -189,21 +559,219 @@ impl Model for SecurityModel {
                 ":area": self.area,
                 ":start": self.start,
                 ":end": self.end,
+                ":start_chainage": Chainage::metres_of(&self.start),
+                ":end_chainage": Chainage::metres_of(&self.end),
                 ":river_width": self.river_width,
                 ":ratio": self.ratio,
                 ":elevation": self.elevation,
                 ":line": self.line,
                 ":allow": self.allow,
                 ":safe": self.safe,
-                ":depth": self.depth,
                 ":channel_width": self.channel_width,
-                ":threshold": self.threshold,
-                ":dredging": self.dredging,
-                ":time": self.time,
                 ":id": self.id,
             }),
-            DbOpt::Delete => stmt.execute([self.id]),
+            DbOpt::Delete => stmt.execute(params![self.id, Local::now()]),
             DbOpt::Select => unimplemented!(),
         }
     }
+
+    fn persist(&self, conn: &Connection, opt: DbOpt) -> Result<usize> {
+        let tx = conn.unchecked_transaction()?;
+        // River written to, when it is already stored.
+        let stored = match opt {
+            DbOpt::Insert => None,
+            DbOpt::Upsert => self.find_same(conn, 0)?,
+            _ => Some(self.id),
+        };
+        // Until rivers stored twice are merged there is no identity index, so
+        // new and renamed rivers are checked here and an upsert is an update
+        // or insert. Rivers already stored twice can still be edited.
+        let enforced = duplicate::is_enforced(conn)?;
+        if !enforced {
+            let except = match opt {
+                DbOpt::Insert => Some(0),
+                DbOpt::Update if self.is_renamed(conn)? => Some(self.id),
+                _ => None,
+            };
+            if let Some(except) = except {
+                if let Some(other) = self.find_same(conn, except)? {
+                    return Err(conflict_error(other));
+                }
+            }
+        }
+        let opt = match (opt, stored) {
+            (DbOpt::Upsert, Some(_)) if !enforced => DbOpt::Update,
+            (DbOpt::Upsert, None) if !enforced => DbOpt::Insert,
+            _ => opt,
+        };
+        let renumbered;
+        let model = match stored {
+            Some(id) if id != self.id => {
+                renumbered = SecurityModel { id, ..self.clone() };
+                &renumbered
+            }
+            _ => self,
+        };
+        let old = match stored {
+            Some(id) => audit::snapshot(conn, id)?,
+            None => None,
+        };
+        let num = model
+            .execute(&mut conn.prepare(Self::get_sql(opt).as_str())?, opt)
+            .map_err(|error| self.explain_conflict(conn, error))?;
+        let id = match (opt, stored) {
+            (DbOpt::Delete, _) => {
+                search::unindex(conn, self.id)?;
+                self.id
+            }
+            (DbOpt::Insert, _) | (DbOpt::Upsert, None) => {
+                let mut inspection = self.inspection();
+                inspection.river_id = conn.last_insert_rowid() as u32;
+                inspection.persist(conn, DbOpt::Insert)?;
+                search::index(conn, inspection.river_id)?;
+                inspection.river_id
+            }
+            (_, Some(id)) if num > 0 => {
+                self.record_inspection(conn, id)?;
+                search::index(conn, id)?;
+                id
+            }
+            _ => self.id,
+        };
+        audit::record(conn, id, old, audit::snapshot(conn, id)?)?;
+        tx.commit()?;
+        Ok(num)
+    }
+}
+
+impl SecurityModel {
+    pub fn inspection(&self) -> InspectionModel {
+        InspectionModel {
+            id: 0,
+            river_id: self.id,
+            time: self.time,
+            depth: self.depth,
+            threshold: self.threshold,
+            dredging: self.dredging.clone(),
+        }
+    }
+
+    /// The river keyed by column name, with values as exported to JSON.
+    pub fn to_json(&self) -> Value {
+        export::json_record(&SecurityModel::fields(), self)
+    }
+
+    /// Whether `other` has the name, jurisdiction and ends of this river, the
+    /// key no two stored rivers share.
+    pub fn is_same_river(&self, other: &SecurityModel) -> bool {
+        self.name == other.name
+            && self.area == other.area
+            && self.start == other.start
+            && self.end == other.end
+    }
+
+    /// Whether the stored river of this id has another name, jurisdiction or
+    /// ends than this one.
+    fn is_renamed(&self, conn: &Connection) -> Result<bool> {
+        conn.query_row(
+            "SELECT name IS ?1 AND area IS ?2 AND start IS ?3 AND end IS ?4 FROM rivers WHERE id=?5",
+            params![self.name, self.area, self.start, self.end, self.id],
+            |row| row.get::<_, bool>(0),
+        )
+        .optional()
+        .map(|same| same == Some(false))
+    }
+
+    /// Id of the river outside the recycle bin, other than `except`, with the
+    /// name, jurisdiction and ends of this one, the first entered if several
+    /// were stored before the identity was enforced.
+    pub fn find_same(&self, conn: &Connection, except: u32) -> Result<Option<u32>> {
+        conn.query_row(
+            "SELECT id FROM rivers \
+             WHERE name=?1 AND area=?2 AND start=?3 AND end=?4 AND deleted IS NULL AND id<>?5 \
+             ORDER BY id LIMIT 1",
+            params![self.name, self.area, self.start, self.end, except],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    /// Names the stored river in the error of a write that would have
+    /// duplicated it.
+    fn explain_conflict(&self, conn: &Connection, error: Error) -> Error {
+        if let Error::SqliteFailure(failure, _) = &error {
+            if failure.extended_code == ffi::SQLITE_CONSTRAINT_UNIQUE {
+                if let Ok(Some(id)) = self.find_same(conn, self.id) {
+                    return conflict_error(id);
+                }
+            }
+        }
+        error
+    }
+
+    /// Reach length in m, known when both ends are stake numbers.
+    pub fn reach_length(&self) -> Option<f64> {
+        Chainage::length(&self.start, &self.end).filter(|length| *length > 0.0)
+    }
+
+    /// Every inspection of this river, oldest first.
+    pub fn inspections(&self, conn: &Connection) -> Result<Vec<InspectionModel>> {
+        let mut stmt = conn.prepare(
+            InspectionModel::get_sql_with_condition("river_id=:river_id", ("time", "ASC"), (0, 0))
+                .as_str(),
+        )?;
+        let mut rows = stmt.query(named_params! { ":river_id": self.id })?;
+        let mut inspections = Vec::new();
+        while let Some(row) = rows.next()? {
+            inspections.push(InspectionModel::from_row(row)?);
+        }
+        Ok(inspections)
+    }
+
+    /// Stores the measurement of updated river `river_id`. An inspection
+    /// taken at the same time is corrected in place, and a new depth is kept
+    /// as a new inspection. An unchanged depth is no new measurement, since
+    /// saves stamp the current time: it only refreshes the threshold and
+    /// verdict of the latest inspection, which keeps its time.
+    fn record_inspection(&self, conn: &Connection, river_id: u32) -> Result<()> {
+        let mut inspection = self.inspection();
+        inspection.river_id = river_id;
+        let same_time = conn
+            .query_row(
+                format!(
+                    "{} WHERE river_id=?1 AND time=?2",
+                    InspectionModel::get_sql(DbOpt::Select)
+                )
+                .as_str(),
+                params![river_id, self.time],
+                InspectionModel::from_row,
+            )
+            .optional()?;
+        let latest = conn
+            .query_row(
+                format!(
+                    "{} WHERE river_id=? ORDER BY time DESC, id DESC LIMIT 1",
+                    InspectionModel::get_sql(DbOpt::Select)
+                )
+                .as_str(),
+                [river_id],
+                InspectionModel::from_row,
+            )
+            .optional()?;
+        match (same_time, latest) {
+            (Some(existing), _) => {
+                inspection.id = existing.id;
+                inspection.persist(conn, DbOpt::Update)?;
+            }
+            (None, Some(latest)) if latest.depth == self.depth => {
+                inspection.id = latest.id;
+                inspection.time = latest.time;
+                inspection.persist(conn, DbOpt::Update)?;
+            }
+            _ => {
+                inspection.persist(conn, DbOpt::Insert)?;
+            }
+        }
+        Ok(())
+    }
 }
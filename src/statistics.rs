@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+
+use crate::{
+    db::DbConn,
+    dredging,
+    export::ExportOptions,
+    security_model::{level_text, SecurityModel},
+};
+
+/// A column statistics are grouped by.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GroupBy {
+    Area,
+    Level,
+    Verdict,
+    /// Year of an inspection; each river counts once per year it was
+    /// inspected, with its last inspection of that year.
+    Year,
+}
+
+impl GroupBy {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "area" | "辖区" | "河道所属辖区" => Ok(GroupBy::Area),
+            "level" | "等级" | "河道防洪排涝等级" => Ok(GroupBy::Level),
+            "dredging" | "verdict" | "清淤判断" => Ok(GroupBy::Verdict),
+            "year" | "年份" | "年度" => Ok(GroupBy::Year),
+            text => Err(format!("无法识别的统计分组：{}", text)),
+        }
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            GroupBy::Area => "河道所属辖区",
+            GroupBy::Level => "河道防洪排涝等级",
+            GroupBy::Verdict => "清淤判断",
+            GroupBy::Year => "年份",
+        }
+    }
+
+    /// Position of `model` in the sorted groups, then the text shown.
+    fn key(&self, model: &SecurityModel) -> (i64, String) {
+        match self {
+            GroupBy::Area => (0, model.area.clone()),
+            GroupBy::Level => (model.level as i64, String::from(level_text(model.level))),
+            GroupBy::Verdict => (
+                [dredging::NEEDED, dredging::SUGGESTED, dredging::NOT_NEEDED]
+                    .iter()
+                    .position(|verdict| *verdict == model.dredging)
+                    .unwrap_or(3) as i64,
+                model.dredging.clone(),
+            ),
+            GroupBy::Year => (model.time.year() as i64, model.time.year().to_string()),
+        }
+    }
+}
+
+pub const STATISTICS_HEADERS: [&str; 10] = [
+    "河道数",
+    "需要清淤",
+    "建议清淤",
+    "总长度(km)",
+    "长度未知",
+    "平均淤积深度(m)",
+    "最大淤积深度(m)",
+    "超过阈值",
+    "最大超出(m)",
+    "超阈值比例",
+];
+
+/// Counts and measures of the rivers in one group, or of all of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    /// Text of each grouped column, in the order grouped by; empty for the
+    /// total.
+    pub key: Vec<String>,
+    pub rivers: usize,
+    pub needed: usize,
+    pub suggested: usize,
+    /// Total reach length in km of the rivers whose length is known.
+    pub length: f64,
+    /// Rivers left out of `length` because their ends are not stake numbers.
+    pub missing: usize,
+    /// Sum of the siltation depths in m, see `average_depth`.
+    pub depth: f64,
+    pub max_depth: f64,
+    /// Rivers whose siltation depth is above their threshold.
+    pub exceeding: usize,
+    /// Largest depth above the threshold, zero when none is above.
+    pub max_exceedance: f64,
+}
+
+impl Statistics {
+    fn add(&mut self, model: &SecurityModel) {
+        self.rivers += 1;
+        if model.dredging == dredging::NEEDED {
+            self.needed += 1;
+        } else if model.dredging == dredging::SUGGESTED {
+            self.suggested += 1;
+        }
+        match model.reach_length() {
+            Some(length) => self.length += length / 1000.0,
+            None => self.missing += 1,
+        }
+        let depth = model.depth as f64;
+        self.depth += depth;
+        self.max_depth = if self.rivers == 1 {
+            depth
+        } else {
+            self.max_depth.max(depth)
+        };
+        let exceedance = (model.depth - model.threshold) as f64;
+        if exceedance > 0.0 {
+            self.exceeding += 1;
+            self.max_exceedance = self.max_exceedance.max(exceedance);
+        }
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        if self.rivers == 0 {
+            0.0
+        } else {
+            self.depth / self.rivers as f64
+        }
+    }
+
+    /// Share of the rivers above their threshold, from 0 to 1.
+    pub fn exceeding_ratio(&self) -> f64 {
+        if self.rivers == 0 {
+            0.0
+        } else {
+            self.exceeding as f64 / self.rivers as f64
+        }
+    }
+}
+
+/// Rivers as counted: each river chosen by `options` as stored, or once per
+/// year inspected when grouping by year.
+fn measurements(
+    conn: &DbConn<SecurityModel>,
+    groups: &[GroupBy],
+    options: &ExportOptions,
+) -> Result<Vec<SecurityModel>, String> {
+    let models = options.rivers(conn)?;
+    if !groups.contains(&GroupBy::Year) {
+        return Ok(models);
+    }
+    let mut measured = Vec::new();
+    for model in models {
+        let mut years: BTreeMap<i32, SecurityModel> = BTreeMap::new();
+        // Oldest first, so the last inspection of a year is kept.
+        for inspection in model
+            .inspections(&conn.instance)
+            .map_err(|error| error.to_string())?
+        {
+            years.insert(
+                inspection.time.year(),
+                SecurityModel {
+                    depth: inspection.depth,
+                    threshold: inspection.threshold,
+                    dredging: inspection.dredging,
+                    time: inspection.time,
+                    ..model.clone()
+                },
+            );
+        }
+        measured.extend(years.into_values());
+    }
+    Ok(measured)
+}
+
+/// Statistics of `models` per combination of `groups`, in the order of the
+/// grouped values, and the total. Levels and years are ordered by number,
+/// verdicts from needed to not needed.
+pub fn tally(models: &[SecurityModel], groups: &[GroupBy]) -> (Vec<Statistics>, Statistics) {
+    let mut rows: BTreeMap<Vec<(i64, String)>, Statistics> = BTreeMap::new();
+    let mut total = Statistics::default();
+    for model in models {
+        let key: Vec<(i64, String)> = groups.iter().map(|group| group.key(model)).collect();
+        rows.entry(key)
+            .or_insert_with_key(|key| Statistics {
+                key: key.iter().map(|(_, text)| text.clone()).collect(),
+                ..Default::default()
+            })
+            .add(model);
+        total.add(model);
+    }
+    (rows.into_values().collect(), total)
+}
+
+/// Statistics of the rivers chosen by `options`, see `tally`.
+pub fn statistics(
+    conn: &DbConn<SecurityModel>,
+    groups: &[GroupBy],
+    options: &ExportOptions,
+) -> Result<(Vec<Statistics>, Statistics), String> {
+    Ok(tally(&measurements(conn, groups, options)?, groups))
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use chrono::{Local, TimeZone};
+use rust_xlsxwriter::{
+    Color, DataValidation, DataValidationErrorStyle, DataValidationRule, Format, FormatAlign,
+    FormatBorder, Workbook, Worksheet, XlsxError,
+};
+
+use crate::{
+    db::{Bound, Field, FieldType, Model},
+    dredging,
+    report::{header_format, text_width, write_value},
+    security_model::{SecurityModel, LEVEL_TEXTS},
+};
+
+pub const TEMPLATE_SHEET: &str = "河道数据";
+
+/// Rows below the headers that get drop-downs and input hints.
+const TEMPLATE_ROWS: u32 = 500;
+/// Spreadsheet rows, from 0, of the headers and of the example.
+const HEADER_ROW: u32 = 1;
+const EXAMPLE_ROW: u32 = 2;
+
+/// Fields a template asks for: every imported field except the id and the
+/// values computed from the others.
+pub fn template_fields() -> Vec<Field<SecurityModel>> {
+    SecurityModel::fields()
+        .into_iter()
+        .filter(|field| field.column != "id" && !dredging::DERIVED_COLUMNS.contains(&field.column))
+        .collect()
+}
+
+/// A complete, valid river shown below the headers as a guide.
+pub fn example() -> SecurityModel {
+    SecurityModel {
+        level: 3,
+        name: String::from("示例河"),
+        area: String::from("望江县"),
+        start: String::from("K0+000"),
+        end: String::from("K1+500"),
+        river_width: 30.0,
+        ratio: 2.0,
+        elevation: 12.5,
+        line: 3.2,
+        allow: 0.8,
+        safe: 0.5,
+        depth: 0.6,
+        time: Local.ymd(2024, 5, 1).and_hms(9, 30, 0),
+        ..Default::default()
+    }
+}
+
+/// Hint shown when a cell of `field` is selected: whether it is required,
+/// its unit and range, and how to write it.
+fn hint(field: &Field<SecurityModel>) -> String {
+    let mut parts = vec![String::from(if field.required {
+        "必填"
+    } else {
+        "选填"
+    })];
+    if let Some(unit) = field.unit {
+        parts.push(format!("单位：{}", unit));
+    }
+    match (field.min, field.max) {
+        (Some(_), Some(_)) | (None, None) => {}
+        (Some(Bound::Exclusive(min)), None) => parts.push(format!("须大于{}", min)),
+        (Some(Bound::Inclusive(min)), None) => parts.push(format!("须不小于{}", min)),
+        (None, Some(Bound::Exclusive(max))) => parts.push(format!("须小于{}", max)),
+        (None, Some(Bound::Inclusive(max))) => parts.push(format!("须不大于{}", max)),
+    }
+    parts.push(String::from(match field.column {
+        "level" => "从下拉列表中选择",
+        "area" => "须含有市、县、区、乡或一般",
+        "start" | "end" => "桩号如K0+000，终点须大于起点；也可填写地名",
+        "ratio" => "不填时按矩形断面计算",
+        "allow" => "是表示0.8，否表示0.4，也可直接填写系数；不填为0",
+        "time" => "如2024-05-01 09:30:00，不填时为导入时间",
+        _ => "",
+    }));
+    parts.retain(|part| !part.is_empty());
+    parts.join("；")
+}
+
+/// Range check of a number field from its bounds.
+fn number_rule(field: &Field<SecurityModel>) -> Option<DataValidationRule<f64>> {
+    if field.field_type != FieldType::Real {
+        return None;
+    }
+    Some(match (field.min, field.max) {
+        (Some(Bound::Inclusive(min)), Some(Bound::Inclusive(max))) => {
+            DataValidationRule::Between(min, max)
+        }
+        (Some(Bound::Exclusive(min)), _) => DataValidationRule::GreaterThan(min),
+        (Some(Bound::Inclusive(min)), _) => DataValidationRule::GreaterThanOrEqualTo(min),
+        (None, Some(Bound::Exclusive(max))) => DataValidationRule::LessThan(max),
+        (None, Some(Bound::Inclusive(max))) => DataValidationRule::LessThanOrEqualTo(max),
+        (None, None) => return None,
+    })
+}
+
+/// Drop-down or range check of a column, with its hint.
+fn validation(field: &Field<SecurityModel>) -> Result<DataValidation, XlsxError> {
+    let validation = match (field.column, number_rule(field)) {
+        ("level", _) => DataValidation::new().allow_list_strings(&LEVEL_TEXTS)?,
+        // Other coefficients are allowed, so Excel only warns about them.
+        ("allow", _) => DataValidation::new()
+            .allow_list_strings(&["是", "否"])?
+            .set_error_style(DataValidationErrorStyle::Warning),
+        (_, Some(rule)) => DataValidation::new()
+            .allow_decimal_number(rule)
+            .set_error_title("数值超出范围")?
+            .set_error_message(format!("{}：{}", field.header(), hint(field)))?,
+        (_, None) => DataValidation::new().allow_any_value(),
+    };
+    validation
+        .set_input_title(field.header())?
+        .set_input_message(hint(field))
+}
+
+fn write_sheet(sheet: &mut Worksheet) -> Result<(), XlsxError> {
+    let fields = template_fields();
+    let last = fields.len() as u16 - 1;
+    let required_header = header_format().set_background_color(Color::RGB(0xC00000));
+    let example_cell = Format::new()
+        .set_border(FormatBorder::Thin)
+        .set_font_color(Color::Gray)
+        .set_italic();
+
+    sheet.set_name(TEMPLATE_SHEET)?;
+    sheet.set_row_height(0, 36)?;
+    sheet.merge_range(
+        0,
+        0,
+        0,
+        last,
+        "河道数据录入模板：红色表头为必填项，选中单元格可查看填写说明；\
+         第3行为示例，请替换或删除后再导入。",
+        &Format::new()
+            .set_text_wrap()
+            .set_align(FormatAlign::VerticalCenter),
+    )?;
+    sheet.set_row_height(HEADER_ROW, 30)?;
+
+    let model = example();
+    for (col, field) in fields.iter().enumerate() {
+        let col = col as u16;
+        let header = field.header();
+        let format = if field.required {
+            required_header.clone()
+        } else {
+            header_format()
+        };
+        sheet.set_column_width(col, (text_width(&header) + 4.0).clamp(10.0, 30.0))?;
+        sheet.write_string_with_format(HEADER_ROW, col, header, &format)?;
+        let format = match field.field_type {
+            FieldType::DateTime => example_cell.clone().set_num_format("yyyy-mm-dd hh:mm:ss"),
+            _ => example_cell.clone(),
+        };
+        write_value(sheet, EXAMPLE_ROW, col, field.value(&model), &format)?;
+        sheet.add_data_validation(
+            HEADER_ROW + 1,
+            col,
+            HEADER_ROW + TEMPLATE_ROWS,
+            col,
+            &validation(field)?,
+        )?;
+    }
+    sheet.set_freeze_panes(HEADER_ROW + 1, 0)?;
+    Ok(())
+}
+
+/// Writes a blank data-entry workbook with the import headers, drop-downs
+/// for the level and wave run-up, input hints with units, and one example
+/// river that imports as is.
+pub fn write_template<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let mut workbook = Workbook::new();
+    write_sheet(workbook.add_worksheet())
+        .and_then(|_| workbook.save(path.as_ref()))
+        .map_err(|error| error.to_string())
+}
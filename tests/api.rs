@@ -0,0 +1,199 @@
+use std::{env, fs, path::PathBuf, process};
+
+use serde_json::Value;
+use wrs_nwg::{
+    api::{self, Request},
+    db::{DbConn, Model},
+    dredging,
+    security_model::SecurityModel,
+};
+
+/// Copy of the sample database, removed on drop.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("wrs-api-{}-{}.db", name, process::id()));
+        fs::copy("water-resources.db", &path).unwrap();
+        Scratch(path)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn call(conn: &mut DbConn<SecurityModel>, method: &str, url: &str, body: &str) -> (u16, Value) {
+    let response = api::handle(
+        conn,
+        &Request::new(method, url, "application/json; charset=utf-8", body),
+    );
+    let value = serde_json::from_slice(&response.body).unwrap_or(Value::Null);
+    (response.status, value)
+}
+
+const RIVER: &str = r#"{
+    "河道名称": "接口测试河",
+    "area": "安庆市望江县",
+    "level": "第二级",
+    "start": "K0+000",
+    "end": "K1+000",
+    "river_width": 30,
+    "elevation": 10,
+    "ratio": 2,
+    "line": 3,
+    "safe": 0.5,
+    "depth": 0.5
+}"#;
+
+#[test]
+fn rivers_are_created_changed_and_deleted() {
+    let scratch = Scratch::new("crud");
+    let mut conn = DbConn::<SecurityModel>::open(&scratch.0).unwrap();
+
+    let (status, created) = call(&mut conn, "POST", "/api/water_security", RIVER);
+    assert_eq!(status, 201, "{}", created);
+    assert_eq!(created["level"], "第二级");
+    assert_eq!(created["dredging"], dredging::NOT_NEEDED);
+    let url = format!("/api/water_security/{}", created["id"]);
+    assert_eq!(call(&mut conn, "POST", "/api/water_security", RIVER).0, 409);
+    let (status, duplicates) = call(&mut conn, "GET", "/api/water_security/duplicates", "");
+    assert_eq!(status, 200);
+    assert_eq!(duplicates["enforced"], true);
+    assert_eq!(duplicates["groups"].as_array().map(Vec::len), Some(0));
+    let merge = format!(
+        r#"{{"keep":{},"merge":[{}]}}"#,
+        created["id"], created["id"]
+    );
+    let (status, _) = call(
+        &mut conn,
+        "POST",
+        "/api/water_security/duplicates/merge",
+        &merge,
+    );
+    assert_eq!(status, 422);
+
+    let (status, found) = call(
+        &mut conn,
+        "GET",
+        "/api/water_security?q=%E6%8E%A5%E5%8F%A3&level=2",
+        "",
+    );
+    assert_eq!(status, 200);
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["items"][0]["id"], created["id"]);
+
+    let (status, changed) = call(&mut conn, "PATCH", &url, r#"{"淤积深度(m)": 50}"#);
+    assert_eq!(status, 200, "{}", changed);
+    assert_eq!(changed["depth"], 50);
+    assert_eq!(changed["name"], "接口测试河");
+    assert_eq!(changed["dredging"], dredging::NEEDED);
+    let (_, inspections) = call(&mut conn, "GET", &format!("{}/inspections", url), "");
+    assert_eq!(inspections.as_array().map(Vec::len), Some(2));
+
+    let (status, assessment) = call(&mut conn, "GET", &format!("{}/assessment", url), "");
+    assert_eq!(status, 200);
+    assert_eq!(assessment["dredging"], dredging::NEEDED);
+    assert_eq!(assessment["threshold"], changed["threshold"]);
+
+    assert_eq!(call(&mut conn, "DELETE", &url, "").0, 204);
+    assert_eq!(call(&mut conn, "GET", &url, "").0, 404);
+
+    let (status, entries) = call(
+        &mut conn,
+        "GET",
+        &format!("/api/audit?river={}", created["id"]),
+        "",
+    );
+    assert_eq!(status, 200);
+    let actions: Vec<&Value> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| &entry["action"])
+        .collect();
+    assert_eq!(actions, ["insert", "update", "delete"]);
+    assert_eq!(entries[1]["old"]["depth"], 0.5);
+    assert_eq!(entries[1]["new"]["depth"], 50);
+    let restore = |entry: &Value| format!("/api/audit/{}/restore", entry["id"]);
+    assert_eq!(call(&mut conn, "POST", &restore(&entries[0]), "").0, 409);
+    let (status, restored) = call(&mut conn, "POST", &restore(&entries[2]), "");
+    assert_eq!(status, 200, "{}", restored);
+    assert_eq!(restored["id"], created["id"]);
+    assert_eq!(restored["depth"], 50);
+    assert_eq!(call(&mut conn, "GET", &url, "").0, 200);
+
+    assert_eq!(call(&mut conn, "DELETE", &url, "").0, 204);
+    let (status, deleted) = call(&mut conn, "GET", "/api/recycle_bin", "");
+    assert_eq!(status, 200);
+    assert_eq!(deleted[0]["id"], created["id"]);
+    assert!(deleted[0]["deleted"].is_string());
+    let bin = format!("/api/recycle_bin/{}", created["id"]);
+    let (status, restored) = call(&mut conn, "POST", &format!("{}/restore", bin), "");
+    assert_eq!(status, 200, "{}", restored);
+    assert_eq!(call(&mut conn, "GET", &url, "").0, 200);
+    assert_eq!(call(&mut conn, "DELETE", &bin, "").0, 404);
+    assert_eq!(call(&mut conn, "DELETE", &url, "").0, 204);
+    assert_eq!(call(&mut conn, "DELETE", &bin, "").0, 204);
+    let (_, deleted) = call(&mut conn, "GET", "/api/recycle_bin", "");
+    assert_eq!(deleted.as_array().unwrap().len(), 0);
+
+    assert_eq!(call(&mut conn, "POST", "/api/water_security", RIVER).0, 201);
+    assert_eq!(call(&mut conn, "POST", &restore(&entries[2]), "").0, 409);
+    assert_eq!(
+        call(&mut conn, "POST", "/api/audit/999999/restore", "").0,
+        404
+    );
+}
+
+#[test]
+fn invalid_requests_are_explained() {
+    let scratch = Scratch::new("invalid");
+    let mut conn = DbConn::<SecurityModel>::open(&scratch.0).unwrap();
+
+    let (status, error) = call(
+        &mut conn,
+        "POST",
+        "/api/water_security",
+        &RIVER.replace("安庆市望江县", "望江"),
+    );
+    assert_eq!(status, 422);
+    assert_eq!(error["issues"][0]["column"], "河道所属辖区");
+    let (status, error) = call(&mut conn, "POST", "/api/water_security", r#"{"宽": 1}"#);
+    assert_eq!(status, 422);
+    assert_eq!(error["issues"][0]["column"], "宽");
+
+    let response = api::handle(
+        &mut conn,
+        &Request::new("POST", "/api/water_security", "text/plain", RIVER),
+    );
+    assert_eq!(response.status, 415);
+    let response = api::handle(
+        &mut conn,
+        &Request::new("DELETE", "/api/water_security/export", "", ""),
+    );
+    assert_eq!(response.status, 405);
+    assert!(response.headers.contains(&("Allow", String::from("GET"))));
+    assert_eq!(
+        call(&mut conn, "GET", "/api/water_security?limit=-1", "").0,
+        400
+    );
+    assert_eq!(call(&mut conn, "GET", "/api/unknown", "").0, 404);
+
+    // Every column is described, with the required ones listed.
+    let (_, spec) = call(&mut conn, "GET", "/openapi.json", "");
+    let river = &spec["components"]["schemas"]["River"];
+    let fields = SecurityModel::fields();
+    assert_eq!(
+        river["properties"]
+            .as_object()
+            .map(|properties| properties.len()),
+        Some(fields.len())
+    );
+    assert_eq!(
+        river["required"].as_array().map(Vec::len),
+        Some(fields.iter().filter(|field| field.required).count())
+    );
+}
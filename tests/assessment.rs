@@ -0,0 +1,100 @@
+use wrs_nwg::{
+    assessment::{self, AssessmentPage},
+    dredging,
+    form::RiverForm,
+    security_model::SecurityModel,
+};
+
+fn sloped_river() -> SecurityModel {
+    let mut model = SecurityModel {
+        name: String::from("皖河"),
+        area: String::from("安庆市望江县"),
+        level: 2,
+        river_width: 30.0,
+        ratio: 2.0,
+        line: 3.0,
+        safe: 0.5,
+        depth: 5.0,
+        ..Default::default()
+    };
+    model.recompute();
+    model
+}
+
+#[test]
+fn page_traces_the_verdict() {
+    let model = sloped_river();
+    let assessment = dredging::assess(&model);
+    let page = AssessmentPage::of(&model);
+
+    assert_eq!(page.verdict, model.dredging.as_str());
+    assert!(page.note.is_none());
+    let last = page.steps.last().unwrap();
+    assert_eq!(last.result, assessment.dredging);
+    assert!(page
+        .steps
+        .iter()
+        .any(|step| step.result == format!("{} m", dredging::number_text(model.threshold))));
+
+    let html = assessment::html(&[page]);
+    assert!(html.contains("<h1>皖河河道清淤评估表</h1>"));
+    assert!(html.contains("<svg"));
+    assert!(html.contains(model.dredging.as_str()));
+}
+
+#[test]
+fn page_notes_a_stale_verdict() {
+    let mut model = sloped_river();
+    model.dredging = String::from(if model.dredging == dredging::NEEDED {
+        dredging::NOT_NEEDED
+    } else {
+        dredging::NEEDED
+    });
+    assert!(AssessmentPage::of(&model).note.is_some());
+}
+
+#[test]
+fn form_calculates_as_it_is_filled_in() {
+    let model = sloped_river();
+    let mut form = RiverForm::new();
+    assert!(form.assessment().is_err());
+
+    for (column, text) in [
+        ("name", "皖河"),
+        ("area", "安庆市望江县"),
+        ("start", "K0+000"),
+        ("end", "K1+000"),
+        ("river_width", "30"),
+        ("ratio", "2"),
+        ("elevation", "10"),
+        ("line", "3"),
+        ("safe", "0.5"),
+        ("depth", "5"),
+    ]
+    .iter()
+    {
+        form.set_text(column, text);
+    }
+    let assessment = form.assessment().ok().unwrap();
+    assert_eq!(assessment.threshold, model.threshold);
+    assert_eq!(assessment.dredging, model.dredging.as_str());
+
+    form.set_text("area", "望江");
+    assert!(form.assessment().is_err());
+    let errors = form.save().err().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "area");
+
+    let stored = SecurityModel {
+        id: 7,
+        start: String::from("K0+000"),
+        end: String::from("K1+000"),
+        elevation: 10.0,
+        ..model
+    };
+    let form = RiverForm::edit(stored);
+    assert_eq!(form.text("level"), "第二级");
+    let saved = form.save().ok().unwrap();
+    assert_eq!(saved.id, 7);
+    assert_eq!(saved.level, 2);
+}
@@ -0,0 +1,52 @@
+use wrs_nwg::chainage::{check_reach, Chainage};
+
+#[test]
+fn stakes_parse_and_format_back() {
+    let stake: Chainage = "K1+250".parse().unwrap();
+    assert_eq!(stake.prefix, "K");
+    assert_eq!(stake.metres, 1250.0);
+    assert_eq!(stake.to_string(), "K1+250");
+
+    for (text, formatted) in [
+        ("k0+005", "K0+005"),
+        ("ＺＫ３＋０５０．５", "ZK3+050.5"),
+        (" YK12 + 999.125 ", "YK12+999.125"),
+    ]
+    .iter()
+    {
+        let stake: Chainage = text.parse().unwrap();
+        assert_eq!(stake.to_string(), *formatted);
+        assert_eq!(formatted.parse::<Chainage>().unwrap(), stake);
+    }
+    assert_eq!(Chainage::normalize("k1+250"), "K1+250");
+    assert_eq!(Chainage::normalize(" 闸口 "), "闸口");
+}
+
+#[test]
+fn malformed_stakes_are_rejected() {
+    for text in [
+        "", "闸口", "1+250", "K1", "K+250", "K1+", "K1+2x0", "A1+250", "K-1+250",
+    ]
+    .iter()
+    {
+        assert!(text.parse::<Chainage>().is_err(), "{}", text);
+    }
+    assert_eq!(
+        "K1+1000".parse::<Chainage>().unwrap_err(),
+        "桩号K1+1000的米数必须小于1000"
+    );
+    assert_eq!(Chainage::metres_of("闸口"), None);
+}
+
+#[test]
+fn reaches_run_downstream_on_one_line() {
+    assert_eq!(Chainage::length("K0+500", "K1+250"), Some(750.0));
+    assert_eq!(Chainage::length("K0+500", "ZK1+250"), None);
+    assert_eq!(Chainage::length("闸口", "K1+250"), None);
+
+    assert!(check_reach("K0+500", "K1+250").is_ok());
+    assert!(check_reach("闸口", "泵站").is_ok());
+    assert!(check_reach("K1+250", "K0+500").is_err());
+    assert!(check_reach("K1+250", "K1+250").is_err());
+    assert!(check_reach("K0+500", "ZK1+250").is_err());
+}
@@ -0,0 +1,98 @@
+use wrs_nwg::{
+    estimate::{self, DredgingMethod, EstimateOptions, PriceCatalog},
+    security_model::SecurityModel,
+};
+
+/// Rectangular reach of 1 km, 10 m wide, silted 1 m deep.
+fn reach(area: &str, end: &str) -> SecurityModel {
+    SecurityModel {
+        area: String::from(area),
+        start: String::from("K0+000"),
+        end: String::from(end),
+        river_width: 10.0,
+        depth: 1.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn section_area_follows_the_slope() {
+    let rectangular = reach("望江县", "K1+000");
+    assert_eq!(estimate::section_area(&rectangular, 2.0), 20.0);
+    assert_eq!(estimate::section_area(&rectangular, 0.0), 0.0);
+
+    let sloped = SecurityModel {
+        ratio: 2.0,
+        channel_width: 4.0,
+        ..rectangular
+    };
+    assert_eq!(estimate::section_area(&sloped, 1.0), 6.0);
+}
+
+#[test]
+fn volume_and_cost_of_a_reach() {
+    let catalog = PriceCatalog::default();
+    let estimate = estimate::estimate(
+        &reach("望江县", "K1+000"),
+        &catalog,
+        &EstimateOptions::default(),
+    );
+    assert_eq!(estimate.length, Some(1000.0));
+    assert_eq!((estimate.excess, estimate.section), (1.0, 10.0));
+    assert_eq!(estimate.volume, Some(10000.0));
+    let cost = estimate.cost.unwrap();
+    assert_eq!(cost.dredging, 180000.0);
+    assert_eq!(cost.haulage, 60000.0);
+    assert_eq!(cost.disposal, 100000.0);
+    assert_eq!(cost.total(), 340000.0);
+
+    let options = EstimateOptions {
+        method: DredgingMethod::parse("水力").unwrap(),
+        distance: 0.0,
+        target: 0.5,
+    };
+    let estimate = estimate::estimate(&reach("望江县", "K1+000"), &catalog, &options);
+    assert_eq!((estimate.excess, estimate.section), (0.5, 5.0));
+    assert_eq!(estimate.cost.unwrap().total(), 5000.0 * (25.0 + 10.0));
+
+    let options = EstimateOptions {
+        target: 2.0,
+        ..options
+    };
+    let estimate = estimate::estimate(&reach("望江县", "K1+000"), &catalog, &options);
+    assert_eq!((estimate.excess, estimate.section), (0.0, 0.0));
+}
+
+#[test]
+fn reaches_without_stakes_are_left_out_of_the_totals() {
+    let catalog = PriceCatalog::default();
+    let options = EstimateOptions::default();
+    let estimates: Vec<_> = [
+        reach("望江县", "K1+000"),
+        reach("太湖县", "K0+500"),
+        reach("望江县", "闸口"),
+    ]
+    .iter()
+    .map(|model| estimate::estimate(model, &catalog, &options))
+    .collect();
+    assert_eq!(estimates[2].volume, None);
+    assert!(estimates[2].cost.is_none());
+
+    let totals = estimate::totals_by_area(&estimates);
+    let areas: Vec<&str> = totals.iter().map(|total| total.area.as_str()).collect();
+    assert_eq!(areas, ["望江县", "太湖县"]);
+    assert_eq!((totals[0].reaches, totals[0].missing), (2, 1));
+    assert_eq!((totals[0].length, totals[0].volume), (1000.0, 10000.0));
+    assert_eq!(totals[0].cost, 340000.0);
+    assert_eq!(totals[1].volume, 5000.0);
+}
+
+#[test]
+fn prices_are_set_by_item() {
+    let mut catalog = PriceCatalog::default();
+    assert!(catalog.set("haulage", 2.0));
+    assert_eq!(catalog.get("haulage"), Some(2.0));
+    assert!(!catalog.set("dredger", 1.0));
+    assert_eq!(catalog.get("dredger"), None);
+    assert!(DredgingMethod::parse("人工").is_none());
+}
@@ -0,0 +1,103 @@
+use chrono::{Local, TimeZone};
+use wrs_nwg::{
+    forecast::{self, Curve},
+    inspection_model::InspectionModel,
+};
+
+/// Inspections on New Year's Day of consecutive years from 2020.
+fn yearly(depths: &[f32]) -> Vec<InspectionModel> {
+    depths
+        .iter()
+        .enumerate()
+        .map(|(index, depth)| InspectionModel {
+            time: Local.ymd(2020 + index as i32, 1, 1).and_hms(0, 0, 0),
+            depth: *depth,
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[test]
+fn linear_fit_recovers_the_line() {
+    let points: Vec<(f64, f64)> = (0..5).map(|t| (t as f64, 1.0 + 0.5 * t as f64)).collect();
+    let fit = forecast::fit_linear(&points).unwrap();
+    match fit.curve {
+        Curve::Linear { a, b } => {
+            assert!((a - 1.0).abs() < 1e-9);
+            assert!((b - 0.5).abs() < 1e-9);
+        }
+        curve => panic!("{:?}", curve),
+    }
+    assert!(fit.sse < 1e-12);
+    assert_eq!(fit.samples, 5);
+    assert!((fit.curve.crossing(3.0).unwrap() - 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn saturating_fit_recovers_the_level() {
+    let truth = Curve::Saturating {
+        c: 3.0,
+        beta: -2.0,
+        k: 0.5,
+    };
+    let points: Vec<(f64, f64)> = (0..8)
+        .map(|t| (t as f64, truth.predict(t as f64)))
+        .collect();
+    let fit = forecast::fit_saturating(&points).unwrap();
+    match fit.curve {
+        Curve::Saturating { c, beta, k } => {
+            assert!((c - 3.0).abs() < 1e-3, "c = {}", c);
+            assert!((beta + 2.0).abs() < 1e-3, "beta = {}", beta);
+            assert!((k - 0.5).abs() < 1e-3, "k = {}", k);
+        }
+        curve => panic!("{:?}", curve),
+    }
+    assert!(fit.curve.crossing(2.0).is_some());
+    assert_eq!(fit.curve.crossing(3.5), None);
+}
+
+#[test]
+fn sparse_inspections_are_not_fitted() {
+    assert!(forecast::fit_linear(&[(0.0, 1.0)]).is_none());
+    assert!(forecast::fit_linear(&[(1.0, 1.0), (1.0, 2.0)]).is_none());
+    assert!(forecast::fit_saturating(&[(0.0, 1.0), (1.0, 2.0), (2.0, 2.5)]).is_none());
+
+    let overflowing = [
+        (0.0, 0.0),
+        (1.0, f64::MAX),
+        (2.0, -f64::MAX),
+        (3.0, f64::MAX),
+    ];
+    assert!(forecast::fit_saturating(&overflowing).is_none());
+
+    assert!(forecast::forecast(&[], 2.0).is_none());
+    assert!(forecast::forecast(&yearly(&[1.0]), 2.0).is_none());
+    let mut same_time = yearly(&[1.0, 1.5]);
+    same_time[1].time = same_time[0].time;
+    assert!(forecast::forecast(&same_time, 2.0).is_none());
+}
+
+#[test]
+fn forecast_gives_the_year_the_threshold_is_reached() {
+    let forecast = forecast::forecast(&yearly(&[1.0, 1.5, 2.0]), 3.0).unwrap();
+    assert_eq!(forecast.fit.curve.name(), "线性");
+    assert!((forecast.rate - 0.5).abs() < 1e-6);
+    assert_eq!(forecast.latest_depth, 2.0);
+    assert!(!forecast.exceeded());
+    assert!((forecast.year.unwrap() - 2024.0).abs() < 1e-6);
+    assert_eq!(forecast.year_text(), "2024");
+
+    let exceeded = forecast::forecast(&yearly(&[1.0, 1.5, 2.0]), 1.5).unwrap();
+    assert!(exceeded.exceeded());
+    assert_eq!(exceeded.year_text(), "已超过阈值");
+}
+
+#[test]
+fn falling_depths_never_reach_the_threshold() {
+    let forecast = forecast::forecast(&yearly(&[2.0, 1.8, 1.5, 1.4]), 3.0).unwrap();
+    assert!(forecast.rate < 0.0);
+    assert_eq!(forecast.year, None);
+    assert_eq!(forecast.year_text(), "不会超过阈值");
+    assert_eq!(forecast.interval, None);
+    assert_eq!(forecast.interval_text(), "");
+}
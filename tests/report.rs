@@ -0,0 +1,125 @@
+use wrs_nwg::{
+    dredging, report,
+    security_model::{SecurityModel, UNKNOWN_LEVEL},
+    statistics::{self, GroupBy},
+};
+
+fn river(area: &str, level: u32, end: &str, verdict: &str) -> SecurityModel {
+    SecurityModel {
+        area: String::from(area),
+        level,
+        start: String::from("K0+000"),
+        end: String::from(end),
+        dredging: String::from(verdict),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn summary_totals_each_area_and_level() {
+    let models = vec![
+        river("望江县", 2, "K1+500", dredging::NEEDED),
+        river("太湖县", 1, "K0+800", dredging::NOT_NEEDED),
+        river("望江县", 1, "K2+000", dredging::SUGGESTED),
+        river("望江县", 2, "终点", dredging::NEEDED),
+    ];
+    let rows = report::summary_rows(&models);
+
+    let keys: Vec<Vec<&str>> = rows
+        .iter()
+        .map(|row| row.key.iter().map(String::as_str).collect())
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            vec!["太湖县", "第一级"],
+            vec!["太湖县"],
+            vec!["望江县", "第一级"],
+            vec!["望江县", "第二级"],
+            vec!["望江县"],
+            vec![],
+        ]
+    );
+    let second = &rows[3];
+    assert_eq!(
+        (
+            second.rivers,
+            second.needed,
+            second.suggested,
+            second.missing
+        ),
+        (2, 2, 0, 1)
+    );
+    assert!((second.length - 1.5).abs() < 1e-9);
+    assert_eq!((rows[4].rivers, rows[4].suggested), (3, 1));
+    let total = &rows[5];
+    assert_eq!((total.rivers, total.needed, total.missing), (4, 2, 1));
+    assert!((total.length - 4.3).abs() < 1e-9);
+}
+
+#[test]
+fn statistics_group_by_any_columns() {
+    let mut models = vec![
+        river("望江县", 2, "K1+500", dredging::NEEDED),
+        river("太湖县", 1, "K0+800", dredging::NOT_NEEDED),
+        river("望江县", 1, "K2+000", dredging::SUGGESTED),
+        river("望江县", 2, "终点", dredging::NEEDED),
+    ];
+    for (model, (depth, threshold)) in models
+        .iter_mut()
+        .zip([(5.0, 3.0), (1.0, 2.0), (2.5, 2.0), (4.0, 2.0)].iter())
+    {
+        model.depth = *depth;
+        model.threshold = *threshold;
+    }
+    let groups = vec![
+        GroupBy::parse("清淤判断").unwrap(),
+        GroupBy::parse("level").unwrap(),
+    ];
+    assert!(GroupBy::parse("河长").is_err());
+    let (rows, total) = statistics::tally(&models, &groups);
+
+    let keys: Vec<Vec<String>> = rows.iter().map(|row| row.key.clone()).collect();
+    assert_eq!(
+        keys,
+        vec![
+            vec![String::from(dredging::NEEDED), String::from("第二级")],
+            vec![String::from(dredging::SUGGESTED), String::from("第一级")],
+            vec![String::from(dredging::NOT_NEEDED), String::from("第一级")],
+        ]
+    );
+    let needed = &rows[0];
+    assert_eq!((needed.rivers, needed.needed, needed.missing), (2, 2, 1));
+    assert!((needed.length - 1.5).abs() < 1e-9);
+    assert!((needed.average_depth() - 4.5).abs() < 1e-9);
+    assert_eq!(needed.max_depth, 5.0);
+    assert_eq!((needed.exceeding, needed.max_exceedance), (2, 2.0));
+
+    assert!(total.key.is_empty());
+    assert_eq!((total.rivers, total.exceeding), (4, 3));
+    assert!((total.exceeding_ratio() - 0.75).abs() < 1e-9);
+}
+
+#[test]
+fn unknown_levels_are_not_counted_as_the_first() {
+    let models = vec![
+        river("望江县", 0, "K1+000", dredging::NEEDED),
+        river("望江县", 1, "K1+000", dredging::NEEDED),
+    ];
+    let (rows, _) = statistics::tally(&models, &[GroupBy::Level]);
+    let keys: Vec<Vec<String>> = rows.iter().map(|row| row.key.clone()).collect();
+    assert_eq!(
+        keys,
+        vec![
+            vec![String::from(UNKNOWN_LEVEL)],
+            vec![String::from("第一级")],
+        ]
+    );
+
+    let rows = report::summary_rows(&models);
+    assert_eq!(
+        rows[0].key,
+        [String::from("望江县"), String::from(UNKNOWN_LEVEL)]
+    );
+    assert_eq!(rows[1].key[1], "第一级");
+}
@@ -0,0 +1,668 @@
+use std::{env, fs, path::PathBuf, process};
+
+use calamine::{DataType, Reader, Xlsx};
+use chrono::Duration;
+use rusqlite::Connection;
+use serde_json::Value;
+use wrs_nwg::{
+    audit::{self, AuditAction},
+    db::{DbConn, Model},
+    dredging,
+    duplicate::{self, Similarity},
+    export::{self, ExportOptions, TextEncoding},
+    import::{self, Action, Severity},
+    recycle,
+    security_model::{conflict_message, SecurityModel},
+    template,
+};
+
+/// Scratch directory holding a copy of the sample database, removed on drop.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("wrs-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy("water-resources.db", dir.join("water-resources.db")).unwrap();
+        Scratch(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn export_then_import_keeps_every_field() {
+    let scratch = Scratch::new("round-trip");
+    let conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let workbook = scratch.path("export.xlsx");
+    let stored = conn.find("", ("id", "ASC"), (0, 0), &[]).unwrap();
+    assert!(!stored.is_empty());
+    assert_eq!(
+        export::write_workbook(&conn, &workbook, &ExportOptions::default()).unwrap(),
+        stored.len()
+    );
+
+    let table = import::read_workbook::<SecurityModel, _>(&workbook, None, None).unwrap();
+    let preview = import::preview::<SecurityModel>(&table, None);
+    assert!(
+        preview.issues.is_empty(),
+        "table issues: {:?}",
+        preview.issues
+    );
+    assert_eq!(preview.rows.len(), stored.len());
+
+    let fields = SecurityModel::fields();
+    let mut checked = 0;
+    for (model, row) in stored.iter().zip(preview.rows.iter()) {
+        // Records that were stored invalid cannot be imported again.
+        if !SecurityModel::validate(model).is_empty() {
+            continue;
+        }
+        assert!(
+            row.issues
+                .iter()
+                .all(|issue| issue.severity != Severity::Error),
+            "row {}: {:?}",
+            row.row,
+            row.issues
+        );
+        for field in fields.iter() {
+            assert_eq!(
+                (field.formatter)(&row.model),
+                (field.formatter)(model),
+                "row {} column {}",
+                row.row,
+                field.column
+            );
+        }
+        checked += 1;
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+fn export_writes_typed_cells() {
+    let scratch = Scratch::new("typed-cells");
+    let conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let workbook = scratch.path("export.xlsx");
+    export::write_workbook(&conn, &workbook, &ExportOptions::default()).unwrap();
+
+    let mut workbook: Xlsx<_> = calamine::open_workbook(&workbook).unwrap();
+    let range = workbook.worksheet_range("Sheet1").unwrap().unwrap();
+    let headers: Vec<String> = range
+        .rows()
+        .next()
+        .unwrap()
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect();
+    let column = |name: &str| headers.iter().position(|header| header == name).unwrap();
+    let stored = conn.find("", ("id", "ASC"), (0, 0), &[]).unwrap();
+    let valid = stored
+        .iter()
+        .position(|model| SecurityModel::validate(model).is_empty())
+        .unwrap();
+    let row = range.rows().nth(valid + 1).unwrap();
+
+    assert!(matches!(
+        row[column("编号")],
+        DataType::Float(_) | DataType::Int(_)
+    ));
+    assert!(matches!(row[column("淤积深度(m)")], DataType::Float(_)));
+    assert!(matches!(row[column("录入时间")], DataType::DateTime(_)));
+    assert!(
+        matches!(&row[column("河道防洪排涝等级")], DataType::String(level) if level.starts_with('第'))
+    );
+}
+
+#[test]
+fn filtered_export_keeps_chosen_rivers_and_columns() {
+    let scratch = Scratch::new("filtered");
+    let conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let file = scratch.path("export.csv");
+    let mut options = ExportOptions::default();
+    options.set_areas("安庆市");
+    options.set_dredging("不需要").unwrap();
+    options.set_columns("河道名称，淤积深度(m)").unwrap();
+    options.set_sort("淤积深度:desc").unwrap();
+    let written = export::write_csv(&conn, &file, TextEncoding::Utf8, &options).unwrap();
+
+    let mut expected: Vec<SecurityModel> = conn
+        .find("", ("id", "ASC"), (0, 0), &[])
+        .unwrap()
+        .into_iter()
+        .filter(|model| model.area.contains("安庆市") && model.dredging == dredging::NOT_NEEDED)
+        .collect();
+    expected.sort_by(|a, b| b.depth.partial_cmp(&a.depth).unwrap());
+    assert!(!expected.is_empty());
+    assert_eq!(written, expected.len());
+
+    let table = import::read_table::<SecurityModel, _>(&file, None, None).unwrap();
+    assert_eq!(table.headers, vec!["河道名称", "淤积深度(m)"]);
+    let names: Vec<&str> = table.rows.iter().map(|row| row[0].as_str()).collect();
+    let expected: Vec<&str> = expected.iter().map(|model| model.name.as_str()).collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn template_example_imports_cleanly() {
+    let scratch = Scratch::new("template");
+    let workbook = scratch.path("template.xlsx");
+    template::write_template(&workbook).unwrap();
+
+    let table = import::read_workbook::<SecurityModel, _>(&workbook, None, None).unwrap();
+    assert_eq!(table.header_row, 2);
+    let headers: Vec<String> = template::template_fields()
+        .iter()
+        .map(|field| field.header())
+        .collect();
+    assert_eq!(table.headers, headers);
+
+    let preview = import::preview::<SecurityModel>(&table, None);
+    assert!(preview.issues.is_empty(), "{:?}", preview.issues);
+    assert_eq!(preview.rows.len(), 1);
+    let row = &preview.rows[0];
+    assert!(row.issues.is_empty(), "{:?}", row.issues);
+    let example = template::example();
+    for field in template::template_fields().iter() {
+        assert_eq!(
+            (field.formatter)(&row.model),
+            (field.formatter)(&example),
+            "column {}",
+            field.column
+        );
+    }
+}
+
+#[test]
+fn search_finds_text_and_pinyin() {
+    let scratch = Scratch::new("search");
+    let mut conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let mut model = template::example();
+    model.name = String::from("清河湖");
+    model.recompute();
+    conn.set(model);
+    conn.insert().unwrap();
+    let mut model = conn
+        .find_first(
+            "name=:name",
+            ("id", "ASC"),
+            (1, 0),
+            &[(":name", &String::from("清河湖"))],
+        )
+        .unwrap();
+
+    let names = |conn: &DbConn<SecurityModel>, keyword: &str| {
+        let mut options = ExportOptions::default();
+        options.set_keyword(keyword);
+        options
+            .rivers(conn)
+            .unwrap()
+            .into_iter()
+            .map(|model| model.name)
+            .collect::<Vec<String>>()
+    };
+    for keyword in [
+        "河湖",
+        "qhh",
+        "QingHe",
+        "hehu",
+        "hh",
+        "望江 qhh",
+        "K1+5",
+        "不需要 河湖",
+    ]
+    .iter()
+    {
+        assert_eq!(names(&conn, keyword), vec!["清河湖"], "{}", keyword);
+    }
+    assert!(names(&conn, "清湖").is_empty());
+    assert!(names(&conn, "qhh 太湖").is_empty());
+    assert!(names(&conn, "\"*").is_empty());
+
+    model.name = String::from("白沙河");
+    conn.set(model);
+    conn.update().unwrap();
+    assert!(names(&conn, "qhh").is_empty());
+    assert_eq!(names(&conn, "bsh"), vec!["白沙河"]);
+    conn.delete().unwrap();
+    assert!(names(&conn, "bsh").is_empty());
+}
+
+#[test]
+fn rivers_without_ends_are_migrated() {
+    let scratch = Scratch::new("legacy");
+    let path = scratch.path("legacy.db");
+    let legacy = Connection::open(&path).unwrap();
+    legacy
+        .execute_batch(
+            r#"CREATE TABLE water_security
+            (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, level INTEGER, name TEXT, area TEXT,
+                start TEXT, end TEXT, river_width REAL, elevation REAL, ratio REAL, line REAL,
+                allow REAL, safe REAL, depth REAL, channel_width REAL, threshold REAL,
+                dredging TEXT, time TEXT
+            );
+            INSERT INTO water_security(level, name, area, start, end, depth, time)
+            VALUES (1, '清河', '城区', NULL, 'K1+000', 1, '2021-05-01 09:00:00');"#,
+        )
+        .unwrap();
+    drop(legacy);
+
+    let conn = DbConn::<SecurityModel>::open(&path).unwrap();
+    let chainage: (Option<f64>, Option<f64>) = conn
+        .instance
+        .query_row(
+            "SELECT start_chainage, end_chainage FROM rivers",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(chainage, (None, Some(1000.0)));
+}
+
+#[test]
+fn only_new_depths_are_new_inspections() {
+    let scratch = Scratch::new("inspections");
+    let mut conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let river = conn.find_by_id(10).unwrap();
+    let inspections = river.inspections(&conn.instance).unwrap().len();
+
+    // Saves stamp the current time, which alone is no new inspection.
+    conn.set(SecurityModel {
+        name: String::from("改名河"),
+        time: river.time + Duration::days(30),
+        ..river.clone()
+    });
+    conn.update().unwrap();
+    *conn.model = None;
+    let stored = river.inspections(&conn.instance).unwrap();
+    assert_eq!(stored.len(), inspections);
+    assert_eq!(stored.last().unwrap().time, river.time);
+    assert_eq!(conn.find_by_id(10).unwrap().time, river.time);
+
+    let later = SecurityModel {
+        depth: river.depth + 1.0,
+        time: river.time + Duration::days(60),
+        ..river.clone()
+    };
+    conn.set(later.clone());
+    conn.update().unwrap();
+    let stored = river.inspections(&conn.instance).unwrap();
+    assert_eq!(stored.len(), inspections + 1);
+    assert_eq!(stored.last().unwrap().time, later.time);
+
+    // A correction at the same time replaces it.
+    conn.set(SecurityModel {
+        depth: river.depth + 2.0,
+        ..later
+    });
+    conn.update().unwrap();
+    let stored = river.inspections(&conn.instance).unwrap();
+    assert_eq!(stored.len(), inspections + 1);
+    assert_eq!(stored.last().unwrap().depth, river.depth + 2.0);
+}
+
+#[test]
+fn audit_log_records_changes_and_restores_them() {
+    let scratch = Scratch::new("audit");
+    let mut conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    audit::set_context(&conn.instance, "张三", "测试").unwrap();
+    let original = conn.find_by_id(10).unwrap();
+
+    let path = scratch.path("raised.json");
+    let mut options = ExportOptions::default();
+    options.set_keyword(&original.name);
+    export::write_file(&conn, &path, TextEncoding::Utf8, &options).unwrap();
+    let mut records: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    for record in records.as_array_mut().unwrap() {
+        if record["id"] == 10 {
+            record["elevation"] = Value::from(original.elevation as f64 + 1.0);
+        }
+    }
+    fs::write(&path, records.to_string()).unwrap();
+    let table = import::read_json(&path).unwrap();
+    let preview = import::preview::<SecurityModel>(&table, None);
+    import::commit(
+        &mut conn,
+        preview.valid_rows(),
+        import::ConflictPolicy::Overwrite,
+    );
+    assert_eq!(
+        conn.find_by_id(10).unwrap().elevation,
+        original.elevation + 1.0
+    );
+
+    conn.set(conn.find_by_id(10).unwrap());
+    conn.delete().unwrap();
+    *conn.model = None;
+    assert!(conn.find_by_id(10).is_err());
+
+    let entries = audit::entries(&conn.instance, Some(10), None, None).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].action, AuditAction::Update);
+    assert_eq!(entries[0].user, "张三");
+    assert_eq!(entries[0].source, import::IMPORT_SOURCE);
+    let changed: Vec<String> = entries[0]
+        .changes()
+        .into_iter()
+        .map(|(header, _, _)| header)
+        .collect();
+    assert_eq!(changed, ["设计河底高程(m)"]);
+    assert_eq!(entries[1].action, AuditAction::Delete);
+    assert_eq!(entries[1].name(), original.name);
+    assert!(entries[1].new.is_none());
+
+    let now = chrono::Local::now();
+    let later = now + chrono::Duration::days(1);
+    assert_eq!(
+        audit::entries(&conn.instance, None, Some(later), None)
+            .unwrap()
+            .len(),
+        0
+    );
+    assert_eq!(
+        audit::entries(&conn.instance, None, None, Some(later))
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let restored = audit::restore(&mut conn, entries[1].id).unwrap();
+    assert_eq!(
+        (restored.id, restored.elevation),
+        (10, original.elevation + 1.0)
+    );
+    let restored = audit::restore(&mut conn, entries[0].id).unwrap();
+    assert_eq!(restored.elevation, original.elevation);
+    assert_eq!(restored.depth, original.depth);
+    assert_eq!(restored.time.timestamp(), original.time.timestamp());
+
+    let entries = audit::entries(&conn.instance, Some(10), None, None).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[2].action, AuditAction::Restore);
+    assert_eq!(
+        entries[3].source,
+        format!("恢复第{}条修改记录", entries[0].id)
+    );
+    assert_eq!(audit::context(&conn.instance).unwrap().1, "测试");
+}
+
+#[test]
+fn deleted_rivers_go_to_the_recycle_bin() {
+    let scratch = Scratch::new("recycle");
+    let mut conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let river = conn.find_by_id(10).unwrap();
+    let inspections = river.inspections(&conn.instance).unwrap().len();
+
+    conn.set(river.clone());
+    assert_eq!(conn.delete().unwrap(), 1);
+    *conn.model = None;
+    assert!(conn.find_by_id(10).is_err());
+    let mut options = ExportOptions::default();
+    options.set_keyword(&river.name);
+    assert!(options.rivers(&conn).unwrap().is_empty());
+    let deleted = recycle::deleted_rivers(&conn.instance).unwrap();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].model.name, river.name);
+
+    let restored = recycle::restore(&conn, 10).unwrap();
+    assert_eq!(restored.name, river.name);
+    assert_eq!(conn.find_by_id(10).unwrap().depth, river.depth);
+    assert_eq!(options.rivers(&conn).unwrap().len(), 1);
+    assert_eq!(
+        conn.find_by_id(10)
+            .unwrap()
+            .inspections(&conn.instance)
+            .unwrap()
+            .len(),
+        inspections
+    );
+    assert!(recycle::restore(&conn, 10).is_err());
+
+    // A deleted river imported again comes back out of the recycle bin.
+    let path = scratch.path("river.json");
+    export::write_file(&conn, &path, TextEncoding::Utf8, &options).unwrap();
+    conn.set(conn.find_by_id(10).unwrap());
+    conn.delete().unwrap();
+    *conn.model = None;
+    let table = import::read_json(&path).unwrap();
+    let preview = import::preview::<SecurityModel>(&table, None);
+    let summary = import::commit(
+        &mut conn,
+        preview.valid_rows(),
+        import::ConflictPolicy::Overwrite,
+    );
+    assert_eq!(summary.restored, 1);
+    assert_eq!(conn.find_by_id(10).unwrap().name, river.name);
+
+    conn.set(conn.find_by_id(10).unwrap());
+    conn.delete().unwrap();
+    *conn.model = None;
+    assert_eq!(recycle::purge(&conn.instance, 10).unwrap(), 1);
+    assert!(recycle::find(&conn.instance, 10).unwrap().is_none());
+    assert!(recycle::restore(&conn, 10).is_err());
+    let entries = audit::entries(&conn.instance, Some(10), None, None).unwrap();
+    assert_eq!(entries.last().unwrap().action, AuditAction::Purge);
+    assert_eq!(entries.last().unwrap().name(), river.name);
+}
+
+#[test]
+fn rivers_are_unique_by_name_area_and_ends() {
+    let scratch = Scratch::new("identity");
+    let path = scratch.path("water-resources.db");
+    let mut conn = DbConn::<SecurityModel>::open(&path).unwrap();
+    let river = conn.find_by_id(10).unwrap();
+    let count =
+        |conn: &DbConn<SecurityModel>| conn.find("", ("id", "ASC"), (0, 0), &[]).unwrap().len();
+    let rivers = count(&conn);
+
+    conn.set(SecurityModel {
+        id: 0,
+        depth: river.depth + 1.0,
+        ..river.clone()
+    });
+    assert_eq!(
+        conn.insert().unwrap_err().to_string(),
+        conflict_message(river.id)
+    );
+    assert_eq!(conn.upsert().unwrap(), 1);
+    *conn.model = None;
+    assert_eq!(count(&conn), rivers);
+    assert_eq!(conn.find_by_id(10).unwrap().depth, river.depth + 1.0);
+    let entries = audit::entries(&conn.instance, Some(10), None, None).unwrap();
+    assert_eq!(entries.last().unwrap().action, AuditAction::Update);
+
+    // Written differently, so only the report finds it.
+    conn.set(SecurityModel {
+        id: 0,
+        name: format!(" {} ", river.name),
+        ..river.clone()
+    });
+    assert_eq!(conn.upsert().unwrap(), 1);
+    *conn.model = None;
+    assert_eq!(count(&conn), rivers + 1);
+    let groups = duplicate::groups(&conn.instance).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].similarity, Similarity::Spelling);
+    assert_eq!(groups[0].rivers[0].id, 10);
+
+    for (start, end) in [("K0+000", "K1+000"), ("K0+500", "K2+000")].iter() {
+        conn.set(SecurityModel {
+            id: 0,
+            name: String::from("重叠河"),
+            start: String::from(*start),
+            end: String::from(*end),
+            ..river.clone()
+        });
+        conn.insert().unwrap();
+    }
+    *conn.model = None;
+    let groups = duplicate::groups(&conn.instance).unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[1].similarity, Similarity::Overlapping);
+    assert_eq!(groups[1].rivers[0].name, "重叠河");
+
+    // Copies stored before the identity index wait for the user to merge them.
+    let version: u32 = conn
+        .instance
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    conn.instance
+        .execute_batch(&format!(
+            "DROP INDEX {};
+            INSERT INTO rivers(
+                level, name, area, start, end, start_chainage, end_chainage, river_width,
+                elevation, ratio, line, allow, safe, channel_width
+            )
+            SELECT
+                level, name, area, start, end, start_chainage, end_chainage, river_width,
+                elevation, ratio, line, allow, safe, channel_width
+            FROM rivers WHERE id=10;
+            INSERT INTO inspections(river_id, time, depth, threshold, dredging)
+                SELECT last_insert_rowid(), time, depth, threshold, dredging
+                FROM inspections WHERE river_id=10;
+            PRAGMA user_version = {};",
+            duplicate::IDENTITY_INDEX,
+            version - 1
+        ))
+        .unwrap();
+    drop(conn);
+    let mut conn = DbConn::<SecurityModel>::open(&path).unwrap();
+    assert!(!duplicate::is_enforced(&conn.instance).unwrap());
+    assert!(recycle::deleted_rivers(&conn.instance).unwrap().is_empty());
+    assert_eq!(count(&conn), rivers + 4);
+    let groups = duplicate::groups(&conn.instance).unwrap();
+    let identical = groups
+        .iter()
+        .find(|group| group.similarity == Similarity::Identical)
+        .unwrap();
+    assert_eq!(identical.rivers[0].id, 10);
+    let copy = identical.rivers.last().unwrap().id;
+    assert!(identical.rivers.last().unwrap().is_same_river(&river));
+
+    // Until then new copies are still refused and upserts update the first.
+    conn.set(SecurityModel {
+        id: 0,
+        ..river.clone()
+    });
+    assert_eq!(
+        conn.insert().unwrap_err().to_string(),
+        conflict_message(river.id)
+    );
+    conn.set(SecurityModel {
+        id: 0,
+        depth: river.depth + 2.0,
+        ..river.clone()
+    });
+    assert_eq!(conn.upsert().unwrap(), 1);
+    *conn.model = None;
+    assert_eq!(conn.find_by_id(10).unwrap().depth, river.depth + 2.0);
+
+    // Nor can a river be renamed into one, while the copies stay editable.
+    let spelled = identical.rivers[1].clone();
+    assert_ne!(spelled.id, copy);
+    conn.set(SecurityModel {
+        name: river.name.clone(),
+        ..spelled
+    });
+    assert_eq!(
+        conn.update().unwrap_err().to_string(),
+        conflict_message(river.id)
+    );
+    conn.set(SecurityModel {
+        id: copy,
+        depth: river.depth + 3.0,
+        ..river.clone()
+    });
+    assert_eq!(conn.update().unwrap(), 1);
+    *conn.model = None;
+
+    assert!(duplicate::merge(&conn, 10, &[copy]).unwrap());
+    assert!(duplicate::is_enforced(&conn.instance).unwrap());
+    assert!(conn.find_by_id(copy).is_err());
+    let deleted = recycle::deleted_rivers(&conn.instance).unwrap();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].model.id, copy);
+    assert!(recycle::restore(&conn, copy).is_err());
+    assert!(duplicate::merge(&conn, 10, &[10]).is_err());
+}
+
+#[test]
+fn repeated_rows_are_matched_by_name_area_and_ends() {
+    let table = import::parse_json(
+        r#"[
+            {"河道名称": "清河", "河道所属辖区": "城区", "河道起点": "K0+000", "河道终点": "K1+000"},
+            {"河道名称": "清河", "河道所属辖区": "城区", "河道起点": "K1+000", "河道终点": "K2+000"},
+            {"河道名称": "清河", "河道所属辖区": "城区", "河道起点": "K0+000", "河道终点": "K1+000"}
+        ]"#,
+    )
+    .unwrap();
+    let mut preview = import::preview::<SecurityModel>(&table, None);
+    import::check_duplicates(&mut preview);
+    let repeated = |index: usize| {
+        preview.rows[index]
+            .issues
+            .iter()
+            .any(|issue| issue.message.contains("为同一河道"))
+    };
+    assert!(!repeated(1));
+    assert!(repeated(2));
+}
+
+#[test]
+fn stale_ids_do_not_rename_rivers() {
+    let scratch = Scratch::new("stale");
+    let conn = DbConn::<SecurityModel>::open(scratch.path("water-resources.db")).unwrap();
+    let river = conn.find_by_id(10).unwrap();
+    let table = import::parse_json(
+        r#"[
+            {"编号": 10, "河道名称": "别的河", "淤积深度": 9},
+            {"编号": 10, "淤积深度": 9}
+        ]"#,
+    )
+    .unwrap();
+    let preview = import::preview::<SecurityModel>(&table, None);
+    let resolve = |index: usize| {
+        import::resolve(
+            &conn,
+            &preview.rows[index],
+            import::ConflictPolicy::Overwrite,
+        )
+    };
+
+    match resolve(0).action {
+        Action::Reject(message) => assert!(message.contains(&river.name), "{}", message),
+        _ => panic!("a stale id renamed river 10"),
+    }
+    let update = resolve(1);
+    assert!(matches!(update.action, Action::Update));
+    assert_eq!(update.model.id, 10);
+    assert!(update.model.is_same_river(&river));
+}
+
+#[test]
+fn unreadable_numbers_are_errors() {
+    let table = import::parse_json(
+        r#"[
+            {"河道名称": "清河", "边坡比": "陡", "河槽宽度(m)": " "}
+        ]"#,
+    )
+    .unwrap();
+    let preview = import::preview::<SecurityModel>(&table, None);
+    let issues = &preview.rows[0].issues;
+    let ratio = issues
+        .iter()
+        .find(|issue| issue.column == "边坡比")
+        .unwrap();
+    assert_eq!(ratio.severity, Severity::Error);
+    assert!(issues.iter().all(|issue| issue.column != "河槽宽度(m)"));
+}
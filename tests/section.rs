@@ -0,0 +1,64 @@
+use wrs_nwg::section::{self, Section, Shape, THRESHOLD_COLOR};
+
+fn section(threshold: f32) -> Section {
+    Section {
+        top_width: 30.0,
+        bottom_width: 16.0,
+        bed: 12.5,
+        height: 3.5,
+        flood: 3.0,
+        silt: 1.2,
+        threshold,
+    }
+}
+
+fn texts(shapes: &[Shape]) -> Vec<&str> {
+    shapes
+        .iter()
+        .filter_map(|shape| match shape {
+            Shape::Text { text, .. } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn sketch_labels_levels_and_freeboard() {
+    let shapes = section(2.0).sketch();
+    let texts = texts(&shapes);
+    for expected in [
+        "堤顶 ▽16",
+        "设计河底 ▽12.5",
+        "设计洪水位 ▽15.5",
+        "安全超高 0.5 m",
+        "淤积阈值 T = 2 m",
+        "淤积深度 d = 1.2 m",
+    ]
+    .iter()
+    {
+        assert!(
+            texts.contains(expected),
+            "{} missing from {:?}",
+            expected,
+            texts
+        );
+    }
+    assert!(shapes.iter().any(|shape| matches!(
+        shape,
+        Shape::Line { stroke, dashed: true, .. } if *stroke == THRESHOLD_COLOR
+    )));
+
+    let svg = section::svg(&shapes);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("stroke-dasharray"));
+}
+
+#[test]
+fn sketch_only_labels_a_threshold_out_of_range() {
+    let shapes = section(40.0).sketch();
+    assert!(!shapes.iter().any(|shape| matches!(
+        shape,
+        Shape::Line { stroke, .. } if *stroke == THRESHOLD_COLOR
+    )));
+    assert!(texts(&shapes).contains(&"（超出图幅）"));
+}